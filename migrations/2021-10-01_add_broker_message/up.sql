@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS public.broker_message (
+    id SERIAL PRIMARY KEY,
+    payload TEXT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS public.broker_event (
+    id BIGSERIAL PRIMARY KEY,
+    topic TEXT NOT NULL,
+    target TEXT NOT NULL,
+    prefix BOOLEAN NOT NULL DEFAULT FALSE,
+    payload TEXT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS broker_event_topic_id ON public.broker_event (topic, id);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS public.notification (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES public."user"(id) ON DELETE CASCADE,
+    kind INTEGER NOT NULL,
+    actor_id INTEGER NULL REFERENCES public."user"(id) ON DELETE SET NULL,
+    puzzle_id INTEGER NULL REFERENCES public.puzzle(id) ON DELETE CASCADE,
+    target_id INTEGER NULL,
+    read BOOLEAN NOT NULL DEFAULT FALSE,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS notification_user_id_read ON public.notification (user_id, read);
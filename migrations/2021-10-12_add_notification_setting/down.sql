@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS public.notification_profile;
+DROP TABLE IF EXISTS public.notification_setting;
+ALTER TABLE public.notification DROP COLUMN IF EXISTS hidden;
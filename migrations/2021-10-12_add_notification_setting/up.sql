@@ -0,0 +1,17 @@
+ALTER TABLE public.notification ADD COLUMN IF NOT EXISTS hidden BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS public.notification_setting (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES public."user"(id) ON DELETE CASCADE,
+    kind INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    UNIQUE (user_id, kind, channel)
+);
+
+CREATE TABLE IF NOT EXISTS public.notification_profile (
+    user_id INTEGER PRIMARY KEY REFERENCES public."user"(id) ON DELETE CASCADE,
+    digest_frequency INTEGER NOT NULL DEFAULT 0,
+    digest_sent TIMESTAMPTZ NULL,
+    webhook_url TEXT NULL
+);
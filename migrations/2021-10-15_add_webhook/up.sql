@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS public.webhook (
+    id SERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events INTEGER[] NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS public.webhook_delivery (
+    id SERIAL PRIMARY KEY,
+    webhook_id INTEGER NOT NULL REFERENCES public.webhook(id) ON DELETE CASCADE,
+    event INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    status INTEGER NOT NULL DEFAULT 0,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_error TEXT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered TIMESTAMPTZ NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_delivery_status_next_attempt
+    ON public.webhook_delivery (status, next_attempt);
@@ -0,0 +1,13 @@
+CREATE OR REPLACE FUNCTION public.update_puzzle_modified() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+  if OLD.status = 0 and NEW.status <> 0 then
+    NEW.modified = now();
+  end if;
+  return NEW;
+end;
+$$;
+
+DROP INDEX IF EXISTS public.puzzle_solved_at;
+ALTER TABLE public.puzzle DROP COLUMN IF EXISTS solved_at;
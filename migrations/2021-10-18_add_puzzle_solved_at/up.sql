@@ -0,0 +1,23 @@
+ALTER TABLE public.puzzle ADD COLUMN IF NOT EXISTS solved_at TIMESTAMPTZ NULL;
+
+-- `modified` used to be set only when the puzzle is solved, and is the best
+-- estimate of the solve time available.
+UPDATE public.puzzle SET solved_at = modified WHERE status <> 0 AND solved_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS puzzle_solved_at ON public.puzzle (solved_at);
+
+-- `modified` is bumped on every update, while `solved_at` is only set once the
+-- puzzle leaves `Undergoing`. Values set explicitly by the update are kept.
+CREATE OR REPLACE FUNCTION public.update_puzzle_modified() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+begin
+  if NEW.modified = OLD.modified then
+    NEW.modified = now();
+  end if;
+  if OLD.status = 0 and NEW.status <> 0 and NEW.solved_at is null then
+    NEW.solved_at = now();
+  end if;
+  return NEW;
+end;
+$$;
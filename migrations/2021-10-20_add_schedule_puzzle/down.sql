@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS public.schedule_scheduled;
+
+ALTER TABLE public.schedule DROP COLUMN IF EXISTS puzzle_id;
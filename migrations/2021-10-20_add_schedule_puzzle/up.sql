@@ -0,0 +1,5 @@
+-- Draft puzzles to be published at `scheduled`
+ALTER TABLE public.schedule ADD COLUMN IF NOT EXISTS puzzle_id INTEGER NULL UNIQUE
+    REFERENCES public.puzzle(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS schedule_scheduled ON public.schedule (scheduled);
@@ -0,0 +1,13 @@
+-- Text fields of a puzzle before each update changing them
+CREATE TABLE IF NOT EXISTS public.puzzle_revision (
+    id SERIAL PRIMARY KEY,
+    puzzle_id INTEGER NOT NULL REFERENCES public.puzzle(id) ON DELETE CASCADE,
+    -- The user making the change
+    user_id INTEGER NULL REFERENCES public."user"(id) ON DELETE SET NULL,
+    content TEXT NOT NULL,
+    solution TEXT NOT NULL,
+    memo TEXT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS puzzle_revision_puzzle_id ON public.puzzle_revision (puzzle_id);
@@ -0,0 +1,11 @@
+ALTER TABLE public.puzzle ADD COLUMN IF NOT EXISTS content_image BYTEA NULL;
+
+-- Only the images not moved into the image store yet can be restored inline
+UPDATE public.puzzle SET content_image = image_pending.data
+FROM public.image_pending
+WHERE image_pending.image_id = puzzle.content_image_id;
+
+ALTER TABLE public.puzzle DROP COLUMN IF EXISTS content_image_id;
+
+DROP TABLE IF EXISTS public.image_pending;
+DROP TABLE IF EXISTS public.image;
@@ -0,0 +1,47 @@
+-- Images kept in the image store, addressed by the SHA-256 of their content
+CREATE TABLE IF NOT EXISTS public.image (
+    id SERIAL PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    -- The uploader
+    user_id INTEGER NULL REFERENCES public."user"(id) ON DELETE SET NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Image data waiting to be moved into the image store, which is done by the
+-- server on startup
+CREATE TABLE IF NOT EXISTS public.image_pending (
+    image_id INTEGER PRIMARY KEY REFERENCES public.image(id) ON DELETE CASCADE,
+    data BYTEA NOT NULL
+);
+
+ALTER TABLE public.puzzle ADD COLUMN IF NOT EXISTS content_image_id INTEGER NULL
+    REFERENCES public.image(id) ON DELETE SET NULL;
+
+-- Move the inline images, deduplicated by their hashes. The content type is
+-- detected when they are moved into the image store.
+INSERT INTO public.image (hash, content_type, size, user_id)
+SELECT DISTINCT ON (hash) hash, 'application/octet-stream', length(content_image), user_id
+FROM (
+    SELECT encode(sha256(content_image), 'hex') AS hash, content_image, user_id, id
+    FROM public.puzzle
+    WHERE content_image IS NOT NULL
+) AS inline
+ORDER BY hash, id
+ON CONFLICT (hash) DO NOTHING;
+
+INSERT INTO public.image_pending (image_id, data)
+SELECT DISTINCT ON (image.id) image.id, puzzle.content_image
+FROM public.puzzle
+INNER JOIN public.image ON image.hash = encode(sha256(puzzle.content_image), 'hex')
+WHERE puzzle.content_image IS NOT NULL
+ORDER BY image.id
+ON CONFLICT (image_id) DO NOTHING;
+
+UPDATE public.puzzle SET content_image_id = image.id
+FROM public.image
+WHERE puzzle.content_image IS NOT NULL
+    AND image.hash = encode(sha256(puzzle.content_image), 'hex');
+
+ALTER TABLE public.puzzle DROP COLUMN IF EXISTS content_image;
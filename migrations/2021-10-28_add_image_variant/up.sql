@@ -0,0 +1,12 @@
+-- Resized variants of the images, kept in the image store along with them
+CREATE TABLE IF NOT EXISTS public.image_variant (
+    id SERIAL PRIMARY KEY,
+    image_id INTEGER NOT NULL REFERENCES public.image(id) ON DELETE CASCADE,
+    -- Maximum width and height the variant is rendered within
+    size INTEGER NOT NULL,
+    format INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    byte_size INTEGER NOT NULL,
+    UNIQUE (image_id, size, format)
+);
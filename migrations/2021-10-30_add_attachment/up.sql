@@ -0,0 +1,29 @@
+-- Ordered images attached to puzzles, hints, dialogue answers and comments.
+-- Exactly the column of the target is set, so that the attachments are deleted
+-- along with it.
+CREATE TABLE IF NOT EXISTS public.attachment (
+    id SERIAL PRIMARY KEY,
+    image_id INTEGER NOT NULL REFERENCES public.image(id) ON DELETE CASCADE,
+    -- 0: puzzle content, 1: puzzle solution, 2: hint, 3: dialogue answer, 4: comment
+    target INTEGER NOT NULL,
+    puzzle_id INTEGER NULL REFERENCES public.puzzle(id) ON DELETE CASCADE,
+    hint_id INTEGER NULL REFERENCES public.hint(id) ON DELETE CASCADE,
+    dialogue_id INTEGER NULL REFERENCES public.dialogue(id) ON DELETE CASCADE,
+    comment_id INTEGER NULL REFERENCES public.comment(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,
+    alt_text TEXT NOT NULL DEFAULT '',
+    -- The user attaching the image
+    user_id INTEGER NULL REFERENCES public."user"(id) ON DELETE SET NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (
+        (target IN (0, 1)) = (puzzle_id IS NOT NULL)
+        AND (target = 2) = (hint_id IS NOT NULL)
+        AND (target = 3) = (dialogue_id IS NOT NULL)
+        AND (target = 4) = (comment_id IS NOT NULL)
+    )
+);
+
+CREATE INDEX IF NOT EXISTS attachment_puzzle_id ON public.attachment (puzzle_id);
+CREATE INDEX IF NOT EXISTS attachment_hint_id ON public.attachment (hint_id);
+CREATE INDEX IF NOT EXISTS attachment_dialogue_id ON public.attachment (dialogue_id);
+CREATE INDEX IF NOT EXISTS attachment_comment_id ON public.attachment (comment_id);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS public.collection_editor;
+DROP TABLE IF EXISTS public.collection_puzzle;
+DROP TABLE IF EXISTS public.collection;
@@ -0,0 +1,35 @@
+-- Puzzle series, themed sets and curated lists
+CREATE TABLE IF NOT EXISTS public.collection (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES public."user"(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    -- Private collections are only visible to the owner and the editors
+    public BOOLEAN NOT NULL DEFAULT TRUE,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    modified TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS collection_user_id ON public.collection (user_id);
+
+-- Puzzles of the collections in order
+CREATE TABLE IF NOT EXISTS public.collection_puzzle (
+    id SERIAL PRIMARY KEY,
+    collection_id INTEGER NOT NULL REFERENCES public.collection(id) ON DELETE CASCADE,
+    puzzle_id INTEGER NOT NULL REFERENCES public.puzzle(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (collection_id, puzzle_id)
+);
+
+CREATE INDEX IF NOT EXISTS collection_puzzle_puzzle_id ON public.collection_puzzle (puzzle_id);
+
+-- Users allowed to manage the puzzles of the collections besides the owners
+CREATE TABLE IF NOT EXISTS public.collection_editor (
+    id SERIAL PRIMARY KEY,
+    collection_id INTEGER NOT NULL REFERENCES public.collection(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES public."user"(id) ON DELETE CASCADE,
+    UNIQUE (collection_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS collection_editor_user_id ON public.collection_editor (user_id);
@@ -0,0 +1,13 @@
+-- Users sharing the rights of the authors of the puzzles.
+-- role 0: co-author, who can also edit the puzzle and its hints,
+-- role 1: answerer, who can only answer the questions.
+CREATE TABLE IF NOT EXISTS public.puzzle_coauthor (
+    id SERIAL PRIMARY KEY,
+    puzzle_id INTEGER NOT NULL REFERENCES public.puzzle(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES public."user"(id) ON DELETE CASCADE,
+    role INTEGER NOT NULL DEFAULT 0,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (puzzle_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS puzzle_coauthor_user_id ON public.puzzle_coauthor (user_id);
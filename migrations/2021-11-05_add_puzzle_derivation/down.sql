@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS public.puzzle_derivation;
+
+ALTER TABLE public.license
+    DROP COLUMN IF EXISTS no_derivatives,
+    DROP COLUMN IF EXISTS share_alike;
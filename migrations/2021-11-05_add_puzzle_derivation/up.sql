@@ -0,0 +1,20 @@
+-- Terms of the licenses on derived puzzles
+ALTER TABLE public.license
+    ADD COLUMN IF NOT EXISTS no_derivatives BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS share_alike BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE public.license SET no_derivatives = TRUE
+WHERE name IN ('CC BY-ND 4.0', 'CC BY-NC-ND 4.0');
+UPDATE public.license SET share_alike = TRUE
+WHERE name IN ('CC BY-SA 4.0', 'CC BY-NC-SA 4.0');
+
+-- Puzzles derived from other puzzles, i.e. remixes
+CREATE TABLE IF NOT EXISTS public.puzzle_derivation (
+    puzzle_id INTEGER PRIMARY KEY REFERENCES public.puzzle(id) ON DELETE CASCADE,
+    -- The attribution is kept after the source is deleted
+    source_id INTEGER NULL REFERENCES public.puzzle(id) ON DELETE SET NULL,
+    attribution TEXT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS puzzle_derivation_source_id ON public.puzzle_derivation (source_id);
@@ -0,0 +1,14 @@
+-- Users who uploaded each image. The same image is stored once, so that it may
+-- have been uploaded by several users, who are the ones allowed to use it.
+CREATE TABLE IF NOT EXISTS public.image_upload (
+    image_id INTEGER NOT NULL REFERENCES public.image(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES public."user"(id) ON DELETE CASCADE,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (image_id, user_id)
+);
+
+INSERT INTO public.image_upload (image_id, user_id, created)
+SELECT id, user_id, created
+FROM public.image
+WHERE user_id IS NOT NULL
+ON CONFLICT DO NOTHING;
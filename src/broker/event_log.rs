@@ -0,0 +1,123 @@
+//! Durable log of the broker events, from which subscribers resuming with an event id
+//! replay the events they missed.
+//!
+//! The log is bounded to the latest `EVENT_LOG_MAX_SIZE` events.
+//!
+//! Events are appended one at a time under an advisory lock, so that their ids are
+//! committed in order. Otherwise a subscriber replaying the events after an id could miss
+//! one committed later with a lower id.
+use diesel::{dsl::min, prelude::*, sql_types::BigInt};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use crate::context::GlobalCtx;
+use crate::schema::broker_event;
+
+use super::{EventId, Key, Target};
+
+const DEFAULT_MAX_SIZE: EventId = 10000;
+/// Old events are pruned every time this number of events are appended on the instance
+const PRUNE_INTERVAL: EventId = 1000;
+/// Key of the advisory lock taken while appending an event
+const APPEND_LOCK: i64 = 0x6369_6e64_795f_6576;
+
+/// Number of events appended on the instance
+static APPENDED: AtomicI64 = AtomicI64::new(0);
+
+lazy_static! {
+    static ref MAX_SIZE: EventId = dotenv::var("EVENT_LOG_MAX_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_MAX_SIZE);
+}
+
+/// Appends an event to the log and returns its id.
+pub fn append(
+    ctx: &GlobalCtx,
+    topic: &str,
+    target: &Target,
+    payload: &str,
+) -> anyhow::Result<EventId> {
+    let conn = ctx.get_conn()?;
+    let (key, prefix) = match target {
+        Target::Default => ("", false),
+        Target::Key(key) => (key.as_str(), false),
+        Target::Prefix(prefix) => (prefix.as_str(), true),
+    };
+
+    let id = conn.transaction::<EventId, diesel::result::Error, _>(|| {
+        // Held until the commit, so that no event with a lower id is committed later
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+            .bind::<BigInt, _>(APPEND_LOCK)
+            .execute(&conn)?;
+        diesel::insert_into(broker_event::table)
+            .values((
+                broker_event::topic.eq(topic),
+                broker_event::target.eq(key),
+                broker_event::prefix.eq(prefix),
+                broker_event::payload.eq(payload),
+            ))
+            .returning(broker_event::id)
+            .get_result(&conn)
+    })?;
+
+    // Counted rather than taken from the ids, which may skip any number on rollbacks
+    if APPENDED.fetch_add(1, Ordering::Relaxed) % PRUNE_INTERVAL == 0 {
+        diesel::delete(broker_event::table.filter(broker_event::id.le(id - *MAX_SIZE)))
+            .execute(&conn)?;
+    }
+
+    Ok(id)
+}
+
+/// Payloads of the events after `since` which subscribers with the key receive, in order.
+///
+/// Returns `None` if some of the events are already pruned from the log.
+pub fn events_since(
+    ctx: &GlobalCtx,
+    topic: &str,
+    key: &Key,
+    since: EventId,
+) -> anyhow::Result<Option<Vec<(EventId, String)>>> {
+    let conn = ctx.get_conn()?;
+
+    let oldest: Option<EventId> = broker_event::table
+        .select(min(broker_event::id))
+        .first(&conn)?;
+    if let Some(oldest) = oldest {
+        if since + 1 < oldest {
+            return Ok(None);
+        }
+    }
+
+    let events: Vec<(EventId, String, bool, String)> = broker_event::table
+        .filter(broker_event::topic.eq(topic))
+        .filter(broker_event::id.gt(since))
+        .filter(
+            broker_event::target
+                .eq(key)
+                .or(broker_event::prefix.eq(true)),
+        )
+        .order(broker_event::id.asc())
+        .select((
+            broker_event::id,
+            broker_event::target,
+            broker_event::prefix,
+            broker_event::payload,
+        ))
+        .load(&conn)?;
+
+    Ok(Some(
+        events
+            .into_iter()
+            .filter(|(_, target, prefix, _)| {
+                if *prefix {
+                    key.starts_with(target.as_str())
+                } else {
+                    target == key
+                }
+            })
+            .map(|(id, _, _, payload)| (id, payload))
+            .collect(),
+    ))
+}
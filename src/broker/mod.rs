@@ -0,0 +1,475 @@
+use chrono::{Date, Duration, Local};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::{PhantomData, Unpin};
+use std::sync::{Mutex, RwLock};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use crate::context::GlobalCtx;
+
+mod event_log;
+mod postgres;
+
+pub use postgres::PgBackend;
+
+const DEFAULT_CHANNEL_CAPACITY: usize = 64;
+
+pub struct SubscribePair {
+    pub tx: Box<dyn Any + Send>,
+    /// Number of the subscription streams alive
+    pub receiver_count: fn(&(dyn Any + Send)) -> usize,
+    pub updated: Date<Local>,
+}
+
+impl SubscribePair {
+    pub fn new<T: BrokerMessage>(tx: broadcast::Sender<Event<T>>) -> Self {
+        SubscribePair {
+            tx: Box::new(tx),
+            receiver_count: receiver_count::<T>,
+            updated: Local::today(),
+        }
+    }
+}
+
+fn receiver_count<T: BrokerMessage>(tx: &(dyn Any + Send)) -> usize {
+    tx.downcast_ref::<broadcast::Sender<Event<T>>>()
+        .map(|tx| tx.receiver_count())
+        .unwrap_or(0)
+}
+
+type Key = String;
+
+/// Monotonically increasing id of the events persisted in the event log
+pub type EventId = i64;
+
+/// A message delivered to the subscription streams
+#[derive(Clone, Debug)]
+pub struct Event<T> {
+    /// Id of the event, which is only assigned to durable messages
+    pub id: Option<EventId>,
+    pub msg: T,
+}
+
+/// A message that can be published with the broker.
+pub trait BrokerMessage:
+    Serialize + DeserializeOwned + Sync + Send + Unpin + Clone + 'static
+{
+    /// Name identifying the message type across server instances.
+    const TOPIC: &'static str;
+    /// Whether the message should be relayed to other server instances.
+    const RELAY: bool = true;
+    /// Whether the message should be persisted in the event log, so that subscribers
+    /// resuming with an event id can replay it.
+    const DURABLE: bool = false;
+
+    /// Message telling a subscriber lagging behind that it missed some messages and
+    /// should refetch the data.
+    ///
+    /// Messages without it are snapshots, for which only the latest one matters.
+    fn resync() -> Option<Self> {
+        None
+    }
+}
+
+/// Subscription streams a message is published to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Target {
+    /// Streams subscribed without a key
+    Default,
+    /// Streams subscribed with the key
+    Key(Key),
+    /// Streams subscribed with a key starting with the prefix
+    Prefix(Key),
+}
+
+/// A serialized message relayed between server instances
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Packet {
+    /// Instance publishing the message
+    pub origin: u64,
+    pub topic: String,
+    pub target: Target,
+    /// Id of the event if the message is durable
+    pub event_id: Option<EventId>,
+    pub payload: serde_json::Value,
+}
+
+/// Function called with the serialized messages of a topic published on the current
+/// instance, along with the target and the event id.
+pub type Listener = fn(&Target, Option<EventId>, &serde_json::Value);
+
+/// Backend relaying messages to other server instances.
+///
+/// Subscribers on the current instance always receive messages in-process. Packets
+/// received from other instances should be passed to `dispatch`.
+pub trait BrokerBackend: Send + Sync {
+    /// Whether messages are relayed to other instances at all.
+    fn is_remote(&self) -> bool;
+    /// Relays the packet to other instances.
+    fn relay(&self, packet: Packet);
+    /// Cleans up resources held by the backend periodically.
+    fn cleanup(&self) {}
+}
+
+/// The default backend, which only delivers messages inside the process.
+pub struct MemoryBackend;
+
+impl BrokerBackend for MemoryBackend {
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    fn relay(&self, _packet: Packet) {}
+}
+
+lazy_static! {
+    static ref SUBSCRIPTIONS: Mutex<HashMap<TypeId, HashMap<Key, SubscribePair>>> =
+        Default::default();
+    static ref BACKEND: RwLock<Box<dyn BrokerBackend>> = RwLock::new(Box::new(MemoryBackend));
+    /// Functions delivering packets of a topic to the local subscribers
+    static ref HANDLERS: Mutex<HashMap<&'static str, fn(Packet)>> = Default::default();
+    /// Listeners of the messages published on the current instance
+    static ref LISTENERS: RwLock<HashMap<&'static str, Vec<Listener>>> = Default::default();
+    /// Random identifier of the current instance
+    static ref INSTANCE_ID: u64 = rand::random();
+    /// Context to access the event log with
+    static ref CONTEXT: RwLock<Option<GlobalCtx>> = RwLock::new(None);
+    /// Number of messages buffered for each subscription stream
+    static ref CHANNEL_CAPACITY: usize = dotenv::var("SUBSCRIPTION_CHANNEL_CAPACITY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|capacity| *capacity > 0)
+        .unwrap_or(DEFAULT_CHANNEL_CAPACITY);
+}
+
+/// Sets up the broker backend specified with `BROKER_BACKEND`.
+pub fn init(ctx: &GlobalCtx) {
+    *CONTEXT.write().unwrap() = Some(ctx.clone());
+
+    let backend = dotenv::var("BROKER_BACKEND").unwrap_or_else(|_| "memory".to_string());
+    match backend.as_str() {
+        "memory" => {}
+        "postgres" => {
+            *BACKEND.write().unwrap() = Box::new(PgBackend::start(ctx.clone()));
+        }
+        _ => panic!("Invalid BROKER_BACKEND env var: {}", backend),
+    };
+    info!("Broker backend: {}", backend);
+}
+
+/// Delivers a packet from another instance to the local subscribers.
+pub fn dispatch(packet: Packet) {
+    if packet.origin == *INSTANCE_ID {
+        return;
+    }
+    let handler = HANDLERS.lock().unwrap().get(packet.topic.as_str()).copied();
+    if let Some(handler) = handler {
+        handler(packet);
+    }
+}
+
+fn deliver_packet<T: BrokerMessage>(packet: Packet) {
+    match serde_json::from_value::<T>(packet.payload) {
+        Ok(msg) => deliver(
+            &packet.target,
+            Event {
+                id: packet.event_id,
+                msg,
+            },
+        ),
+        Err(err) => warn!("Broker: invalid payload of `{}`: {}", packet.topic, err),
+    }
+}
+
+fn register<T: BrokerMessage>() {
+    HANDLERS
+        .lock()
+        .unwrap()
+        .entry(T::TOPIC)
+        .or_insert(deliver_packet::<T> as fn(Packet));
+}
+
+fn deliver<T: BrokerMessage>(target: &Target, msg: Event<T>) {
+    match target {
+        Target::Default => {
+            with_senders_to_if_exists::<T, _, _>(Key::default(), |tx| {
+                tx.send(msg).ok();
+            });
+        }
+        Target::Key(key) => {
+            with_senders_to_if_exists::<T, _, _>(key.to_owned(), |tx| {
+                tx.send(msg).ok();
+            });
+        }
+        Target::Prefix(prefix) => {
+            let mut map = SUBSCRIPTIONS.lock().unwrap();
+            let submap = map
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Default::default());
+            submap
+                .iter_mut()
+                .filter(|(key, _)| key.starts_with(prefix.as_str()))
+                .for_each(|(_, sp)| {
+                    let today = Local::today();
+                    if sp.updated != today {
+                        sp.updated = today;
+                    };
+                    let tx = sp.tx.downcast_ref::<broadcast::Sender<Event<T>>>().unwrap();
+                    tx.send(msg.clone()).ok();
+                });
+        }
+    }
+}
+
+fn send<T: BrokerMessage>(target: Target, msg: T) {
+    let backend = BACKEND.read().unwrap();
+    let relay = T::RELAY && backend.is_remote();
+    let listeners = LISTENERS
+        .read()
+        .unwrap()
+        .get(T::TOPIC)
+        .cloned()
+        .unwrap_or_default();
+    let payload = if T::DURABLE || relay || !listeners.is_empty() {
+        match serde_json::to_value(&msg) {
+            Ok(payload) => Some(payload),
+            Err(err) => {
+                warn!("Broker: unable to serialize `{}`: {}", T::TOPIC, err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut event_id = None;
+    if let Some(payload) = payload {
+        if T::DURABLE {
+            if let Some(ctx) = CONTEXT.read().unwrap().as_ref() {
+                match event_log::append(ctx, T::TOPIC, &target, &payload.to_string()) {
+                    Ok(id) => event_id = Some(id),
+                    Err(err) => warn!("Broker: unable to log `{}`: {}", T::TOPIC, err),
+                }
+            }
+        }
+        for listener in listeners {
+            listener(&target, event_id, &payload);
+        }
+        if relay {
+            backend.relay(Packet {
+                origin: *INSTANCE_ID,
+                topic: T::TOPIC.to_string(),
+                target: target.clone(),
+                event_id,
+                payload,
+            });
+        }
+    }
+
+    deliver(&target, Event { id: event_id, msg });
+}
+
+/// Events of the topic after `since` which subscribers with the key receive.
+///
+/// Starts with `T::resync()` if some of them are not in the event log anymore.
+fn replay<T: BrokerMessage>(key: &Key, since: EventId) -> Vec<Event<T>> {
+    let ctx = match CONTEXT.read().unwrap().as_ref() {
+        Some(ctx) => ctx.clone(),
+        None => return Vec::new(),
+    };
+    let resync = || {
+        T::resync()
+            .map(|msg| Event { id: None, msg })
+            .into_iter()
+            .collect()
+    };
+
+    match event_log::events_since(&ctx, T::TOPIC, key, since) {
+        Ok(Some(events)) => events
+            .into_iter()
+            .filter_map(|(id, payload)| match serde_json::from_str(&payload) {
+                Ok(msg) => Some(Event { id: Some(id), msg }),
+                Err(err) => {
+                    warn!("Broker: invalid logged payload of `{}`: {}", T::TOPIC, err);
+                    None
+                }
+            })
+            .collect(),
+        Ok(None) => resync(),
+        Err(err) => {
+            warn!("Broker: unable to replay `{}`: {}", T::TOPIC, err);
+            resync()
+        }
+    }
+}
+
+fn with_senders_to<T, SP, F>(key: Key, f: F) -> SP
+where
+    T: BrokerMessage,
+    F: FnOnce(&broadcast::Sender<Event<T>>) -> SP,
+{
+    let mut map = SUBSCRIPTIONS.lock().unwrap();
+    let submap = map
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| Default::default());
+    let sp = submap.entry(key).or_insert_with(|| {
+        let (tx, _) = broadcast::channel::<Event<T>>(*CHANNEL_CAPACITY);
+        SubscribePair::new(tx)
+    });
+    let today = Local::today();
+    if sp.updated != today {
+        sp.updated = today;
+    };
+    let tx = sp.tx.downcast_ref::<broadcast::Sender<Event<T>>>().unwrap();
+    f(tx)
+}
+
+fn with_senders_to_if_exists<T, SP, F>(key: Key, f: F) -> Option<SP>
+where
+    T: BrokerMessage,
+    F: FnOnce(&broadcast::Sender<Event<T>>) -> SP,
+{
+    let mut map = SUBSCRIPTIONS.lock().unwrap();
+    let type_id = TypeId::of::<T>();
+    if map.contains_key(&type_id) {
+        let submap = map.get_mut(&type_id).unwrap();
+        if submap.contains_key(&key) {
+            let sp = submap.get_mut(&key).unwrap();
+            let today = Local::today();
+            if sp.updated != today {
+                sp.updated = today;
+            };
+            let tx = sp.tx.downcast_ref::<broadcast::Sender<Event<T>>>().unwrap();
+            Some(f(tx))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+/// Turns the receiver into a stream, replacing the messages missed with `T::resync()`.
+fn into_stream<T: BrokerMessage>(
+    rx: broadcast::Receiver<Event<T>>,
+) -> impl Stream<Item = Event<T>> {
+    BroadcastStream::new(rx).filter_map(|item| async move {
+        match item {
+            Ok(msg) => Some(msg),
+            Err(BroadcastStreamRecvError::Lagged(count)) => {
+                debug!(
+                    "Broker: subscriber of `{}` missed {} messages",
+                    T::TOPIC,
+                    count
+                );
+                T::resync().map(|msg| Event { id: None, msg })
+            }
+        }
+    })
+}
+
+/// A simple broker delivering messages to the subscription streams.
+///
+/// Messages are relayed to other server instances with the configured backend.
+pub struct CindyBroker<T>(PhantomData<T>);
+
+impl<T: BrokerMessage> CindyBroker<T> {
+    /// Publish a message that all subscription streams can receive.
+    pub fn publish(msg: T) {
+        send(Target::Default, msg);
+    }
+
+    /// Subscribe to the message of the specified type and returns a `Stream`.
+    ///
+    /// Only messages published after subscribing are received.
+    pub fn subscribe() -> impl Stream<Item = Event<T>> {
+        register::<T>();
+        with_senders_to::<T, _, _>(Key::default(), |tx| into_stream(tx.subscribe()))
+    }
+
+    /// Publish a message that all subscription streams can receive with a given key.
+    pub fn publish_to(key: Key, msg: T) {
+        send(Target::Key(key), msg);
+    }
+
+    /// Publish a message that all subscription streams can receive with a key starting with
+    /// the given prefix.
+    pub fn publish_to_prefix(prefix: Key, msg: T) {
+        send(Target::Prefix(prefix), msg);
+    }
+
+    /// Subscribe to the message of the specified type with a given key and returns a `Stream`.
+    pub fn subscribe_to(key: Key) -> impl Stream<Item = Event<T>> {
+        register::<T>();
+        with_senders_to::<T, _, _>(key, |tx| into_stream(tx.subscribe()))
+    }
+
+    /// Subscribe to the message of the specified type with a given key, replaying the
+    /// events after `since` from the event log before the live ones.
+    pub fn subscribe_to_since(key: Key, since: Option<EventId>) -> BoxStream<'static, Event<T>> {
+        // Subscribe before reading the log, so that no event is lost in between.
+        let live = Self::subscribe_to(key.clone());
+        let since = match since {
+            Some(since) => since,
+            None => return live.boxed(),
+        };
+
+        let events = replay::<T>(&key, since);
+        let last_id = events
+            .iter()
+            .filter_map(|event| event.id)
+            .max()
+            .unwrap_or(since);
+        futures::stream::iter(events)
+            .chain(live.filter(move |event| {
+                let fresh = event.id.map(|id| id > last_id).unwrap_or(true);
+                async move { fresh }
+            }))
+            .boxed()
+    }
+}
+
+/// Calls the listener with the messages of the specified type published on the current
+/// instance.
+///
+/// Messages relayed from other instances are not passed to the listener, so that each
+/// message is handled once across all instances.
+pub fn listen<T: BrokerMessage>(listener: Listener) {
+    LISTENERS
+        .write()
+        .unwrap()
+        .entry(T::TOPIC)
+        .or_default()
+        .push(listener);
+}
+
+pub fn cleanup() {
+    BACKEND.read().unwrap().cleanup();
+
+    let mut map = SUBSCRIPTIONS.lock().unwrap();
+    let today = Local::today();
+    let env_max_cache_days = dotenv::var("SUBSCRIPTION_MAX_CACHE_TIME")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3);
+    let max_cache_time = Duration::days(env_max_cache_days);
+
+    for (_, submap) in map.iter_mut() {
+        let keys: Vec<Key> = submap
+            .keys()
+            .into_iter()
+            .map(|key| key.to_owned())
+            .collect();
+        for key in keys {
+            let sp = &submap[&key];
+            // Channels still subscribed to should be kept, otherwise subscribers stop
+            // receiving messages published to a newly created one.
+            if today - sp.updated > max_cache_time && (sp.receiver_count)(&*sp.tx) == 0 {
+                submap.remove(&key);
+            }
+        }
+    }
+}
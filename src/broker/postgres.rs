@@ -0,0 +1,137 @@
+//! Broker backend relaying messages between server instances with Postgres
+//! `LISTEN`/`NOTIFY`.
+//!
+//! `NOTIFY` payloads are limited to 8000 bytes, so larger packets are stored in the
+//! `broker_message` table and only their ids are notified.
+use anyhow::anyhow;
+use diesel::{prelude::*, sql_types::Text};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio_postgres::{AsyncMessage, NoTls};
+
+use crate::context::GlobalCtx;
+use crate::schema::broker_message;
+
+use super::{dispatch, BrokerBackend, Packet};
+
+/// Channel to listen and notify
+const CHANNEL: &str = "cindy_broker";
+/// Packets larger than this are stored in the `broker_message` table
+const MAX_PAYLOAD_SIZE: usize = 7900;
+/// Prefix of the payload referring to a row in the `broker_message` table
+const REF_PREFIX: &str = "#";
+/// Interval to reconnect after the listener connection is lost
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct PgBackend {
+    ctx: GlobalCtx,
+}
+
+impl PgBackend {
+    /// Creates the backend and spawns a task listening to the packets from other instances.
+    pub fn start(ctx: GlobalCtx) -> Self {
+        let listener_ctx = ctx.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = listen(&listener_ctx).await {
+                    warn!("Broker listener: {}", err);
+                }
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+            }
+        });
+
+        Self { ctx }
+    }
+
+    fn notify(&self, packet: &Packet) -> anyhow::Result<()> {
+        let conn = self.ctx.get_conn()?;
+        let mut payload = serde_json::to_string(packet)?;
+
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            let id: i32 = diesel::insert_into(broker_message::table)
+                .values(broker_message::payload.eq(&payload))
+                .returning(broker_message::id)
+                .get_result(&conn)?;
+            payload = format!("{}{}", REF_PREFIX, id);
+        }
+
+        diesel::sql_query("SELECT pg_notify($1, $2)")
+            .bind::<Text, _>(CHANNEL)
+            .bind::<Text, _>(&payload)
+            .execute(&conn)?;
+
+        Ok(())
+    }
+}
+
+impl BrokerBackend for PgBackend {
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    fn relay(&self, packet: Packet) {
+        if let Err(err) = self.notify(&packet) {
+            warn!("Broker: unable to relay `{}`: {}", packet.topic, err);
+        }
+    }
+
+    fn cleanup(&self) {
+        let result = self.ctx.get_conn().and_then(|conn| {
+            diesel::sql_query("DELETE FROM broker_message WHERE created < now() - interval '1 day'")
+                .execute(&conn)
+                .map_err(anyhow::Error::from)
+        });
+        if let Err(err) = result {
+            warn!("Broker: unable to clean up broker_message: {}", err);
+        }
+    }
+}
+
+/// Listens to the channel until the connection is lost.
+async fn listen(ctx: &GlobalCtx) -> anyhow::Result<()> {
+    let database_url = dotenv::var("DATABASE_URL")?;
+    let (client, mut connection) = tokio_postgres::connect(&database_url, NoTls).await?;
+
+    // The connection has to be polled to make progress, and notifications are only
+    // available from it.
+    let (tx, mut rx) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if tx.unbounded_send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    client.batch_execute(&format!("LISTEN {}", CHANNEL)).await?;
+    info!("Broker listening to channel `{}`", CHANNEL);
+
+    while let Some(message) = rx.next().await {
+        if let AsyncMessage::Notification(notification) = message? {
+            if let Err(err) = receive(ctx, notification.payload()) {
+                warn!("Broker: invalid notification: {}", err);
+            }
+        }
+    }
+
+    Err(anyhow!("Connection closed"))
+}
+
+fn receive(ctx: &GlobalCtx, payload: &str) -> anyhow::Result<()> {
+    let packet: Packet = if let Some(id) = payload.strip_prefix(REF_PREFIX) {
+        let id: i32 = id.parse()?;
+        let conn = ctx.get_conn()?;
+        let payload: String = broker_message::table
+            .filter(broker_message::id.eq(id))
+            .select(broker_message::payload)
+            .first(&conn)?;
+        serde_json::from_str(&payload)?
+    } else {
+        serde_json::from_str(payload)?
+    };
+
+    dispatch(packet);
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use actix_web::http::HeaderMap;
 use anyhow::{Context, Result};
 use diesel::pg::PgConnection;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
@@ -40,6 +41,23 @@ pub struct RequestCtx {
 }
 
 impl RequestCtx {
+    /// Builds the request context from the authorization headers of a http request.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let token = headers.get("Authorization").and_then(|value| {
+            value
+                .to_str()
+                .ok()
+                // Drop `Bearer `
+                .and_then(|v| v.splitn(2, ' ').nth(1))
+                .map(|v| v.to_string())
+        });
+        let admin_secret = headers
+            .get("X-CINDY-ADMIN-SECRET")
+            .and_then(|value| value.to_str().map(|v| v.to_owned()).ok());
+
+        Self::default().with_token(token).with_secret(admin_secret)
+    }
+
     pub fn with_token(mut self, token: Option<String>) -> Self {
         self.jwt_payload = token.and_then(|token| parse_jwt(&token).ok());
         self
@@ -0,0 +1,270 @@
+//! Digest emails of the notifications, sent daily or weekly as set in the notification
+//! profile of each user.
+//!
+//! Only the notification kinds with the email channel enabled are included. Notifications
+//! read in-app before the digest is sent are left out.
+use chrono::Utc;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+use crate::context::GlobalCtx;
+use crate::mail::{self, Mail};
+use crate::models::notification::*;
+use crate::models::*;
+use crate::schema::{notification, notification_profile, notification_setting, puzzle, user};
+
+/// Interval to look for the digests due
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Spawns the task sending the digests due.
+pub fn init(ctx: GlobalCtx) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Both the queries and the mail transport are blocking
+            let ctx = ctx.clone();
+            let result = tokio::task::spawn_blocking(move || send_digests(&ctx)).await;
+            match result {
+                Ok(Err(err)) => error!("Digest: {}", err),
+                Err(err) => error!("Digest: {}", err),
+                Ok(Ok(())) => {}
+            }
+        }
+    });
+}
+
+/// Sends the digests due for all users.
+pub fn send_digests(ctx: &GlobalCtx) -> anyhow::Result<()> {
+    let conn = ctx.get_conn()?;
+    let now = Utc::now();
+
+    let profiles: Vec<NotificationProfile> = notification_profile::table
+        .filter(notification_profile::digest_frequency.ne(DigestFrequency::Never))
+        .load(&conn)?;
+
+    for profile in profiles {
+        let since = match digest_since(&profile, now) {
+            Some(since) => since,
+            None => continue,
+        };
+
+        if let Err(err) = send_digest(&conn, &profile, since) {
+            warn!(
+                "Digest: unable to send to user {}: {}",
+                profile.user_id, err
+            );
+            continue;
+        }
+
+        diesel::update(notification_profile::table)
+            .filter(notification_profile::user_id.eq(profile.user_id))
+            .set(notification_profile::digest_sent.eq(now))
+            .execute(&conn)?;
+    }
+
+    Ok(())
+}
+
+fn send_digest(
+    conn: &PgConnection,
+    profile: &NotificationProfile,
+    since: Timestamptz,
+) -> anyhow::Result<()> {
+    let kinds: Vec<NotificationKind> = notification_setting::table
+        .filter(notification_setting::user_id.eq(profile.user_id))
+        .filter(notification_setting::channel.eq(NotificationChannel::Email))
+        .filter(notification_setting::enabled.eq(true))
+        .select(notification_setting::kind)
+        .load(conn)?;
+    if kinds.is_empty() {
+        return Ok(());
+    }
+
+    let notifications: Vec<Notification> = notification::table
+        .filter(notification::user_id.eq(profile.user_id))
+        .filter(notification::read.eq(false))
+        .filter(notification::created.gt(since))
+        .filter(notification::kind.eq_any(kinds))
+        .order(notification::created.asc())
+        .load(conn)?;
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let puzzle_ids: Vec<ID> = notifications.iter().filter_map(|n| n.puzzle_id).collect();
+    let titles: HashMap<ID, String> = puzzle::table
+        .filter(puzzle::id.eq_any(puzzle_ids))
+        .select((puzzle::id, puzzle::title))
+        .load::<(ID, String)>(conn)?
+        .into_iter()
+        .collect();
+
+    let (nickname, email): (String, String) = user::table
+        .filter(user::id.eq(profile.user_id))
+        .select((user::nickname, user::email))
+        .first(conn)?;
+
+    match digest_mail(&nickname, email, &notifications, &titles) {
+        Some(mail) => mail::send(&mail),
+        None => Ok(()),
+    }
+}
+
+/// Start of the period the digest of the profile covers, or `None` if it is not due.
+fn digest_since(profile: &NotificationProfile, now: Timestamptz) -> Option<Timestamptz> {
+    let period = profile.digest_frequency.period()?;
+    match profile.digest_sent {
+        Some(digest_sent) if now - digest_sent < period => None,
+        Some(digest_sent) => Some(digest_sent),
+        None => Some(now - period),
+    }
+}
+
+/// Batches the notifications into a digest mail, or `None` if there are none.
+fn digest_mail(
+    nickname: &str,
+    email: String,
+    notifications: &[Notification],
+    titles: &HashMap<ID, String>,
+) -> Option<Mail> {
+    if notifications.is_empty() {
+        return None;
+    }
+
+    let lines: Vec<String> = notifications
+        .iter()
+        .map(|notification| {
+            let title = notification
+                .puzzle_id
+                .and_then(|puzzle_id| titles.get(&puzzle_id))
+                .map(|title| format!(" ({})", title))
+                .unwrap_or_default();
+            format!(
+                "- [{}] {}{}",
+                notification.created.format("%Y-%m-%d %H:%M"),
+                describe(notification.kind),
+                title
+            )
+        })
+        .collect();
+
+    Some(Mail {
+        to: email,
+        subject: format!("You have {} new notifications", notifications.len()),
+        body: format!(
+            "Hi {},\r\n\r\nHere is what happened since your last digest:\r\n\r\n{}\r\n",
+            nickname,
+            lines.join("\r\n")
+        ),
+    })
+}
+
+fn describe(kind: NotificationKind) -> &'static str {
+    match kind {
+        NotificationKind::Answered => "Your question was answered",
+        NotificationKind::Hint => "You received a hint",
+        NotificationKind::Comment => "Your puzzle was commented",
+        NotificationKind::Star => "Your puzzle was starred",
+        NotificationKind::Award => "You were granted an award",
+        NotificationKind::DazeWarning => "Your puzzle is going to be dazed soon",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn profile(
+        frequency: DigestFrequency,
+        digest_sent: Option<Timestamptz>,
+    ) -> NotificationProfile {
+        NotificationProfile {
+            user_id: 1,
+            digest_frequency: frequency,
+            digest_sent,
+            webhook_url: None,
+        }
+    }
+
+    fn notification(id: ID, kind: NotificationKind, puzzle_id: Option<ID>) -> Notification {
+        Notification {
+            id,
+            user_id: 1,
+            kind,
+            actor_id: None,
+            puzzle_id,
+            target_id: None,
+            read: false,
+            created: format!("2021-10-01T12:{:02}:00Z", id).parse().unwrap(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn digest_is_due_after_the_period() {
+        let now: Timestamptz = "2021-10-08T00:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            digest_since(&profile(DigestFrequency::Never, None), now),
+            None
+        );
+        assert_eq!(
+            digest_since(&profile(DigestFrequency::Daily, None), now),
+            Some(now - Duration::days(1))
+        );
+        let recent = now - Duration::hours(2);
+        assert_eq!(
+            digest_since(&profile(DigestFrequency::Daily, Some(recent)), now),
+            None
+        );
+        assert_eq!(
+            digest_since(&profile(DigestFrequency::Weekly, Some(recent)), now),
+            None
+        );
+        let old = now - Duration::days(8);
+        assert_eq!(
+            digest_since(&profile(DigestFrequency::Weekly, Some(old)), now),
+            Some(old)
+        );
+    }
+
+    #[test]
+    fn digest_batches_the_notifications() {
+        let mut titles = HashMap::new();
+        titles.insert(3, "Turtle soup".to_string());
+        let notifications = vec![
+            notification(1, NotificationKind::Answered, Some(3)),
+            notification(2, NotificationKind::Award, None),
+        ];
+
+        let mail = digest_mail(
+            "alice",
+            "alice@example.com".to_string(),
+            &notifications,
+            &titles,
+        )
+        .expect("The digest should not be empty");
+        assert_eq!(mail.to, "alice@example.com");
+        assert_eq!(mail.subject, "You have 2 new notifications");
+        assert!(mail.body.starts_with("Hi alice,"));
+        assert!(mail
+            .body
+            .contains("- [2021-10-01 12:01] Your question was answered (Turtle soup)\r\n"));
+        assert!(mail
+            .body
+            .contains("- [2021-10-01 12:02] You were granted an award\r\n"));
+    }
+
+    #[test]
+    fn digest_is_skipped_without_notifications() {
+        assert!(digest_mail(
+            "alice",
+            "alice@example.com".to_string(),
+            &[],
+            &HashMap::new()
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,241 @@
+use async_graphql::{self, guard::Guard, Context, InputObject, Object};
+use diesel::prelude::*;
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::*;
+use crate::schema::{attachment, comment, dialogue, hint, puzzle};
+
+#[derive(Default)]
+pub struct AttachmentMutation;
+
+/// Asserts the user is allowed to attach images to the target, i.e. the author of the
+/// comment, one of the answerers of the puzzle for answers, or one of the authors of the
+/// puzzle otherwise.
+fn target_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    target: AttachmentTarget,
+    target_id: ID,
+) -> async_graphql::Result<()> {
+    let puzzle: Puzzle = match target {
+        AttachmentTarget::PuzzleContent | AttachmentTarget::PuzzleSolution => {
+            puzzle::table.filter(puzzle::id.eq(target_id)).first(conn)?
+        }
+        AttachmentTarget::Hint => hint::table
+            .inner_join(puzzle::table)
+            .filter(hint::id.eq(target_id))
+            .select(puzzle::all_columns)
+            .first(conn)?,
+        AttachmentTarget::DialogueAnswer => {
+            let puzzle = dialogue::table
+                .inner_join(puzzle::table)
+                .filter(dialogue::id.eq(target_id))
+                .select(puzzle::all_columns)
+                .first(conn)?;
+            return puzzle_answerer_guard(ctx, conn, &puzzle);
+        }
+        AttachmentTarget::Comment => {
+            let user_id = comment::table
+                .filter(comment::id.eq(target_id))
+                .select(comment::user_id)
+                .first(conn)?;
+            return user_id_guard(ctx, user_id);
+        }
+    };
+
+    puzzle_author_guard(ctx, conn, &puzzle)
+}
+
+/// Asserts the user is allowed to modify the attachment.
+fn attachment_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    id: ID,
+) -> async_graphql::Result<Attachment> {
+    let attachment: Attachment = attachment::table
+        .filter(attachment::id.eq(id))
+        .limit(1)
+        .first(conn)?;
+    let target_id = attachment
+        .puzzle_id
+        .or(attachment.hint_id)
+        .or(attachment.dialogue_id)
+        .or(attachment.comment_id)
+        .unwrap_or_default();
+    target_guard(ctx, conn, attachment.target, target_id)?;
+
+    Ok(attachment)
+}
+
+#[derive(InputObject, Debug)]
+pub struct CreateAttachmentInput {
+    pub image_id: ID,
+    pub target: AttachmentTarget,
+    /// Id of the puzzle, hint, dialogue or comment
+    pub target_id: ID,
+    /// Position among the attachments of the target, after the last one by default
+    pub position: Option<i32>,
+    #[graphql(default)]
+    pub alt_text: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "attachment"]
+pub struct CreateAttachmentData {
+    pub image_id: ID,
+    pub target: i32,
+    pub puzzle_id: Option<ID>,
+    pub hint_id: Option<ID>,
+    pub dialogue_id: Option<ID>,
+    pub comment_id: Option<ID>,
+    pub position: i32,
+    pub alt_text: String,
+    pub user_id: Option<ID>,
+}
+
+#[derive(InputObject, Debug)]
+pub struct UpdateAttachmentInput {
+    pub position: Option<i32>,
+    pub alt_text: Option<String>,
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "attachment"]
+pub struct UpdateAttachmentData {
+    pub position: Option<i32>,
+    pub alt_text: Option<String>,
+}
+
+impl From<UpdateAttachmentInput> for UpdateAttachmentData {
+    fn from(x: UpdateAttachmentInput) -> Self {
+        Self {
+            position: x.position,
+            alt_text: x.alt_text,
+        }
+    }
+}
+
+#[Object]
+impl AttachmentMutation {
+    /// Attach the image to the puzzle, hint, dialogue answer or comment.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn create_attachment(
+        &self,
+        ctx: &Context<'_>,
+        data: CreateAttachmentInput,
+    ) -> async_graphql::Result<Attachment> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        target_guard(ctx, &conn, data.target, data.target_id)?;
+        image_guard(ctx, &conn, data.image_id)?;
+
+        let position = match data.position {
+            Some(position) => position,
+            None => {
+                let last: Option<i32> = Attachment::load_of(&conn, data.target, data.target_id)?
+                    .last()
+                    .map(|attachment| attachment.position);
+                last.map(|position| position + 1).unwrap_or(0)
+            }
+        };
+
+        let target_id = Some(data.target_id);
+        let mut insert = CreateAttachmentData {
+            image_id: data.image_id,
+            target: data.target as i32,
+            puzzle_id: None,
+            hint_id: None,
+            dialogue_id: None,
+            comment_id: None,
+            position,
+            alt_text: data.alt_text,
+            user_id: reqctx.get_user_id(),
+        };
+        match data.target {
+            AttachmentTarget::PuzzleContent | AttachmentTarget::PuzzleSolution => {
+                insert.puzzle_id = target_id
+            }
+            AttachmentTarget::Hint => insert.hint_id = target_id,
+            AttachmentTarget::DialogueAnswer => insert.dialogue_id = target_id,
+            AttachmentTarget::Comment => insert.comment_id = target_id,
+        };
+
+        let attachment: Attachment = diesel::insert_into(attachment::table)
+            .values(&insert)
+            .get_result(&conn)?;
+
+        Ok(attachment)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn update_attachment(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        set: UpdateAttachmentInput,
+    ) -> async_graphql::Result<Attachment> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        attachment_guard(ctx, &conn, id)?;
+
+        let attachment: Attachment = diesel::update(attachment::table)
+            .filter(attachment::id.eq(id))
+            .set(UpdateAttachmentData::from(set))
+            .get_result(&conn)?;
+
+        Ok(attachment)
+    }
+
+    /// Reorder the attachments of the target as listed. The attachments not listed are
+    /// moved after them.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn reorder_attachments(
+        &self,
+        ctx: &Context<'_>,
+        target: AttachmentTarget,
+        target_id: ID,
+        ids: Vec<ID>,
+    ) -> async_graphql::Result<Vec<Attachment>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        target_guard(ctx, &conn, target, target_id)?;
+
+        let mut attachments = Attachment::load_of(&conn, target, target_id)?;
+        attachments.sort_by_key(|attachment| {
+            ids.iter()
+                .position(|id| *id == attachment.id)
+                .unwrap_or(ids.len())
+        });
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            for (position, attachment) in attachments.iter_mut().enumerate() {
+                attachment.position = position as i32;
+                diesel::update(attachment::table)
+                    .filter(attachment::id.eq(attachment.id))
+                    .set(attachment::position.eq(attachment.position))
+                    .execute(&conn)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(attachments)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn delete_attachment(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Attachment> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        attachment_guard(ctx, &conn, id)?;
+
+        let attachment: Attachment =
+            diesel::delete(attachment::table.filter(attachment::id.eq(id))).get_result(&conn)?;
+
+        Ok(attachment)
+    }
+}
@@ -78,6 +78,41 @@ impl BookmarkQuery {
 
         Ok(result)
     }
+
+    pub async fn bookmark_aggregate(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<Vec<BookmarkFilter>>,
+        group_by: Option<Vec<BookmarkAggregateGroupBy>>,
+        functions: Vec<AggregateFunction>,
+        field: Option<BookmarkAggregateField>,
+    ) -> async_graphql::Result<Vec<BookmarkAggregate>> {
+        use crate::schema::bookmark::dsl::*;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = bookmark.into_boxed();
+        // Drafts are only counted for admins
+        if reqctx.get_role() != Role::Admin {
+            query = query.filter(puzzle_id.eq_any(published_puzzles()));
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+
+        let results = AggregateQuery::<_, BookmarkAggregate>::new(
+            query,
+            group_by.unwrap_or_default(),
+            functions,
+            field,
+        )?
+        .load(&conn)?;
+
+        Ok(results)
+    }
 }
 
 #[derive(InputObject, AsChangeset, Debug)]
@@ -122,6 +157,9 @@ impl BookmarkMutation {
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
             _ => {}
         };
+        if let Some(puzzle_id) = set.puzzle_id {
+            published_guard(&conn, puzzle_id)?;
+        }
 
         let bookmark: Bookmark = diesel::update(bookmark::table)
             .filter(bookmark::id.eq(id))
@@ -153,6 +191,7 @@ impl BookmarkMutation {
             Role::Admin => {}
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
         };
+        published_guard(&conn, data.puzzle_id)?;
 
         let bookmark: Bookmark = diesel::insert_into(bookmark::table)
             .values(&data)
@@ -6,7 +6,7 @@ use diesel::prelude::*;
 use futures::{Stream, StreamExt};
 
 use crate::auth::Role;
-use crate::broker::CindyBroker;
+use crate::broker::{CindyBroker, Event, EventId};
 use crate::context::{GlobalCtx, RequestCtx};
 use crate::models::chatmessage::*;
 use crate::models::*;
@@ -27,11 +27,15 @@ impl ChatmessageQuery {
         id: i32,
     ) -> async_graphql::Result<Chatmessage> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let chatmessage = chatmessage::table
+        let mut query = chatmessage::table
             .filter(chatmessage::id.eq(id))
-            .limit(1)
-            .first(&conn)?;
+            .into_boxed();
+        if let Some(visible) = Chatmessage::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let chatmessage = query.limit(1).first(&conn)?;
 
         Ok(chatmessage)
     }
@@ -47,8 +51,12 @@ impl ChatmessageQuery {
         use crate::schema::chatmessage::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
         let mut query = chatmessage.into_boxed();
+        if let Some(visible) = Chatmessage::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
         if let Some(order) = order {
             query = ChatmessageOrders::new(order).apply_order(query);
         }
@@ -77,8 +85,12 @@ impl ChatmessageQuery {
         use crate::schema::chatmessage::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
         let mut query = chatmessage.into_boxed();
+        if let Some(visible) = Chatmessage::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
         if let Some(filter) = filter {
             if let Some(filter_exp) = filter.as_expression() {
                 query = query.filter(filter_exp)
@@ -180,7 +192,10 @@ impl ChatmessageMutation {
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
-        CindyBroker::publish(ChatmessageSub::Updated(cm_inst, chatmessage.clone()));
+        CindyBroker::publish_to(
+            format!("chatroom<{}>", chatmessage.chatroom_id),
+            ChatmessageSub::Updated(cm_inst, chatmessage.clone()),
+        );
 
         Ok(chatmessage)
     }
@@ -212,7 +227,10 @@ impl ChatmessageMutation {
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
-        CindyBroker::publish(ChatmessageSub::Created(chatmessage.clone()));
+        CindyBroker::publish_to(
+            format!("chatroom<{}>", chatmessage.chatroom_id),
+            ChatmessageSub::Created(chatmessage.clone()),
+        );
 
         Ok(chatmessage)
     }
@@ -229,9 +247,15 @@ impl ChatmessageMutation {
     ) -> async_graphql::Result<Chatmessage> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        let chatmessage = diesel::delete(chatmessage::table.filter(chatmessage::id.eq(id)))
-            .get_result(&conn)
-            .map_err(|err| async_graphql::Error::from(err))?;
+        let chatmessage: Chatmessage =
+            diesel::delete(chatmessage::table.filter(chatmessage::id.eq(id)))
+                .get_result(&conn)
+                .map_err(|err| async_graphql::Error::from(err))?;
+
+        CindyBroker::publish_to(
+            format!("chatroom<{}>", chatmessage.chatroom_id),
+            ChatmessageSub::Deleted(chatmessage.clone()),
+        );
 
         Ok(chatmessage)
     }
@@ -240,15 +264,12 @@ impl ChatmessageMutation {
 #[derive(InputObject, Eq, PartialEq, Clone)]
 pub struct ChatmessageSubFilter {
     id: Option<I32Filtering>,
-    chatroom_id: Option<I32Filtering>,
 }
 
 impl RawFilter<Chatmessage> for ChatmessageSubFilter {
     fn check(&self, item: &Chatmessage) -> bool {
         if let Some(filter) = self.id.as_ref() {
             filter.check(&item.id)
-        } else if let Some(filter) = self.chatroom_id.as_ref() {
-            filter.check(&item.chatroom_id)
         } else {
             true
         }
@@ -259,20 +280,42 @@ impl RawFilter<Chatmessage> for ChatmessageSubFilter {
 impl ChatmessageSubscription {
     pub async fn chatmessage_sub(
         &self,
+        ctx: &Context<'_>,
+        chatroom_id: ID,
         filter: Option<ChatmessageSubFilter>,
-    ) -> impl Stream<Item = Option<ChatmessageSub>> {
-        CindyBroker::<ChatmessageSub>::subscribe().filter(move |cm_sub| {
-            let check = if let Some(filter) = filter.as_ref() {
-                match cm_sub {
-                    Some(ChatmessageSub::Created(cm)) => filter.check(&cm),
-                    Some(ChatmessageSub::Updated(orig, _)) => filter.check(&orig),
-                    None => false,
-                }
-            } else {
-                cm_sub.is_some()
-            };
-
-            async move { check }
-        })
+        since: Option<EventId>,
+    ) -> async_graphql::Result<impl Stream<Item = Event<ChatmessageSub>>> {
+        use crate::schema::chatroom;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let chatroom_inst: Chatroom = chatroom::table
+            .filter(chatroom::id.eq(chatroom_id))
+            .limit(1)
+            .first(&conn)?;
+        if !chatroom_inst.is_visible_to(&conn, reqctx)? {
+            return Err(async_graphql::Error::new(
+                "Chatroom is private and not accessible",
+            ));
+        }
+
+        let key = format!("chatroom<{}>", chatroom_id);
+        Ok(
+            CindyBroker::<ChatmessageSub>::subscribe_to_since(key, since).filter(move |cm_sub| {
+                let check = if let Some(filter) = filter.as_ref() {
+                    match &cm_sub.msg {
+                        ChatmessageSub::Created(cm) => filter.check(&cm),
+                        ChatmessageSub::Updated(orig, _) => filter.check(&orig),
+                        ChatmessageSub::Deleted(cm) => filter.check(&cm),
+                        ChatmessageSub::Resync => true,
+                    }
+                } else {
+                    true
+                };
+
+                async move { check }
+            }),
+        )
     }
 }
@@ -0,0 +1,380 @@
+use async_graphql::{self, guard::Guard, Context, InputObject, Object};
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::collection::*;
+use crate::models::*;
+use crate::schema::{collection, collection_editor, collection_puzzle, puzzle};
+
+#[derive(Default)]
+pub struct CollectionQuery;
+#[derive(Default)]
+pub struct CollectionMutation;
+
+#[Object]
+impl CollectionQuery {
+    pub async fn collection(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let collection: Collection = collection::table
+            .filter(collection::id.eq(id))
+            .limit(1)
+            .first(&conn)?;
+        if !collection.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        Ok(collection)
+    }
+
+    pub async fn collections(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        filter: Option<Vec<CollectionFilter>>,
+        order: Option<Vec<CollectionOrder>>,
+    ) -> async_graphql::Result<Vec<Collection>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = collection::table.into_boxed();
+        if let Some(visible) = Collection::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        if let Some(order) = order {
+            query = CollectionOrders::new(order).apply_order(query);
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        if let Some(offset) = offset {
+            query = query.offset(offset);
+        }
+
+        let collections = query.load::<Collection>(&conn)?;
+
+        Ok(collections)
+    }
+}
+
+/// Asserts the user is the owner of the collection.
+fn owner_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    id: ID,
+) -> async_graphql::Result<Collection> {
+    let collection: Collection = collection::table
+        .filter(collection::id.eq(id))
+        .limit(1)
+        .first(conn)?;
+    user_id_guard(ctx, collection.user_id)?;
+
+    Ok(collection)
+}
+
+/// Asserts the user is the owner or one of the editors of the collection.
+fn editor_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    id: ID,
+) -> async_graphql::Result<Collection> {
+    let reqctx = ctx.data::<RequestCtx>()?;
+
+    let collection: Collection = collection::table
+        .filter(collection::id.eq(id))
+        .limit(1)
+        .first(conn)?;
+    if user_id_guard(ctx, collection.user_id).is_err()
+        && !collection.is_editor(conn, reqctx.get_user_id())?
+    {
+        return Err(async_graphql::Error::new(
+            "Only the owner and the editors can edit the collection",
+        ));
+    }
+
+    Ok(collection)
+}
+
+/// Marks the collection as modified.
+fn touch(conn: &PgConnection, id: ID) -> QueryResult<Collection> {
+    diesel::update(collection::table)
+        .filter(collection::id.eq(id))
+        .set(collection::modified.eq(Utc::now()))
+        .get_result(conn)
+}
+
+#[derive(InputObject, Debug)]
+pub struct UpdateCollectionInput {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub public: Option<bool>,
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "collection"]
+pub struct UpdateCollectionData {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub public: Option<bool>,
+    pub modified: Timestamptz,
+}
+
+impl From<UpdateCollectionInput> for UpdateCollectionData {
+    fn from(x: UpdateCollectionInput) -> Self {
+        Self {
+            title: x.title,
+            description: x.description,
+            public: x.public,
+            modified: Utc::now(),
+        }
+    }
+}
+
+#[derive(InputObject, Insertable)]
+#[table_name = "collection"]
+pub struct CreateCollectionInput {
+    pub user_id: Option<ID>,
+    pub title: String,
+    #[graphql(default)]
+    pub description: String,
+    #[graphql(default = true)]
+    pub public: bool,
+}
+
+#[Object]
+impl CollectionMutation {
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn create_collection(
+        &self,
+        ctx: &Context<'_>,
+        mut data: CreateCollectionInput,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        if let Some(user_id) = data.user_id {
+            user_id_guard(ctx, user_id)?;
+        } else {
+            data.user_id = reqctx.get_user_id();
+        }
+
+        let collection: Collection = diesel::insert_into(collection::table)
+            .values(&data)
+            .get_result(&conn)?;
+
+        Ok(collection)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn update_collection(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        set: UpdateCollectionInput,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        owner_guard(ctx, &conn, id)?;
+
+        let collection: Collection = diesel::update(collection::table)
+            .filter(collection::id.eq(id))
+            .set(UpdateCollectionData::from(set))
+            .get_result(&conn)?;
+
+        Ok(collection)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn delete_collection(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        owner_guard(ctx, &conn, id)?;
+
+        let collection: Collection =
+            diesel::delete(collection::table.filter(collection::id.eq(id))).get_result(&conn)?;
+
+        Ok(collection)
+    }
+
+    /// Add the puzzle to the collection, after the last one by default.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn add_collection_puzzle(
+        &self,
+        ctx: &Context<'_>,
+        collection_id: ID,
+        puzzle_id: ID,
+        position: Option<i32>,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        editor_guard(ctx, &conn, collection_id)?;
+        let puzzle: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(puzzle_id))
+            .limit(1)
+            .first(&conn)?;
+        if !puzzle.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+
+        let position = match position {
+            Some(position) => position,
+            None => {
+                let last: Option<i32> = collection_puzzle::table
+                    .filter(collection_puzzle::collection_id.eq(collection_id))
+                    .select(diesel::dsl::max(collection_puzzle::position))
+                    .first(&conn)?;
+                last.map(|position| position + 1).unwrap_or(0)
+            }
+        };
+
+        diesel::insert_into(collection_puzzle::table)
+            .values((
+                collection_puzzle::collection_id.eq(collection_id),
+                collection_puzzle::puzzle_id.eq(puzzle_id),
+                collection_puzzle::position.eq(position),
+            ))
+            .on_conflict((
+                collection_puzzle::collection_id,
+                collection_puzzle::puzzle_id,
+            ))
+            .do_update()
+            .set(collection_puzzle::position.eq(position))
+            .execute(&conn)?;
+
+        Ok(touch(&conn, collection_id)?)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn remove_collection_puzzle(
+        &self,
+        ctx: &Context<'_>,
+        collection_id: ID,
+        puzzle_id: ID,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        editor_guard(ctx, &conn, collection_id)?;
+
+        diesel::delete(
+            collection_puzzle::table
+                .filter(collection_puzzle::collection_id.eq(collection_id))
+                .filter(collection_puzzle::puzzle_id.eq(puzzle_id)),
+        )
+        .execute(&conn)?;
+
+        Ok(touch(&conn, collection_id)?)
+    }
+
+    /// Reorder the puzzles of the collection as listed. The puzzles not listed are moved
+    /// after them.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn reorder_collection_puzzles(
+        &self,
+        ctx: &Context<'_>,
+        collection_id: ID,
+        puzzle_ids: Vec<ID>,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        editor_guard(ctx, &conn, collection_id)?;
+
+        let mut ids: Vec<ID> = collection_puzzle::table
+            .filter(collection_puzzle::collection_id.eq(collection_id))
+            .order((
+                collection_puzzle::position.asc(),
+                collection_puzzle::id.asc(),
+            ))
+            .select(collection_puzzle::puzzle_id)
+            .load(&conn)?;
+        ids.sort_by_key(|id| {
+            puzzle_ids
+                .iter()
+                .position(|puzzle_id| puzzle_id == id)
+                .unwrap_or(puzzle_ids.len())
+        });
+
+        let collection = conn.transaction::<_, diesel::result::Error, _>(|| {
+            for (position, id) in ids.iter().enumerate() {
+                diesel::update(collection_puzzle::table)
+                    .filter(collection_puzzle::collection_id.eq(collection_id))
+                    .filter(collection_puzzle::puzzle_id.eq(id))
+                    .set(collection_puzzle::position.eq(position as i32))
+                    .execute(&conn)?;
+            }
+            touch(&conn, collection_id)
+        })?;
+
+        Ok(collection)
+    }
+
+    /// Allow the user to manage the puzzles of the collection.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn add_collection_editor(
+        &self,
+        ctx: &Context<'_>,
+        collection_id: ID,
+        user_id: ID,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let collection = owner_guard(ctx, &conn, collection_id)?;
+        if user_id == collection.user_id {
+            return Err(async_graphql::Error::new(
+                "The owner cannot be an editor of the collection",
+            ));
+        }
+
+        diesel::insert_into(collection_editor::table)
+            .values((
+                collection_editor::collection_id.eq(collection_id),
+                collection_editor::user_id.eq(user_id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&conn)?;
+
+        Ok(collection)
+    }
+
+    /// Revoke the editor of the collection, by the owner or the editor themselves.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn remove_collection_editor(
+        &self,
+        ctx: &Context<'_>,
+        collection_id: ID,
+        user_id: ID,
+    ) -> async_graphql::Result<Collection> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let collection: Collection = collection::table
+            .filter(collection::id.eq(collection_id))
+            .limit(1)
+            .first(&conn)?;
+        if reqctx.get_user_id() != Some(user_id) {
+            user_id_guard(ctx, collection.user_id)?;
+        }
+
+        diesel::delete(
+            collection_editor::table
+                .filter(collection_editor::collection_id.eq(collection_id))
+                .filter(collection_editor::user_id.eq(user_id)),
+        )
+        .execute(&conn)?;
+
+        Ok(collection)
+    }
+}
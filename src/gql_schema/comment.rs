@@ -4,9 +4,12 @@ use diesel::prelude::*;
 use crate::auth::Role;
 use crate::context::{GlobalCtx, RequestCtx};
 use crate::models::comment::*;
+use crate::models::notification::NotificationKind;
 use crate::models::*;
 use crate::schema::comment;
 
+use super::notification::{notify, CreateNotificationData};
+
 #[derive(Default)]
 pub struct CommentQuery;
 #[derive(Default)]
@@ -101,6 +104,41 @@ impl CommentQuery {
         Ok(result)
     }
 
+    pub async fn comment_aggregate(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<Vec<CommentFilter>>,
+        group_by: Option<Vec<CommentAggregateGroupBy>>,
+        functions: Vec<AggregateFunction>,
+        field: Option<CommentAggregateField>,
+    ) -> async_graphql::Result<Vec<CommentAggregate>> {
+        use crate::schema::comment::dsl::*;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = comment.into_boxed();
+        // Drafts are only counted for admins
+        if reqctx.get_role() != Role::Admin {
+            query = query.filter(puzzle_id.eq_any(published_puzzles()));
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+
+        let results = AggregateQuery::<_, CommentAggregate>::new(
+            query,
+            group_by.unwrap_or_default(),
+            functions,
+            field,
+        )?
+        .load(&conn)?;
+
+        Ok(results)
+    }
+
     pub async fn user_received_comments(
         &self,
         ctx: &Context<'_>,
@@ -190,6 +228,9 @@ impl CommentMutation {
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
             _ => {}
         };
+        if let Some(puzzle_id) = set.puzzle_id {
+            published_guard(&conn, puzzle_id)?;
+        }
 
         let comment: Comment = diesel::update(comment::table)
             .filter(comment::id.eq(id))
@@ -223,12 +264,30 @@ impl CommentMutation {
             Role::Admin => {}
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
         };
+        published_guard(&conn, data.puzzle_id)?;
 
         let comment: Comment = diesel::insert_into(comment::table)
             .values(&data)
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        // Notify the author of the puzzle
+        {
+            use crate::schema::puzzle;
+
+            let puzzle_inst: Puzzle = puzzle::table
+                .filter(puzzle::id.eq(comment.puzzle_id))
+                .limit(1)
+                .first(&conn)?;
+            notify(
+                &conn,
+                CreateNotificationData::new(puzzle_inst.user_id, NotificationKind::Comment)
+                    .actor_id(Some(comment.user_id))
+                    .puzzle_id(comment.puzzle_id)
+                    .target_id(comment.id),
+            );
+        }
+
         Ok(comment)
     }
 
@@ -7,10 +7,13 @@ use crate::broker::CindyBroker;
 use crate::context::{GlobalCtx, RequestCtx};
 use crate::models::{
     dialogue::*,
+    notification::NotificationKind,
     puzzle_log::{PuzzleLogSub, UnsolvedPuzzleStatsSub},
     *,
 };
-use crate::schema::dialogue;
+use crate::schema::{dialogue, puzzle};
+
+use super::notification::{notify, CreateNotificationData};
 
 #[derive(Default)]
 pub struct DialogueQuery;
@@ -63,6 +66,36 @@ impl DialogueQuery {
         Ok(dialogues)
     }
 
+    pub async fn dialogue_aggregate(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<Vec<DialogueFilter>>,
+        group_by: Option<Vec<DialogueAggregateGroupBy>>,
+        functions: Vec<AggregateFunction>,
+        field: Option<DialogueAggregateField>,
+    ) -> async_graphql::Result<Vec<DialogueAggregate>> {
+        use crate::schema::dialogue::dsl::*;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let mut query = dialogue.into_boxed();
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+
+        let results = AggregateQuery::<_, DialogueAggregate>::new(
+            query,
+            group_by.unwrap_or_default(),
+            functions,
+            field,
+        )?
+        .load(&conn)?;
+
+        Ok(results)
+    }
+
     pub async fn user_max_yami_dialogue_count(
         &self,
         ctx: &Context<'_>,
@@ -231,6 +264,19 @@ impl DialogueMutation {
             Role::User => {
                 assert_eq_guard_msg(set.qno, None, "Setting qno explicitly is prohibited")?;
 
+                // The question is edited by the questioner, and answered by the authors
+                // or the answerers of the puzzle
+                if set.question.is_some() {
+                    user_id_guard(ctx, dialogue_inst.user_id)?;
+                }
+                if set.answer.is_some() || set.is_good.is_some() || set.is_true.is_some() {
+                    let puzzle_inst: Puzzle = puzzle::table
+                        .filter(puzzle::id.eq(dialogue_inst.puzzle_id))
+                        .limit(1)
+                        .first(&conn)?;
+                    puzzle_answerer_guard(ctx, &conn, &puzzle_inst)?;
+                }
+
                 // Update edit times
                 if set.question.is_some() {
                     set.question_edit_times = Some(dialogue_inst.question_edit_times + 1);
@@ -254,13 +300,32 @@ impl DialogueMutation {
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        // Notify the questioner when the question is answered
+        if dialogue_inst.answer.is_empty() && !dialogue.answer.is_empty() {
+            let puzzle_inst: Puzzle = puzzle::table
+                .filter(puzzle::id.eq(dialogue.puzzle_id))
+                .limit(1)
+                .first(&conn)?;
+            let actor_id = if puzzle_inst.anonymous {
+                None
+            } else {
+                Some(puzzle_inst.user_id)
+            };
+            notify(
+                &conn,
+                CreateNotificationData::new(dialogue.user_id, NotificationKind::Answered)
+                    .actor_id(actor_id)
+                    .puzzle_id(dialogue.puzzle_id)
+                    .target_id(dialogue.id),
+            );
+        }
+
         // Update PuzzleLogs
         let puzzle_id = dialogue.puzzle_id;
         let sub = PuzzleLogSub::DialogueUpdated(dialogue_inst, dialogue.clone());
         tokio::spawn(async move {
             let key_starts_with = format!("puzzleLog<{}", puzzle_id);
-            CindyBroker::publish(sub.clone());
-            CindyBroker::publish_to_all(|key| key.starts_with(&key_starts_with), sub);
+            CindyBroker::publish_to_prefix(key_starts_with, sub);
         });
 
         // Update PuzzleLog count in unsolved puzzles
@@ -307,6 +372,9 @@ impl DialogueMutation {
             data.user_id = reqctx.get_user_id();
         };
 
+        // Drafts are not open to questions until they are published
+        published_guard(&conn, data.puzzle_id)?;
+
         // Set qno
         let qno: i64 = dialogue::table
             .filter(dialogue::puzzle_id.eq(data.puzzle_id))
@@ -320,9 +388,8 @@ impl DialogueMutation {
             .map_err(|err| async_graphql::Error::from(err))?;
 
         let key_starts_with = format!("puzzleLog<{}", dialogue.puzzle_id);
-        CindyBroker::publish(PuzzleLogSub::DialogueCreated(dialogue.clone()));
-        CindyBroker::publish_to_all(
-            |key| key.starts_with(&key_starts_with),
+        CindyBroker::publish_to_prefix(
+            key_starts_with,
             PuzzleLogSub::DialogueCreated(dialogue.clone()),
         );
 
@@ -341,10 +408,16 @@ impl DialogueMutation {
     ) -> async_graphql::Result<Dialogue> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        let dialogue = diesel::delete(dialogue::table.filter(dialogue::id.eq(id)))
+        let dialogue: Dialogue = diesel::delete(dialogue::table.filter(dialogue::id.eq(id)))
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        let key_starts_with = format!("puzzleLog<{}", dialogue.puzzle_id);
+        CindyBroker::publish_to_prefix(
+            key_starts_with,
+            PuzzleLogSub::DialogueDeleted(dialogue.clone()),
+        );
+
         Ok(dialogue)
     }
 }
@@ -4,7 +4,7 @@ use diesel::prelude::*;
 use futures::Stream;
 
 use crate::auth::Role;
-use crate::broker::CindyBroker;
+use crate::broker::{CindyBroker, Event, EventId};
 use crate::context::{GlobalCtx, RequestCtx};
 use crate::models::direct_message::*;
 use crate::models::*;
@@ -206,11 +206,25 @@ impl DirectMessageMutation {
     ) -> async_graphql::Result<DirectMessage> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        let direct_message =
+        let direct_message: DirectMessage =
             diesel::delete(direct_message::table.filter(direct_message::id.eq(id)))
                 .get_result(&conn)
                 .map_err(|err| async_graphql::Error::from(err))?;
 
+        let dm = direct_message.clone();
+        tokio::spawn(async move {
+            CindyBroker::publish(DirectMessageSub::Deleted(dm.clone()));
+
+            CindyBroker::publish_to(
+                format!("dm<{}>", dm.sender_id),
+                DirectMessageSub::Deleted(dm.clone()),
+            );
+            CindyBroker::publish_to(
+                format!("dm<{}>", dm.receiver_id),
+                DirectMessageSub::Deleted(dm),
+            );
+        });
+
         Ok(direct_message)
     }
 }
@@ -220,9 +234,10 @@ impl DirectMessageSubscription {
     pub async fn direct_message_sub(
         &self,
         user_id: ID,
-    ) -> impl Stream<Item = Option<DirectMessageSub>> {
+        since: Option<EventId>,
+    ) -> impl Stream<Item = Event<DirectMessageSub>> {
         let key = format!("dm<{}>", &user_id);
 
-        CindyBroker::<DirectMessageSub>::subscribe_to(key)
+        CindyBroker::<DirectMessageSub>::subscribe_to_since(key, since)
     }
 }
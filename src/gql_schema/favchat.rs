@@ -59,6 +59,28 @@ impl FavchatQuery {
     }
 }
 
+/// Asserts the user is allowed to add members to the chatroom, i.e. anyone for public
+/// chatrooms and the owner for private ones.
+fn chatroom_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    chatroom_id: ID,
+) -> async_graphql::Result<Chatroom> {
+    use crate::schema::chatroom;
+
+    let chatroom_inst: Chatroom = chatroom::table
+        .filter(chatroom::id.eq(chatroom_id))
+        .limit(1)
+        .first(conn)?;
+    if chatroom_inst.private && user_id_guard(ctx, chatroom_inst.user_id).is_err() {
+        return Err(async_graphql::Error::new(
+            "Only the owner can add members to the private chatroom",
+        ));
+    }
+
+    Ok(chatroom_inst)
+}
+
 #[derive(InputObject, AsChangeset, Debug)]
 #[table_name = "favchat"]
 pub struct UpdateFavchatInput {
@@ -96,6 +118,12 @@ impl FavchatMutation {
                     .limit(1)
                     .first(&conn)?;
                 user_id_guard(ctx, favchat_inst.user_id)?;
+                if let Some(user_id) = set.user_id {
+                    user_id_guard(ctx, user_id)?;
+                }
+                if let Some(chatroom_id) = set.chatroom_id {
+                    chatroom_guard(ctx, &conn, chatroom_id)?;
+                }
             }
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
             _ => {}
@@ -122,9 +150,12 @@ impl FavchatMutation {
 
         match role {
             Role::User => {
-                // Assert the user is the owner of the puzzle.
+                // Members of private chatrooms are added by the owner only
+                let chatroom_inst = chatroom_guard(ctx, &conn, data.chatroom_id)?;
                 if let Some(user_id) = data.user_id {
-                    user_id_guard(ctx, user_id)?;
+                    if !chatroom_inst.private {
+                        user_id_guard(ctx, user_id)?;
+                    }
                 } else {
                     data.user_id = reqctx.get_user_id();
                 };
@@ -154,12 +185,17 @@ impl FavchatMutation {
 
         match role {
             Role::User => {
-                // User should be the owner
+                // User should be the owner, or the owner of the private chatroom
                 let favchat_inst: Favchat = favchat::table
                     .filter(favchat::id.eq(id))
                     .limit(1)
                     .first(&conn)?;
-                user_id_guard(ctx, favchat_inst.user_id)?;
+                if user_id_guard(ctx, favchat_inst.user_id).is_err() {
+                    let chatroom_inst = chatroom_guard(ctx, &conn, favchat_inst.chatroom_id)?;
+                    if !chatroom_inst.private {
+                        return Err(async_graphql::Error::new("Not the owner of the favchat"));
+                    }
+                }
             }
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
             _ => {}
@@ -5,9 +5,11 @@ use diesel::prelude::*;
 use crate::auth::Role;
 use crate::broker::CindyBroker;
 use crate::context::{GlobalCtx, RequestCtx};
-use crate::models::{hint::*, puzzle_log::PuzzleLogSub, *};
+use crate::models::{hint::*, notification::NotificationKind, puzzle_log::PuzzleLogSub, *};
 use crate::schema::hint;
 
+use super::notification::{notify, CreateNotificationData};
+
 #[derive(Default)]
 pub struct HintQuery;
 #[derive(Default)]
@@ -157,12 +159,12 @@ impl HintMutation {
 
         match role {
             Role::User => {
-                // User should be the owner on update mutation
+                // User should be one of the authors on update mutation
                 let puzzle_inst: Puzzle = puzzle::table
                     .filter(puzzle::id.eq(hint_inst.puzzle_id))
                     .limit(1)
                     .first(&conn)?;
-                user_id_guard(ctx, puzzle_inst.user_id)?;
+                puzzle_author_guard(ctx, &conn, &puzzle_inst)?;
 
                 // Set `modified` to the current time when edited
                 set.edit_times = Some(hint_inst.edit_times + 1);
@@ -182,9 +184,8 @@ impl HintMutation {
             .map_err(|err| async_graphql::Error::from(err))?;
 
         let key_starts_with = format!("puzzleLog<{}", hint.puzzle_id);
-        CindyBroker::publish(PuzzleLogSub::HintUpdated(hint_inst.clone(), hint.clone()));
-        CindyBroker::publish_to_all(
-            |key| key.starts_with(&key_starts_with),
+        CindyBroker::publish_to_prefix(
+            key_starts_with,
             PuzzleLogSub::HintUpdated(hint_inst, hint.clone()),
         );
 
@@ -202,15 +203,16 @@ impl HintMutation {
         let reqctx = ctx.data::<RequestCtx>()?;
         let role = reqctx.get_role();
 
+        // Assert that upstream puzzle exists
+        let puzzle_inst: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(data.puzzle_id))
+            .limit(1)
+            .first(&conn)?;
+
         match role {
             Role::User => {
-                // Assert that upstream puzzle exists
-                let puzzle_inst: Puzzle = puzzle::table
-                    .filter(puzzle::id.eq(data.puzzle_id))
-                    .limit(1)
-                    .first(&conn)?;
-                // Assert the user is the owner of the puzzle.
-                user_id_guard(ctx, puzzle_inst.user_id)?;
+                // Assert the user is one of the authors of the puzzle.
+                puzzle_author_guard(ctx, &conn, &puzzle_inst)?;
             }
             Role::Admin => {}
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
@@ -221,12 +223,23 @@ impl HintMutation {
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        if let Some(receiver_id) = hint.receiver_id {
+            let actor_id = if puzzle_inst.anonymous {
+                None
+            } else {
+                Some(puzzle_inst.user_id)
+            };
+            notify(
+                &conn,
+                CreateNotificationData::new(receiver_id, NotificationKind::Hint)
+                    .actor_id(actor_id)
+                    .puzzle_id(hint.puzzle_id)
+                    .target_id(hint.id),
+            );
+        }
+
         let key_starts_with = format!("puzzleLog<{}", hint.puzzle_id);
-        CindyBroker::publish(PuzzleLogSub::HintCreated(hint.clone()));
-        CindyBroker::publish_to_all(
-            |key| key.starts_with(&key_starts_with),
-            PuzzleLogSub::HintCreated(hint.clone()),
-        );
+        CindyBroker::publish_to_prefix(key_starts_with, PuzzleLogSub::HintCreated(hint.clone()));
 
         Ok(hint)
     }
@@ -239,10 +252,13 @@ impl HintMutation {
     pub async fn delete_hint(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Hint> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        let hint = diesel::delete(hint::table.filter(hint::id.eq(id)))
+        let hint: Hint = diesel::delete(hint::table.filter(hint::id.eq(id)))
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        let key_starts_with = format!("puzzleLog<{}", hint.puzzle_id);
+        CindyBroker::publish_to_prefix(key_starts_with, PuzzleLogSub::HintDeleted(hint.clone()));
+
         Ok(hint)
     }
 }
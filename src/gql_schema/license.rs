@@ -66,6 +66,8 @@ pub struct UpdateLicenseInput {
     pub name: Option<String>,
     pub url: Option<String>,
     pub description: Option<String>,
+    pub no_derivatives: Option<bool>,
+    pub share_alike: Option<bool>,
 }
 
 #[derive(InputObject, Insertable)]
@@ -76,6 +78,8 @@ pub struct CreateLicenseInput {
     pub name: String,
     pub url: String,
     pub description: String,
+    pub no_derivatives: Option<bool>,
+    pub share_alike: Option<bool>,
 }
 
 #[Object]
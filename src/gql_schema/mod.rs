@@ -1,14 +1,19 @@
 use async_graphql::{MergedObject, MergedSubscription, Object, Schema, SimpleObject, Subscription};
 //use futures::lock::Mutex;
 use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 //use std::sync::Arc;
 use std::time::Duration;
 use tokio_stream::wrappers::IntervalStream;
 
+use crate::broker::BrokerMessage;
+
+mod attachment;
 mod award;
 mod bookmark;
 mod chatmessage;
 mod chatroom;
+mod collection;
 mod comment;
 mod dialogue;
 mod direct_message;
@@ -16,18 +21,25 @@ mod dm_read;
 mod favchat;
 mod hint;
 mod license;
+mod notification;
+mod presence;
 mod puzzle;
+mod puzzle_coauthor;
 mod puzzle_log;
 mod puzzle_tag;
+mod schedule;
 mod star;
 mod tag;
 mod user;
 mod user_award;
+mod webhook;
 
+pub use attachment::AttachmentMutation;
 pub use award::{AwardMutation, AwardQuery};
 pub use bookmark::{BookmarkMutation, BookmarkQuery};
 pub use chatmessage::{ChatmessageMutation, ChatmessageQuery, ChatmessageSubscription};
 pub use chatroom::{ChatroomMutation, ChatroomQuery};
+pub use collection::{CollectionMutation, CollectionQuery};
 pub use comment::{CommentMutation, CommentQuery};
 pub use dialogue::{DialogueMutation, DialogueQuery};
 pub use direct_message::{DirectMessageMutation, DirectMessageQuery, DirectMessageSubscription};
@@ -35,13 +47,21 @@ pub use dm_read::{DmReadMutation, DmReadQuery};
 pub use favchat::{FavchatMutation, FavchatQuery};
 pub use hint::{HintMutation, HintQuery};
 pub use license::{LicenseMutation, LicenseQuery};
-pub use puzzle::{PuzzleMutation, PuzzleQuery, PuzzleSubscription};
+pub use notification::{
+    notify, CreateNotificationData, NotificationMutation, NotificationQuery,
+    NotificationSubscription,
+};
+pub use presence::{PresenceMutation, PresenceQuery, PresenceSubscription};
+pub use puzzle::{publish_draft, PuzzleMutation, PuzzleQuery, PuzzleSubscription};
+pub use puzzle_coauthor::PuzzleCoauthorMutation;
 pub use puzzle_log::{PuzzleLogQuery, PuzzleLogSubscription};
 pub use puzzle_tag::{PuzzleTagMutation, PuzzleTagQuery};
+pub use schedule::{ScheduleMutation, ScheduleQuery};
 pub use star::{StarMutation, StarQuery};
 pub use tag::{TagMutation, TagQuery};
 pub use user::{UserMutation, UserQuery};
 pub use user_award::{UserAwardMutation, UserAwardQuery};
+pub use webhook::{WebhookMutation, WebhookQuery};
 
 pub type CindySchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
 
@@ -52,6 +72,7 @@ pub struct QueryRoot(
     BookmarkQuery,
     ChatmessageQuery,
     ChatroomQuery,
+    CollectionQuery,
     CommentQuery,
     DialogueQuery,
     DirectMessageQuery,
@@ -59,21 +80,27 @@ pub struct QueryRoot(
     FavchatQuery,
     HintQuery,
     LicenseQuery,
+    NotificationQuery,
+    PresenceQuery,
     PuzzleLogQuery,
     PuzzleQuery,
     PuzzleTagQuery,
+    ScheduleQuery,
     StarQuery,
     TagQuery,
     UserQuery,
     UserAwardQuery,
+    WebhookQuery,
 );
 
 #[derive(MergedObject, Default)]
 pub struct MutationRoot(
+    AttachmentMutation,
     AwardMutation,
     BookmarkMutation,
     ChatmessageMutation,
     ChatroomMutation,
+    CollectionMutation,
     CommentMutation,
     DialogueMutation,
     DirectMessageMutation,
@@ -81,12 +108,17 @@ pub struct MutationRoot(
     FavchatMutation,
     HintMutation,
     LicenseMutation,
+    NotificationMutation,
+    PresenceMutation,
+    PuzzleCoauthorMutation,
     PuzzleMutation,
     PuzzleTagMutation,
+    ScheduleMutation,
     StarMutation,
     TagMutation,
     UserMutation,
     UserAwardMutation,
+    WebhookMutation,
 );
 
 #[derive(MergedSubscription, Default)]
@@ -94,6 +126,8 @@ pub struct SubscriptionRoot(
     BaseSubscription,
     ChatmessageSubscription,
     DirectMessageSubscription,
+    NotificationSubscription,
+    PresenceSubscription,
     PuzzleLogSubscription,
     PuzzleSubscription,
 );
@@ -104,16 +138,21 @@ struct BaseQuery;
 #[Object]
 impl BaseQuery {
     async fn online_users_count(&self) -> i32 {
-        use crate::broker::online_users_count;
-        online_users_count()
+        crate::presence::online_users().len() as i32
     }
 }
 
-#[derive(Clone, Default, SimpleObject)]
+#[derive(Clone, Default, SimpleObject, Serialize, Deserialize)]
 struct IntervalMsg {
     msg: String,
 }
 
+impl BrokerMessage for IntervalMsg {
+    const TOPIC: &'static str = "interval";
+    // Published by every instance on its own
+    const RELAY: bool = false;
+}
+
 impl IntervalMsg {
     pub fn new(msg: String) -> Self {
         IntervalMsg { msg }
@@ -125,10 +164,7 @@ struct BaseSubscription;
 
 #[Subscription]
 impl BaseSubscription {
-    async fn interval(
-        &self,
-        #[graphql(default = 1)] n: i32,
-    ) -> impl Stream<Item = Option<IntervalMsg>> {
+    async fn interval(&self, #[graphql(default = 1)] n: i32) -> impl Stream<Item = IntervalMsg> {
         use crate::broker::CindyBroker;
 
         tokio::spawn(async move {
@@ -140,6 +176,6 @@ impl BaseSubscription {
             }
         });
 
-        CindyBroker::<IntervalMsg>::subscribe()
+        CindyBroker::<IntervalMsg>::subscribe().map(|event| event.msg)
     }
 }
@@ -0,0 +1,369 @@
+use async_graphql::{self, guard::Guard, Context, MaybeUndefined, Object, Subscription};
+use diesel::{pg::PgConnection, prelude::*};
+use futures::Stream;
+
+use crate::auth::Role;
+use crate::broker::{CindyBroker, Event, EventId};
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::notification::*;
+use crate::models::*;
+use crate::schema::{notification, notification_profile, notification_setting};
+use crate::webhook;
+
+#[derive(Default)]
+pub struct NotificationQuery;
+#[derive(Default)]
+pub struct NotificationMutation;
+#[derive(Default)]
+pub struct NotificationSubscription;
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "notification"]
+pub struct CreateNotificationData {
+    pub user_id: ID,
+    pub kind: i32,
+    pub actor_id: Option<ID>,
+    pub puzzle_id: Option<ID>,
+    pub target_id: Option<ID>,
+    pub hidden: bool,
+}
+
+impl CreateNotificationData {
+    pub fn new(user_id: ID, kind: NotificationKind) -> Self {
+        Self {
+            user_id,
+            kind: kind as i32,
+            actor_id: None,
+            puzzle_id: None,
+            target_id: None,
+            hidden: false,
+        }
+    }
+
+    pub fn actor_id(mut self, actor_id: Option<ID>) -> Self {
+        self.actor_id = actor_id;
+        self
+    }
+
+    pub fn puzzle_id(mut self, puzzle_id: ID) -> Self {
+        self.puzzle_id = Some(puzzle_id);
+        self
+    }
+
+    pub fn target_id(mut self, target_id: ID) -> Self {
+        self.target_id = Some(target_id);
+        self
+    }
+}
+
+/// Channels the user enabled for the notification kind
+fn enabled_channels(
+    conn: &PgConnection,
+    user_id: ID,
+    kind: i32,
+) -> QueryResult<Vec<NotificationChannel>> {
+    let settings: Vec<NotificationSetting> = notification_setting::table
+        .filter(notification_setting::user_id.eq(user_id))
+        .filter(notification_setting::kind.eq(kind))
+        .load(conn)?;
+
+    Ok(NotificationChannel::ALL
+        .iter()
+        .copied()
+        .filter(|channel| {
+            settings
+                .iter()
+                .find(|setting| setting.channel == *channel)
+                .map(|setting| setting.enabled)
+                .unwrap_or_else(|| channel.enabled_by_default())
+        })
+        .collect())
+}
+
+/// Creates a notification and delivers it through the channels the user enabled.
+///
+/// Notifications with the in-app channel disabled are still stored as hidden ones, to be
+/// batched into digest emails. Users are not notified of their own actions. Failures are
+/// only logged, as they should not fail the mutation generating the notification.
+pub fn notify(conn: &PgConnection, mut data: CreateNotificationData) {
+    if data.actor_id == Some(data.user_id) {
+        return;
+    }
+
+    let result = enabled_channels(conn, data.user_id, data.kind).and_then(|channels| {
+        if channels.is_empty() {
+            return Ok(());
+        }
+        data.hidden = !channels.contains(&NotificationChannel::InApp);
+
+        let notification: Notification = diesel::insert_into(notification::table)
+            .values(&data)
+            .get_result(conn)?;
+
+        if channels.contains(&NotificationChannel::Webhook) {
+            let webhook_url: Option<Option<String>> = notification_profile::table
+                .filter(notification_profile::user_id.eq(data.user_id))
+                .select(notification_profile::webhook_url)
+                .first(conn)
+                .optional()?;
+            if let Some(url) = webhook_url.flatten() {
+                webhook::post(url, &notification);
+            }
+        }
+
+        if !notification.hidden {
+            CindyBroker::publish_to(
+                format!("notification<{}>", notification.user_id),
+                NotificationSub::Created(notification),
+            );
+        }
+
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        warn!("Unable to create notification {:?}: {}", data, err);
+    }
+}
+
+/// Id of the user whose notifications are accessed by the request
+fn notification_user_id(reqctx: &RequestCtx) -> async_graphql::Result<ID> {
+    reqctx
+        .get_user_id()
+        .ok_or_else(|| async_graphql::Error::new("User not logged in"))
+}
+
+#[Object]
+impl NotificationQuery {
+    /// Notifications of the user, or of all users for admins.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn notifications(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        filter: Option<Vec<NotificationFilter>>,
+        order: Option<Vec<NotificationOrder>>,
+    ) -> async_graphql::Result<Vec<Notification>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = notification::table
+            .filter(notification::hidden.eq(false))
+            .into_boxed();
+        if let Role::User = reqctx.get_role() {
+            query = query.filter(notification::user_id.eq(notification_user_id(reqctx)?));
+        }
+        if let Some(order) = order {
+            query = NotificationOrders::new(order).apply_order(query);
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        if let Some(offset) = offset {
+            query = query.offset(offset);
+        }
+
+        let notifications = query.load::<Notification>(&conn)?;
+
+        Ok(notifications)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn notification_unread_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        let result = notification::table
+            .filter(notification::user_id.eq(user_id))
+            .filter(notification::read.eq(false))
+            .filter(notification::hidden.eq(false))
+            .count()
+            .get_result::<i64>(&conn)?;
+
+        Ok(result)
+    }
+
+    /// Explicit notification settings of the user.
+    ///
+    /// Without a setting, only the in-app channel is enabled.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn notification_settings(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<NotificationSetting>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        let settings = notification_setting::table
+            .filter(notification_setting::user_id.eq(user_id))
+            .order(notification_setting::id.asc())
+            .load::<NotificationSetting>(&conn)?;
+
+        Ok(settings)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn notification_profile(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<NotificationProfile>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        let profile = notification_profile::table
+            .filter(notification_profile::user_id.eq(user_id))
+            .first::<NotificationProfile>(&conn)
+            .optional()?;
+
+        Ok(profile)
+    }
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "notification_profile"]
+pub struct UpdateNotificationProfileData {
+    pub digest_frequency: Option<i32>,
+    pub webhook_url: Option<Option<String>>,
+}
+
+#[Object]
+impl NotificationMutation {
+    /// Marks the notifications of the user as read, or all of them if `ids` is not set.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn mark_notifications_read(
+        &self,
+        ctx: &Context<'_>,
+        ids: Option<Vec<ID>>,
+    ) -> async_graphql::Result<Vec<Notification>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        let target = notification::table
+            .select(notification::id)
+            .filter(notification::user_id.eq(user_id))
+            .filter(notification::read.eq(false))
+            .filter(notification::hidden.eq(false))
+            .into_boxed();
+        let target = if let Some(ids) = ids {
+            target.filter(notification::id.eq_any(ids))
+        } else {
+            target
+        };
+        let target_ids: Vec<ID> = target.load(&conn)?;
+
+        let notifications: Vec<Notification> = diesel::update(notification::table)
+            .filter(notification::id.eq_any(target_ids))
+            .set(notification::read.eq(true))
+            .get_results(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        for notification in notifications.iter() {
+            CindyBroker::publish_to(
+                format!("notification<{}>", user_id),
+                NotificationSub::Updated(notification.clone()),
+            );
+        }
+
+        Ok(notifications)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn set_notification_setting(
+        &self,
+        ctx: &Context<'_>,
+        kind: NotificationKind,
+        channel: NotificationChannel,
+        enabled: bool,
+    ) -> async_graphql::Result<NotificationSetting> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        let setting = diesel::insert_into(notification_setting::table)
+            .values((
+                notification_setting::user_id.eq(user_id),
+                notification_setting::kind.eq(kind),
+                notification_setting::channel.eq(channel),
+                notification_setting::enabled.eq(enabled),
+            ))
+            .on_conflict((
+                notification_setting::user_id,
+                notification_setting::kind,
+                notification_setting::channel,
+            ))
+            .do_update()
+            .set(notification_setting::enabled.eq(enabled))
+            .get_result(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        Ok(setting)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn update_notification_profile(
+        &self,
+        ctx: &Context<'_>,
+        digest_frequency: Option<DigestFrequency>,
+        webhook_url: MaybeUndefined<String>,
+    ) -> async_graphql::Result<NotificationProfile> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        if let MaybeUndefined::Value(url) = &webhook_url {
+            webhook::resolve_public(url).await.map_err(|err| {
+                async_graphql::Error::new(format!("Invalid webhook url: {}", err))
+            })?;
+        }
+
+        diesel::insert_into(notification_profile::table)
+            .values(notification_profile::user_id.eq(user_id))
+            .on_conflict_do_nothing()
+            .execute(&conn)?;
+
+        let set = UpdateNotificationProfileData {
+            digest_frequency: digest_frequency.map(|frequency| frequency as i32),
+            webhook_url: webhook_url.as_options(),
+        };
+        let profile = if set.digest_frequency.is_none() && set.webhook_url.is_none() {
+            notification_profile::table
+                .filter(notification_profile::user_id.eq(user_id))
+                .first(&conn)?
+        } else {
+            diesel::update(notification_profile::table)
+                .filter(notification_profile::user_id.eq(user_id))
+                .set(set)
+                .get_result(&conn)
+                .map_err(|err| async_graphql::Error::from(err))?
+        };
+
+        Ok(profile)
+    }
+}
+
+#[Subscription]
+impl NotificationSubscription {
+    pub async fn notification_sub(
+        &self,
+        ctx: &Context<'_>,
+        since: Option<EventId>,
+    ) -> async_graphql::Result<impl Stream<Item = Event<NotificationSub>>> {
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = notification_user_id(reqctx)?;
+
+        Ok(CindyBroker::<NotificationSub>::subscribe_to_since(
+            format!("notification<{}>", user_id),
+            since,
+        ))
+    }
+}
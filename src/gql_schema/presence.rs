@@ -0,0 +1,208 @@
+use async_graphql::{self, guard::Guard, Context, Object, Subscription};
+use diesel::{pg::PgConnection, prelude::*};
+use futures::{Stream, StreamExt};
+
+use crate::auth::Role;
+use crate::broker::CindyBroker;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::presence::*;
+use crate::models::*;
+use crate::presence;
+use crate::schema::user;
+
+#[derive(Default)]
+pub struct PresenceQuery;
+#[derive(Default)]
+pub struct PresenceMutation;
+#[derive(Default)]
+pub struct PresenceSubscription;
+
+/// Loads the users with the ids, ordered by id.
+fn load_users(conn: &PgConnection, user_ids: Vec<ID>) -> async_graphql::Result<Vec<User>> {
+    let users = user::table
+        .filter(user::id.eq_any(user_ids))
+        .order(user::id.asc())
+        .load::<User>(conn)?;
+
+    Ok(users)
+}
+
+/// Asserts that the location is visible to the request.
+fn location_guard(
+    conn: &PgConnection,
+    reqctx: &RequestCtx,
+    location: PresenceLocation,
+) -> async_graphql::Result<()> {
+    use crate::schema::{chatroom, puzzle};
+
+    match location {
+        PresenceLocation::Chatroom(chatroom_id) => {
+            let chatroom_inst: Chatroom = chatroom::table
+                .filter(chatroom::id.eq(chatroom_id))
+                .limit(1)
+                .first(conn)?;
+            if !chatroom_inst.is_visible_to(conn, reqctx)? {
+                return Err(async_graphql::Error::new(
+                    "Chatroom is private and not accessible",
+                ));
+            }
+        }
+        // Drafts are not found except by their authors
+        PresenceLocation::Puzzle(puzzle_id) => {
+            let puzzle_inst: Puzzle = puzzle::table
+                .filter(puzzle::id.eq(puzzle_id))
+                .limit(1)
+                .first(conn)?;
+            if !puzzle_inst.is_visible_to(conn, reqctx)? {
+                return Err(diesel::result::Error::NotFound.into());
+            }
+        }
+        PresenceLocation::Site => {}
+    }
+
+    Ok(())
+}
+
+/// Location of typing events, which should be a chatroom or a puzzle.
+fn typing_location(location: PresenceLocationInput) -> async_graphql::Result<PresenceLocation> {
+    match location.into_location()? {
+        PresenceLocation::Site => Err(async_graphql::Error::new(
+            "Either puzzle_id or chatroom_id is required",
+        )),
+        location => Ok(location),
+    }
+}
+
+#[Object]
+impl PresenceQuery {
+    pub async fn online_users(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<User>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        load_users(&conn, presence::online_users())
+    }
+
+    pub async fn puzzle_viewers(
+        &self,
+        ctx: &Context<'_>,
+        puzzle_id: ID,
+    ) -> async_graphql::Result<Vec<User>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let location = PresenceLocation::Puzzle(puzzle_id);
+        location_guard(&conn, reqctx, location)?;
+
+        load_users(&conn, presence::users_at(location))
+    }
+
+    pub async fn chatroom_members_online(
+        &self,
+        ctx: &Context<'_>,
+        chatroom_id: ID,
+    ) -> async_graphql::Result<Vec<User>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let location = PresenceLocation::Chatroom(chatroom_id);
+        location_guard(&conn, reqctx, location)?;
+
+        load_users(&conn, presence::users_at(location))
+    }
+}
+
+#[Object]
+impl PresenceMutation {
+    /// Marks the user as present at the location.
+    ///
+    /// Clients should send it periodically, in an interval shorter than the
+    /// `PRESENCE_TIMEOUT` of the server.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn presence_heartbeat(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] location: PresenceLocationInput,
+    ) -> async_graphql::Result<bool> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = reqctx
+            .get_user_id()
+            .ok_or_else(|| async_graphql::Error::new("User not logged in"))?;
+
+        let location = location.into_location()?;
+        location_guard(&conn, reqctx, location)?;
+
+        presence::heartbeat(user_id, location);
+
+        Ok(true)
+    }
+
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn presence_leave(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] location: PresenceLocationInput,
+    ) -> async_graphql::Result<bool> {
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = reqctx
+            .get_user_id()
+            .ok_or_else(|| async_graphql::Error::new("User not logged in"))?;
+
+        presence::leave(user_id, location.into_location()?);
+
+        Ok(true)
+    }
+
+    /// Notifies that the user is typing in the chatroom or in the questions of the puzzle.
+    ///
+    /// Events sent too frequently, and stop events without a typing event before, are
+    /// dropped, in which case `false` is returned.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn typing(
+        &self,
+        ctx: &Context<'_>,
+        location: PresenceLocationInput,
+        #[graphql(default)] stopped: bool,
+    ) -> async_graphql::Result<bool> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let user_id = reqctx
+            .get_user_id()
+            .ok_or_else(|| async_graphql::Error::new("User not logged in"))?;
+
+        let location = typing_location(location)?;
+        location_guard(&conn, reqctx, location)?;
+
+        Ok(presence::typing(user_id, location, stopped))
+    }
+}
+
+#[Subscription]
+impl PresenceSubscription {
+    pub async fn presence_sub(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] location: PresenceLocationInput,
+    ) -> async_graphql::Result<impl Stream<Item = PresenceSub>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let location = location.into_location()?;
+        location_guard(&conn, reqctx, location)?;
+
+        Ok(CindyBroker::<PresenceSub>::subscribe_to(location.key()).map(|event| event.msg))
+    }
+
+    pub async fn typing_sub(
+        &self,
+        ctx: &Context<'_>,
+        location: PresenceLocationInput,
+    ) -> async_graphql::Result<impl Stream<Item = TypingSub>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let location = typing_location(location)?;
+        location_guard(&conn, reqctx, location)?;
+
+        Ok(CindyBroker::<TypingSub>::subscribe_to(TypingSub::key(location)).map(|event| event.msg))
+    }
+}
@@ -10,13 +10,15 @@ use diesel::{
     sql_types::{self, Integer},
 };
 use futures::{Stream, StreamExt};
+use std::collections::HashMap;
 
 use crate::auth::Role;
-use crate::broker::CindyBroker;
+use crate::broker::{CindyBroker, Event, EventId};
 use crate::context::{GlobalCtx, RequestCtx};
 use crate::models::puzzle::*;
+use crate::models::puzzle_revision::*;
 use crate::models::*;
-use crate::schema::puzzle;
+use crate::schema::{puzzle, puzzle_revision, schedule};
 
 #[derive(Default)]
 pub struct PuzzleQuery;
@@ -29,11 +31,15 @@ pub struct PuzzleSubscription;
 impl PuzzleQuery {
     pub async fn puzzle(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Puzzle> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let puzzle = puzzle::table
+        let puzzle: Puzzle = puzzle::table
             .filter(puzzle::id.eq(id))
             .limit(1)
             .first(&conn)?;
+        if !puzzle.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
 
         Ok(puzzle)
     }
@@ -49,13 +55,17 @@ impl PuzzleQuery {
         use crate::schema::puzzle::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
         let mut query = puzzle.into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
         if let Some(order) = order {
             query = PuzzleOrders::new(order).apply_order(query);
         }
         if let Some(filter) = filter {
-            if let Some(filter_exp) = filter.as_expression() {
+            if let Some(filter_exp) = PuzzleFilter::as_guarded_expression(filter, reqctx) {
                 query = query.filter(filter_exp)
             }
         }
@@ -79,10 +89,14 @@ impl PuzzleQuery {
         use crate::schema::puzzle::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
         let mut query = puzzle.into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
         if let Some(filter) = filter {
-            if let Some(filter_exp) = filter.as_expression() {
+            if let Some(filter_exp) = PuzzleFilter::as_guarded_expression(filter, reqctx) {
                 query = query.filter(filter_exp)
             }
         }
@@ -92,16 +106,56 @@ impl PuzzleQuery {
         Ok(result)
     }
 
+    pub async fn puzzle_aggregate(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<Vec<PuzzleFilter>>,
+        group_by: Option<Vec<PuzzleAggregateGroupBy>>,
+        functions: Vec<AggregateFunction>,
+        field: Option<PuzzleAggregateField>,
+    ) -> async_graphql::Result<Vec<PuzzleAggregate>> {
+        use crate::schema::puzzle::dsl::*;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = puzzle.into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = PuzzleFilter::as_guarded_expression(filter, reqctx) {
+                query = query.filter(filter_exp)
+            }
+        }
+        let group_by = group_by.unwrap_or_default();
+        // Grouping by the author would reveal the masked authors of anonymous puzzles
+        if group_by.contains(&PuzzleAggregateGroupBy::UserId) {
+            if let Some(visible) = Puzzle::author_visible_expression(reqctx) {
+                query = query.filter(visible);
+            }
+        }
+
+        let results = AggregateQuery::<_, PuzzleAggregate>::new(query, group_by, functions, field)?
+            .load(&conn)?;
+
+        Ok(results)
+    }
+
     pub async fn puzzle_count_by_genre(
         &self,
         ctx: &Context<'_>,
         user_id: ID,
     ) -> async_graphql::Result<Vec<PuzzleCountByGenre>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let reveal = reqctx.get_role() == Role::Admin || reqctx.get_user_id() == Some(user_id);
 
         let results: Vec<PuzzleCountByGenre> =
             diesel::sql_query(include_str!("../sql/puzzle_count_by_genre.sql"))
                 .bind::<Integer, _>(user_id)
+                .bind::<sql_types::Bool, _>(*ANONYMOUS_REVEAL_ON_SOLVE)
+                .bind::<sql_types::Bool, _>(reveal)
                 .get_results(&conn)?;
 
         Ok(results)
@@ -113,10 +167,14 @@ impl PuzzleQuery {
         user_id: ID,
     ) -> async_graphql::Result<Vec<PuzzleStarAggrGroup>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let reveal = reqctx.get_role() == Role::Admin || reqctx.get_user_id() == Some(user_id);
 
         let results: Vec<PuzzleStarAggrGroup> =
             diesel::sql_query(include_str!("../sql/puzzle_star_count_groups.sql"))
                 .bind::<Integer, _>(user_id)
+                .bind::<sql_types::Bool, _>(*ANONYMOUS_REVEAL_ON_SOLVE)
+                .bind::<sql_types::Bool, _>(reveal)
                 .get_results(&conn)?;
 
         Ok(results)
@@ -128,10 +186,14 @@ impl PuzzleQuery {
         user_id: ID,
     ) -> async_graphql::Result<Vec<PuzzleStarAggrGroup>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        let reveal = reqctx.get_role() == Role::Admin || reqctx.get_user_id() == Some(user_id);
 
         let results: Vec<PuzzleStarAggrGroup> =
             diesel::sql_query(include_str!("../sql/puzzle_star_sum_groups.sql"))
                 .bind::<Integer, _>(user_id)
+                .bind::<sql_types::Bool, _>(*ANONYMOUS_REVEAL_ON_SOLVE)
+                .bind::<sql_types::Bool, _>(reveal)
                 .get_results(&conn)?;
 
         Ok(results)
@@ -143,12 +205,23 @@ impl PuzzleQuery {
         puzzle_id: ID,
     ) -> async_graphql::Result<Vec<PuzzleParticipant>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let puzzle_inst: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(puzzle_id))
+            .limit(1)
+            .first(&conn)?;
 
-        let results: Vec<PuzzleParticipant> =
+        let mut results: Vec<PuzzleParticipant> =
             diesel::sql_query(include_str!("../sql/puzzle_participants.sql"))
                 .bind::<Integer, _>(puzzle_id)
                 .get_results(&conn)?;
 
+        // The author of an anonymous puzzle could be told by its own questions
+        if !puzzle_inst.author_visible_to(reqctx) {
+            results.retain(|participant| participant.id != puzzle_inst.user_id);
+        }
+
         Ok(results)
     }
 
@@ -201,7 +274,7 @@ impl PuzzleQuery {
     ) -> async_graphql::Result<Vec<Puzzle>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        // The range of the time puzzles are created
+        // The range of the time puzzles are solved
         let start_time = Date::from_ymd(year, month, 1);
         let end_time = if month == 12 {
             Date::from_ymd(year + 1, 1, 1)
@@ -237,7 +310,8 @@ pub struct UpdatePuzzleInput {
     pub dazed_on: Option<Date>,
     pub grotesque: Option<bool>,
     pub license_id: MaybeUndefined<ID>,
-    pub content_image: MaybeUndefined<Vec<u8>>,
+    pub content_image_id: MaybeUndefined<ID>,
+    pub solved_at: MaybeUndefined<Timestamptz>,
 }
 
 #[derive(AsChangeset, Debug)]
@@ -257,7 +331,8 @@ pub struct UpdatePuzzleData {
     pub dazed_on: Option<Date>,
     pub grotesque: Option<bool>,
     pub license_id: Option<Option<ID>>,
-    pub content_image: Option<Option<Vec<u8>>>,
+    pub content_image_id: Option<Option<ID>>,
+    pub solved_at: Option<Option<Timestamptz>>,
 }
 
 impl From<UpdatePuzzleInput> for UpdatePuzzleData {
@@ -277,7 +352,8 @@ impl From<UpdatePuzzleInput> for UpdatePuzzleData {
             dazed_on: data.dazed_on,
             grotesque: data.grotesque,
             license_id: data.license_id.as_options(),
-            content_image: data.content_image.as_options(),
+            content_image_id: data.content_image_id.as_options(),
+            solved_at: data.solved_at.as_options(),
         }
     }
 }
@@ -372,7 +448,8 @@ pub struct CreatePuzzleInput {
     pub dazed_on: Option<Date>,
     pub grotesque: Option<bool>,
     pub license_id: MaybeUndefined<ID>,
-    pub content_image: MaybeUndefined<Vec<u8>>,
+    pub content_image_id: MaybeUndefined<ID>,
+    pub solved_at: MaybeUndefined<Timestamptz>,
 }
 
 impl CreatePuzzleInput {
@@ -425,7 +502,8 @@ pub struct CreatePuzzleData {
     pub dazed_on: Option<Date>,
     pub grotesque: Option<bool>,
     pub license_id: Option<Option<ID>>,
-    pub content_image: Option<Option<Vec<u8>>>,
+    pub content_image_id: Option<Option<ID>>,
+    pub solved_at: Option<Option<Timestamptz>>,
 }
 
 impl From<CreatePuzzleInput> for CreatePuzzleData {
@@ -445,11 +523,80 @@ impl From<CreatePuzzleInput> for CreatePuzzleData {
             dazed_on: data.dazed_on,
             grotesque: data.grotesque,
             license_id: data.license_id.as_options(),
-            content_image: data.content_image.as_options(),
+            content_image_id: data.content_image_id.as_options(),
+            solved_at: data.solved_at.as_options(),
         }
     }
 }
 
+/// Whether the update changes any text fields kept in the revisions.
+fn changes_text(puzzle: &Puzzle, set: &UpdatePuzzleInput) -> bool {
+    set.content.as_ref().map_or(false, |v| v != &puzzle.content)
+        || set
+            .solution
+            .as_ref()
+            .map_or(false, |v| v != &puzzle.solution)
+        || set.memo.as_ref().map_or(false, |v| v != &puzzle.memo)
+}
+
+/// Publishes the update of the puzzle, as seen by the users other than the author.
+fn publish_update(orig: Puzzle, puzzle: Puzzle) {
+    match (orig.status, puzzle.status) {
+        (Status::Draft, Status::Draft) => {}
+        (Status::Draft, _) => CindyBroker::publish(PuzzleSub::Created(puzzle)),
+        (_, Status::Draft) => CindyBroker::publish(PuzzleSub::Deleted(orig)),
+        _ => CindyBroker::publish(PuzzleSub::Updated(orig, puzzle)),
+    }
+}
+
+/// Publishes the draft puzzle as undergoing from now on, and removes its schedule.
+///
+/// `dazed_on` is recomputed from the publish time. Returns `None` if the puzzle is not a
+/// draft, e.g. it has been published by another instance in the meantime, in which case
+/// only the stale schedule is removed.
+pub fn publish_draft(conn: &PgConnection, id: ID) -> QueryResult<Option<Puzzle>> {
+    let puzzle_inst: Option<Puzzle> = puzzle::table
+        .filter(puzzle::id.eq(id))
+        .filter(puzzle::status.eq(Status::Draft))
+        .first(conn)
+        .optional()?;
+    let puzzle_inst = match puzzle_inst {
+        Some(puzzle_inst) => puzzle_inst,
+        None => {
+            diesel::delete(schedule::table.filter(schedule::puzzle_id.eq(id))).execute(conn)?;
+            return Ok(None);
+        }
+    };
+
+    let now = Utc::now();
+    let dazed_on = now.date().naive_utc()
+        + DazedTimeCalc::default()
+            .yami(Some(puzzle_inst.yami))
+            .genre(Some(puzzle_inst.genre))
+            .duration();
+
+    let puzzle = conn.transaction::<Option<Puzzle>, diesel::result::Error, _>(|| {
+        let puzzle: Option<Puzzle> = diesel::update(puzzle::table)
+            .filter(puzzle::id.eq(id))
+            .filter(puzzle::status.eq(Status::Draft))
+            .set((
+                puzzle::status.eq(Status::Undergoing),
+                puzzle::created.eq(now),
+                puzzle::dazed_on.eq(dazed_on),
+            ))
+            .get_result(conn)
+            .optional()?;
+        diesel::delete(schedule::table.filter(schedule::puzzle_id.eq(id))).execute(conn)?;
+        Ok(puzzle)
+    })?;
+
+    if let Some(puzzle) = puzzle.as_ref() {
+        CindyBroker::publish(PuzzleSub::Created(puzzle.clone()));
+    }
+
+    Ok(puzzle)
+}
+
 #[Object]
 impl PuzzleMutation {
     pub async fn update_puzzle(
@@ -471,7 +618,17 @@ impl PuzzleMutation {
         match role {
             Role::User => {
                 // Assert that time-related are unset
-                user_id_guard(ctx, puzzle_inst.user_id)?;
+                puzzle_author_guard(ctx, &conn, &puzzle_inst)?;
+                if set.user_id.is_some() && reqctx.get_user_id() != Some(puzzle_inst.user_id) {
+                    return Err(async_graphql::Error::new(
+                        "Co-authors cannot change the author",
+                    ));
+                }
+                if !set.solved_at.is_undefined() {
+                    return Err(async_graphql::Error::new(
+                        "`solved_at` is set automatically",
+                    ));
+                }
 
                 // Prevent further edit from user if its status is forced hidden
                 if let Status::ForceHidden = puzzle_inst.status {
@@ -479,26 +636,64 @@ impl PuzzleMutation {
                         "Further edits are blocked from a forced hidden puzzle",
                     ));
                 };
+
+                // Remixes of share-alike puzzles stay under the same license
+                if !set.license_id.is_undefined() {
+                    if let Some(required) = PuzzleDerivation::required_license_id(&conn, id)? {
+                        let license_id = match set.license_id {
+                            MaybeUndefined::Value(license_id) => Some(license_id),
+                            _ => None,
+                        };
+                        if license_id != Some(required) {
+                            return Err(async_graphql::Error::new(
+                                "The license is inherited from the source of the remix",
+                            ));
+                        }
+                    }
+                }
+
+                // Only the images uploaded by the user can be referenced
+                if let MaybeUndefined::Value(content_image_id) = set.content_image_id {
+                    if puzzle_inst.content_image_id != Some(content_image_id) {
+                        image_guard(ctx, &conn, content_image_id)?;
+                    }
+                }
+
+                // Drafts are published by `schedule_puzzle` only
+                if let Some(status) = set.status {
+                    if (status == Status::Draft) != (puzzle_inst.status == Status::Draft) {
+                        return Err(async_graphql::Error::new(
+                            "Drafts are published by scheduling them",
+                        ));
+                    }
+                }
             }
             Role::Admin => {}
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
         };
 
-        // Set `modified` to the current time when puzzle is solved
-        // TODO separate `modified` from `time_solved`
-        //
-        // This is redundant as it is already set by postgresql function
-        //if puzzle_inst.status == Status::Undergoing && set.status != Some(Status::Undergoing) {
-        //    set.modified = Some(Utc::now());
-        //};
+        // `modified` is set to the current time, and `solved_at` is set when the puzzle
+        // leaves `Undergoing`, by the postgresql function `update_puzzle_modified`.
+
+        let puzzle = conn
+            .transaction::<Puzzle, diesel::result::Error, _>(|| {
+                if changes_text(&puzzle_inst, &set) {
+                    diesel::insert_into(puzzle_revision::table)
+                        .values(&CreatePuzzleRevisionData::new(
+                            &puzzle_inst,
+                            reqctx.get_user_id(),
+                        ))
+                        .execute(&conn)?;
+                }
 
-        let puzzle: Puzzle = diesel::update(puzzle::table)
-            .filter(puzzle::id.eq(id))
-            .set(UpdatePuzzleData::from(set))
-            .get_result(&conn)
+                diesel::update(puzzle::table)
+                    .filter(puzzle::id.eq(id))
+                    .set(UpdatePuzzleData::from(set))
+                    .get_result(&conn)
+            })
             .map_err(|err| async_graphql::Error::from(err))?;
 
-        CindyBroker::publish(PuzzleSub::Updated(puzzle_inst, puzzle.clone()));
+        publish_update(puzzle_inst, puzzle.clone());
 
         Ok(puzzle)
     }
@@ -515,22 +710,54 @@ impl PuzzleMutation {
         set: UpdatePuzzleInput,
     ) -> async_graphql::Result<Vec<Puzzle>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let puzzles: Vec<Puzzle> =
-            if let Some(filter_exp) = filter.and_then(|filter| filter.as_expression()) {
-                diesel::update(puzzle::table)
-                    .filter(filter_exp)
-                    .set(UpdatePuzzleData::from(set))
-                    .get_results(&conn)
-                    .map_err(|err| async_graphql::Error::from(err))?
-            } else {
-                diesel::update(puzzle::table)
+        let mut query = puzzle::table.select(puzzle::id).into_boxed();
+        if let Some(filter_exp) = filter.and_then(|filter| filter.as_expression()) {
+            query = query.filter(filter_exp);
+        }
+
+        let (puzzle_insts, puzzles) = conn
+            .transaction::<(Vec<Puzzle>, Vec<Puzzle>), diesel::result::Error, _>(|| {
+                // Original puzzles are required to publish the updates and record the
+                // revisions, so that they are locked until the update is done
+                let puzzle_insts: Vec<Puzzle> = puzzle::table
+                    .filter(puzzle::id.eq_any(query))
+                    .for_update()
+                    .load(&conn)?;
+                let ids: Vec<ID> = puzzle_insts.iter().map(|puzzle| puzzle.id).collect();
+
+                let revisions: Vec<_> = puzzle_insts
+                    .iter()
+                    .filter(|puzzle_inst| changes_text(puzzle_inst, &set))
+                    .map(|puzzle_inst| {
+                        CreatePuzzleRevisionData::new(puzzle_inst, reqctx.get_user_id())
+                    })
+                    .collect();
+                if !revisions.is_empty() {
+                    diesel::insert_into(puzzle_revision::table)
+                        .values(&revisions)
+                        .execute(&conn)?;
+                }
+
+                let puzzles = diesel::update(puzzle::table)
+                    .filter(puzzle::id.eq_any(ids))
                     .set(UpdatePuzzleData::from(set))
-                    .get_results(&conn)
-                    .map_err(|err| async_graphql::Error::from(err))?
-            };
+                    .get_results(&conn)?;
+                Ok((puzzle_insts, puzzles))
+            })
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        let mut puzzle_insts: HashMap<ID, Puzzle> = puzzle_insts
+            .into_iter()
+            .map(|puzzle| (puzzle.id, puzzle))
+            .collect();
+        for puzzle in puzzles.iter() {
+            if let Some(puzzle_inst) = puzzle_insts.remove(&puzzle.id) {
+                publish_update(puzzle_inst, puzzle.clone());
+            }
+        }
 
-        // TODO Publish to subscriptions
         Ok(puzzles)
     }
 
@@ -549,6 +776,15 @@ impl PuzzleMutation {
                 // Assert that time-related are unset
                 assert_eq_guard(data.created, None)?;
                 assert_eq_guard(data.modified, None)?;
+                if !data.solved_at.is_undefined() {
+                    return Err(async_graphql::Error::new(
+                        "`solved_at` is set automatically",
+                    ));
+                }
+                // Only the images uploaded by the user can be referenced
+                if let MaybeUndefined::Value(content_image_id) = data.content_image_id {
+                    image_guard(ctx, &conn, content_image_id)?;
+                }
                 // Assert user_id is set to the user
                 let insert_data = if let Some(user_id) = data.user_id {
                     user_id_guard(ctx, user_id)?;
@@ -568,7 +804,202 @@ impl PuzzleMutation {
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
-        CindyBroker::publish(PuzzleSub::Created(puzzle.clone()));
+        // Drafts are published later by the scheduler
+        if puzzle.status != Status::Draft {
+            CindyBroker::publish(PuzzleSub::Created(puzzle.clone()));
+        }
+
+        Ok(puzzle)
+    }
+
+    /// Create a draft remix of the puzzle, copying its content and solution with the
+    /// attribution of the source.
+    ///
+    /// The license of the source must allow derivatives, and share-alike licenses are
+    /// inherited by the remix. The authors of the source are not bound by its license.
+    /// Hidden puzzles can only be remixed by admins.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn remix_puzzle(
+        &self,
+        ctx: &Context<'_>,
+        source_id: ID,
+        license_id: Option<ID>,
+    ) -> async_graphql::Result<Puzzle> {
+        use crate::schema::{license, puzzle_derivation, user};
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let source: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(source_id))
+            .limit(1)
+            .first(&conn)?;
+        if !source.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+        if matches!(source.status, Status::Hidden | Status::ForceHidden)
+            && reqctx.get_role() != Role::Admin
+        {
+            return Err(async_graphql::Error::new(
+                "Hidden puzzles cannot be remixed",
+            ));
+        }
+        // The solution is copied along with the content
+        if !source.solution_visible_to(&conn, reqctx)? {
+            return Err(async_graphql::Error::new(
+                "The puzzle cannot be remixed until it is solved",
+            ));
+        }
+
+        let source_license: Option<License> = match source.license_id {
+            Some(source_license_id) => license::table
+                .filter(license::id.eq(source_license_id))
+                .limit(1)
+                .first(&conn)
+                .optional()?,
+            None => None,
+        };
+        let is_licensor = puzzle_author_guard(ctx, &conn, &source).is_ok();
+        let license_id = match source_license.as_ref() {
+            _ if is_licensor => license_id,
+            None => {
+                return Err(async_graphql::Error::new(
+                    "The puzzle is not licensed for remixes",
+                ))
+            }
+            Some(source_license) if source_license.no_derivatives => {
+                return Err(async_graphql::Error::new(format!(
+                    "{} does not allow remixes",
+                    &source_license.name
+                )))
+            }
+            Some(source_license) if source_license.share_alike => {
+                if license_id.is_some() && license_id != Some(source_license.id) {
+                    return Err(async_graphql::Error::new(format!(
+                        "Remixes of the puzzle must be under {}",
+                        &source_license.name
+                    )));
+                }
+                Some(source_license.id)
+            }
+            Some(_) => license_id,
+        };
+
+        let user_id = reqctx.get_user_id();
+        let license_id = match (license_id, user_id) {
+            (Some(license_id), _) => Some(license_id),
+            (None, Some(user_id)) => user::table
+                .filter(user::id.eq(user_id))
+                .select(user::default_license_id)
+                .first(&conn)?,
+            (None, None) => None,
+        };
+
+        let author: Option<User> = if source.author_visible_to(&RequestCtx::default()) {
+            user::table
+                .filter(user::id.eq(source.user_id))
+                .limit(1)
+                .first(&conn)
+                .optional()?
+        } else {
+            None
+        };
+        let attribution =
+            PuzzleDerivation::attribution(&source, author.as_ref(), source_license.as_ref());
+
+        let data = CreatePuzzleInput {
+            title: Some(source.title.clone()),
+            yami: Some(source.yami),
+            genre: Some(source.genre),
+            content: Some(source.content.clone()),
+            solution: Some(source.solution.clone()),
+            created: None,
+            modified: None,
+            // Published by scheduling it after editing
+            status: Some(Status::Draft),
+            memo: None,
+            user_id,
+            anonymous: None,
+            dazed_on: None,
+            grotesque: Some(source.grotesque),
+            license_id: match license_id {
+                Some(license_id) => MaybeUndefined::Value(license_id),
+                None => MaybeUndefined::Null,
+            },
+            content_image_id: match source.content_image_id {
+                Some(content_image_id) => MaybeUndefined::Value(content_image_id),
+                None => MaybeUndefined::Null,
+            },
+            solved_at: MaybeUndefined::Undefined,
+        };
+        let insert_data = CreatePuzzleData::from(data.set_default());
+
+        let puzzle = conn.transaction::<Puzzle, diesel::result::Error, _>(|| {
+            let puzzle: Puzzle = diesel::insert_into(puzzle::table)
+                .values(&insert_data)
+                .get_result(&conn)?;
+            diesel::insert_into(puzzle_derivation::table)
+                .values((
+                    puzzle_derivation::puzzle_id.eq(puzzle.id),
+                    puzzle_derivation::source_id.eq(source.id),
+                    puzzle_derivation::attribution.eq(&attribution),
+                ))
+                .execute(&conn)?;
+            Ok(puzzle)
+        })?;
+
+        Ok(puzzle)
+    }
+
+    // Restore the text fields of the puzzle to the revision, which is recorded as a revision
+    // in turn.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn restore_puzzle_revision(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Puzzle> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let revision: PuzzleRevision = puzzle_revision::table
+            .filter(puzzle_revision::id.eq(id))
+            .limit(1)
+            .first(&conn)?;
+        let puzzle_inst: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(revision.puzzle_id))
+            .limit(1)
+            .first(&conn)?;
+
+        // User should be one of the authors, as on update mutation
+        puzzle_author_guard(ctx, &conn, &puzzle_inst)?;
+        if reqctx.get_role() == Role::User && puzzle_inst.status == Status::ForceHidden {
+            return Err(async_graphql::Error::new(
+                "Further edits are blocked from a forced hidden puzzle",
+            ));
+        }
+
+        let puzzle = conn
+            .transaction::<Puzzle, diesel::result::Error, _>(|| {
+                diesel::insert_into(puzzle_revision::table)
+                    .values(&CreatePuzzleRevisionData::new(
+                        &puzzle_inst,
+                        reqctx.get_user_id(),
+                    ))
+                    .execute(&conn)?;
+
+                diesel::update(puzzle::table)
+                    .filter(puzzle::id.eq(puzzle_inst.id))
+                    .set((
+                        puzzle::content.eq(&revision.content),
+                        puzzle::solution.eq(&revision.solution),
+                        puzzle::memo.eq(&revision.memo),
+                    ))
+                    .get_result(&conn)
+            })
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        publish_update(puzzle_inst, puzzle.clone());
 
         Ok(puzzle)
     }
@@ -581,10 +1012,14 @@ impl PuzzleMutation {
     pub async fn delete_puzzle(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Puzzle> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        let puzzle = diesel::delete(puzzle::table.filter(puzzle::id.eq(id)))
+        let puzzle: Puzzle = diesel::delete(puzzle::table.filter(puzzle::id.eq(id)))
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        if puzzle.status != Status::Draft {
+            CindyBroker::publish(PuzzleSub::Deleted(puzzle.clone()));
+        }
+
         Ok(puzzle)
     }
 }
@@ -595,6 +1030,7 @@ pub struct PuzzleSubFilter {
     status: Option<StatusFiltering>,
     yami: Option<YamiFiltering>,
     genre: Option<GenreFiltering>,
+    solved_at: Option<NullableTimestamptzFiltering>,
 }
 
 impl RawFilter<Puzzle> for PuzzleSubFilter {
@@ -607,6 +1043,8 @@ impl RawFilter<Puzzle> for PuzzleSubFilter {
             filter.check(&item.yami)
         } else if let Some(filter) = self.genre.as_ref() {
             filter.check(&item.genre)
+        } else if let Some(filter) = self.solved_at.as_ref() {
+            filter.check(&item.solved_at)
         } else {
             true
         }
@@ -618,19 +1056,23 @@ impl PuzzleSubscription {
     pub async fn puzzle_sub(
         &self,
         filter: Option<PuzzleSubFilter>,
-    ) -> impl Stream<Item = Option<PuzzleSub>> {
-        CindyBroker::<PuzzleSub>::subscribe().filter(move |puzzle_sub| {
-            let check = if let Some(filter) = filter.as_ref() {
-                match puzzle_sub {
-                    Some(PuzzleSub::Created(puzzle)) => filter.check(&puzzle),
-                    Some(PuzzleSub::Updated(orig, _)) => filter.check(&orig),
-                    None => false,
-                }
-            } else {
-                puzzle_sub.is_some()
-            };
+        since: Option<EventId>,
+    ) -> impl Stream<Item = Event<PuzzleSub>> {
+        CindyBroker::<PuzzleSub>::subscribe_to_since(Default::default(), since).filter(
+            move |puzzle_sub| {
+                let check = if let Some(filter) = filter.as_ref() {
+                    match &puzzle_sub.msg {
+                        PuzzleSub::Created(puzzle) => filter.check(&puzzle),
+                        PuzzleSub::Updated(orig, _) => filter.check(&orig),
+                        PuzzleSub::Deleted(puzzle) => filter.check(&puzzle),
+                        PuzzleSub::Resync => true,
+                    }
+                } else {
+                    true
+                };
 
-            async move { check }
-        })
+                async move { check }
+            },
+        )
     }
 }
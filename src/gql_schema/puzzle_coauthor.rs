@@ -0,0 +1,79 @@
+use async_graphql::{self, guard::Guard, Context, Object};
+use diesel::prelude::*;
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::*;
+use crate::schema::{puzzle, puzzle_coauthor};
+
+#[derive(Default)]
+pub struct PuzzleCoauthorMutation;
+
+#[Object]
+impl PuzzleCoauthorMutation {
+    /// Add the user as a co-author of the puzzle, or change their role if they already
+    /// are one. Only the author can manage the co-authors.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn add_puzzle_coauthor(
+        &self,
+        ctx: &Context<'_>,
+        puzzle_id: ID,
+        user_id: ID,
+        #[graphql(default_with = "CoauthorRole::Coauthor")] role: CoauthorRole,
+    ) -> async_graphql::Result<PuzzleCoauthor> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let puzzle_inst: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(puzzle_id))
+            .limit(1)
+            .first(&conn)?;
+        user_id_guard(ctx, puzzle_inst.user_id)?;
+        if user_id == puzzle_inst.user_id {
+            return Err(async_graphql::Error::new(
+                "The author cannot be a co-author of the puzzle",
+            ));
+        }
+
+        let coauthor: PuzzleCoauthor = diesel::insert_into(puzzle_coauthor::table)
+            .values((
+                puzzle_coauthor::puzzle_id.eq(puzzle_id),
+                puzzle_coauthor::user_id.eq(user_id),
+                puzzle_coauthor::role.eq(role),
+            ))
+            .on_conflict((puzzle_coauthor::puzzle_id, puzzle_coauthor::user_id))
+            .do_update()
+            .set(puzzle_coauthor::role.eq(role))
+            .get_result(&conn)?;
+
+        Ok(coauthor)
+    }
+
+    /// Remove the co-author of the puzzle, by the author or the co-author themselves.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn remove_puzzle_coauthor(
+        &self,
+        ctx: &Context<'_>,
+        puzzle_id: ID,
+        user_id: ID,
+    ) -> async_graphql::Result<PuzzleCoauthor> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        if reqctx.get_user_id() != Some(user_id) {
+            let author_id: ID = puzzle::table
+                .filter(puzzle::id.eq(puzzle_id))
+                .select(puzzle::user_id)
+                .first(&conn)?;
+            user_id_guard(ctx, author_id)?;
+        }
+
+        let coauthor: PuzzleCoauthor = diesel::delete(
+            puzzle_coauthor::table
+                .filter(puzzle_coauthor::puzzle_id.eq(puzzle_id))
+                .filter(puzzle_coauthor::user_id.eq(user_id)),
+        )
+        .get_result(&conn)?;
+
+        Ok(coauthor)
+    }
+}
@@ -2,7 +2,7 @@ use async_graphql::{Context, InputObject, Object, Subscription};
 use diesel::prelude::*;
 use futures::{Stream, StreamExt};
 
-use crate::broker::CindyBroker;
+use crate::broker::{CindyBroker, Event, EventId};
 use crate::context::GlobalCtx;
 use crate::models::{dialogue::*, hint::*, puzzle_log::*, *};
 
@@ -157,7 +157,8 @@ impl PuzzleLogSubscription {
     pub async fn puzzle_log_sub(
         &self,
         filter: Option<PuzzleLogSubFilter>,
-    ) -> impl Stream<Item = Option<PuzzleLogSub>> {
+        since: Option<EventId>,
+    ) -> impl Stream<Item = Event<PuzzleLogSub>> {
         let key = if let Some(filter) = filter.as_ref() {
             if let Some(user_id) = filter.user_id {
                 format!("puzzleLog<{}-{}>", filter.puzzle_id, user_id)
@@ -167,26 +168,27 @@ impl PuzzleLogSubscription {
         } else {
             "puzzleLog".to_string()
         };
-        CindyBroker::<PuzzleLogSub>::subscribe_to(key).filter(move |puzzle_log_sub| {
+        CindyBroker::<PuzzleLogSub>::subscribe_to_since(key, since).filter(move |puzzle_log_sub| {
             let check = if let Some(filter) = filter.as_ref() {
-                match puzzle_log_sub {
-                    Some(PuzzleLogSub::DialogueCreated(obj)) => filter.check(obj),
-                    Some(PuzzleLogSub::HintCreated(obj)) => filter.check(obj),
-                    Some(PuzzleLogSub::DialogueUpdated(orig, _)) => filter.check(orig),
-                    Some(PuzzleLogSub::HintUpdated(orig, _)) => filter.check(orig),
-                    None => false,
+                match &puzzle_log_sub.msg {
+                    PuzzleLogSub::DialogueCreated(obj) => filter.check(obj),
+                    PuzzleLogSub::HintCreated(obj) => filter.check(obj),
+                    PuzzleLogSub::DialogueUpdated(orig, _) => filter.check(orig),
+                    PuzzleLogSub::HintUpdated(orig, _) => filter.check(orig),
+                    PuzzleLogSub::DialogueDeleted(obj) => filter.check(obj),
+                    PuzzleLogSub::HintDeleted(obj) => filter.check(obj),
+                    PuzzleLogSub::Resync => true,
                 }
             } else {
-                puzzle_log_sub.is_some()
+                true
             };
 
             async move { check }
         })
     }
 
-    pub async fn unsolved_puzzle_stats_sub(
-        &self,
-    ) -> impl Stream<Item = Option<UnsolvedPuzzleStatsSub>> {
+    pub async fn unsolved_puzzle_stats_sub(&self) -> impl Stream<Item = UnsolvedPuzzleStatsSub> {
         CindyBroker::<UnsolvedPuzzleStatsSub>::subscribe_to("puzzlePuzzleLogs".to_string())
+            .map(|event| event.msg)
     }
 }
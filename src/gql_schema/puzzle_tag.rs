@@ -231,7 +231,8 @@ impl PuzzleTagMutation {
                 let user_id = reqctx
                     .get_user_id()
                     .ok_or(async_graphql::Error::new("No user"))?;
-                // User should be the owner of the puzzle or the puzzle_tag
+                // User should be the owner of the puzzle_tag or one of the authors of the
+                // puzzle
                 let puzzle_tag_inst: PuzzleTag = puzzle_tag::table
                     .filter(puzzle_tag::id.eq(id))
                     .limit(1)
@@ -241,7 +242,7 @@ impl PuzzleTagMutation {
                     .limit(1)
                     .first(&conn)?;
                 assert_eq_guard(puzzle_tag_inst.user_id, user_id)
-                    .or_else(|_| assert_eq_guard(puzzle_inst.user_id, user_id))?;
+                    .or_else(|_| puzzle_author_guard(ctx, &conn, &puzzle_inst))?;
             }
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
             _ => {}
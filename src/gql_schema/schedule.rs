@@ -0,0 +1,130 @@
+use async_graphql::{self, guard::Guard, Context, Object};
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::schedule::*;
+use crate::models::*;
+use crate::schema::{puzzle, schedule};
+
+use super::puzzle::publish_draft;
+
+#[derive(Default)]
+pub struct ScheduleQuery;
+#[derive(Default)]
+pub struct ScheduleMutation;
+
+#[Object]
+impl ScheduleQuery {
+    /// Schedules of the user, or of all users for admins
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn schedules(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        filter: Option<Vec<ScheduleFilter>>,
+        order: Option<Vec<ScheduleOrder>>,
+    ) -> async_graphql::Result<Vec<Schedule>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = schedule::table.into_boxed();
+        if reqctx.get_role() != Role::Admin {
+            query = query.filter(schedule::user_id.nullable().eq(reqctx.get_user_id()));
+        }
+        if let Some(order) = order {
+            query = ScheduleOrders::new(order).apply_order(query);
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        if let Some(offset) = offset {
+            query = query.offset(offset);
+        }
+
+        let schedules = query.load::<Schedule>(&conn)?;
+
+        Ok(schedules)
+    }
+}
+
+/// Asserts the puzzle is a draft the user is allowed to schedule.
+fn draft_guard(ctx: &Context<'_>, conn: &PgConnection, id: ID) -> async_graphql::Result<Puzzle> {
+    let puzzle: Puzzle = puzzle::table
+        .filter(puzzle::id.eq(id))
+        .limit(1)
+        .first(conn)?;
+    puzzle_author_guard(ctx, conn, &puzzle)?;
+    if puzzle.status != Status::Draft {
+        return Err(async_graphql::Error::new("The puzzle is not a draft"));
+    }
+
+    Ok(puzzle)
+}
+
+#[Object]
+impl ScheduleMutation {
+    /// Schedule the draft puzzle to be published at the time, replacing the former
+    /// schedule if any.
+    ///
+    /// The puzzle is published right away and `null` is returned if the time has passed.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn schedule_puzzle(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        at: Timestamptz,
+    ) -> async_graphql::Result<Option<Schedule>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let puzzle = draft_guard(ctx, &conn, id)?;
+
+        if at <= Utc::now() {
+            publish_draft(&conn, id)?;
+            return Ok(None);
+        }
+
+        let schedule: Schedule = diesel::insert_into(schedule::table)
+            .values((
+                schedule::content.eq(&puzzle.title),
+                schedule::scheduled.eq(at),
+                schedule::user_id.eq(puzzle.user_id),
+                schedule::puzzle_id.eq(id),
+            ))
+            .on_conflict(schedule::puzzle_id)
+            .do_update()
+            .set((
+                schedule::content.eq(&puzzle.title),
+                schedule::scheduled.eq(at),
+            ))
+            .get_result(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        Ok(Some(schedule))
+    }
+
+    /// Cancel the schedule of the draft puzzle, keeping it as a draft.
+    #[graphql(guard(DenyRoleGuard(role = "Role::Guest")))]
+    pub async fn unschedule_puzzle(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Schedule> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        draft_guard(ctx, &conn, id)?;
+
+        let schedule: Schedule = diesel::delete(schedule::table.filter(schedule::puzzle_id.eq(id)))
+            .get_result(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        Ok(schedule)
+    }
+}
@@ -3,10 +3,13 @@ use diesel::{dsl::sum, prelude::*};
 
 use crate::auth::Role;
 use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::notification::NotificationKind;
 use crate::models::star::*;
 use crate::models::*;
 use crate::schema::star;
 
+use super::notification::{notify, CreateNotificationData};
+
 #[derive(Default)]
 pub struct StarQuery;
 #[derive(Default)]
@@ -76,6 +79,41 @@ impl StarQuery {
         Ok(result)
     }
 
+    pub async fn star_aggregate(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<Vec<StarFilter>>,
+        group_by: Option<Vec<StarAggregateGroupBy>>,
+        functions: Vec<AggregateFunction>,
+        field: Option<StarAggregateField>,
+    ) -> async_graphql::Result<Vec<StarAggregate>> {
+        use crate::schema::star::dsl::*;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = star.into_boxed();
+        // Drafts are only counted for admins
+        if reqctx.get_role() != Role::Admin {
+            query = query.filter(puzzle_id.eq_any(published_puzzles()));
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+
+        let results = AggregateQuery::<_, StarAggregate>::new(
+            query,
+            group_by.unwrap_or_default(),
+            functions,
+            field,
+        )?
+        .load(&conn)?;
+
+        Ok(results)
+    }
+
     pub async fn star_sum_by_puzzle(
         &self,
         ctx: &Context<'_>,
@@ -134,6 +172,9 @@ impl StarMutation {
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
             _ => {}
         };
+        if let Some(puzzle_id) = set.puzzle_id {
+            published_guard(&conn, puzzle_id)?;
+        }
 
         let star: Star = diesel::update(star::table)
             .filter(star::id.eq(id))
@@ -165,12 +206,30 @@ impl StarMutation {
             Role::Admin => {}
             Role::Guest => return Err(async_graphql::Error::new("User not logged in")),
         };
+        published_guard(&conn, data.puzzle_id)?;
 
         let star: Star = diesel::insert_into(star::table)
             .values(&data)
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        // Notify the author of the puzzle
+        {
+            use crate::schema::puzzle;
+
+            let puzzle_inst: Puzzle = puzzle::table
+                .filter(puzzle::id.eq(star.puzzle_id))
+                .limit(1)
+                .first(&conn)?;
+            notify(
+                &conn,
+                CreateNotificationData::new(puzzle_inst.user_id, NotificationKind::Star)
+                    .actor_id(Some(star.user_id))
+                    .puzzle_id(star.puzzle_id)
+                    .target_id(star.id),
+            );
+        }
+
         Ok(star)
     }
 
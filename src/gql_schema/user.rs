@@ -10,6 +10,7 @@ use diesel::{
 
 use crate::auth::Role;
 use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::puzzle::ANONYMOUS_REVEAL_ON_SOLVE;
 use crate::models::user::*;
 use crate::models::*;
 use crate::schema::user;
@@ -122,7 +123,7 @@ impl UserQuery {
     ) -> async_graphql::Result<Vec<UserRankingRow>> {
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
 
-        // The range of the time puzzles are created
+        // The range of the time puzzles are solved
         let start_time = Date::from_ymd(year, month, 1);
         let end_time = if month == 12 {
             Date::from_ymd(year + 1, 1, 1)
@@ -136,6 +137,7 @@ impl UserQuery {
                 .bind::<sql_types::Date, _>(end_time)
                 .bind::<Integer, _>(limit)
                 .bind::<Integer, _>(offset)
+                .bind::<sql_types::Bool, _>(*ANONYMOUS_REVEAL_ON_SOLVE)
                 .get_results(&conn)?;
 
         Ok(results)
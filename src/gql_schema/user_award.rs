@@ -4,10 +4,13 @@ use diesel::prelude::*;
 
 use crate::auth::Role;
 use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::notification::NotificationKind;
 use crate::models::user_award::*;
 use crate::models::*;
 use crate::schema::user_award;
 
+use super::notification::{notify, CreateNotificationData};
+
 #[derive(Default)]
 pub struct UserAwardQuery;
 #[derive(Default)]
@@ -133,6 +136,12 @@ impl UserAwardMutation {
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
 
+        notify(
+            &conn,
+            CreateNotificationData::new(user_award.user_id, NotificationKind::Award)
+                .target_id(user_award.id),
+        );
+
         Ok(user_award)
     }
 
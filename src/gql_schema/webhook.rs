@@ -0,0 +1,227 @@
+use async_graphql::{self, guard::Guard, Context, InputObject, Object};
+use diesel::prelude::*;
+
+use crate::auth::Role;
+use crate::context::GlobalCtx;
+use crate::models::webhook::*;
+use crate::models::*;
+use crate::schema::{webhook, webhook_delivery};
+
+#[derive(Default)]
+pub struct WebhookQuery;
+#[derive(Default)]
+pub struct WebhookMutation;
+
+#[Object]
+impl WebhookQuery {
+    // Site event webhooks (admin only)
+    #[graphql(guard(and(
+        DenyRoleGuard(role = "Role::User"),
+        DenyRoleGuard(role = "Role::Guest")
+    )))]
+    pub async fn webhooks(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Webhook>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let webhooks = webhook::table
+            .order(webhook::id.asc())
+            .load::<Webhook>(&conn)?;
+
+        Ok(webhooks)
+    }
+
+    /// Deliveries of the webhooks. Filter by `status: { eq: Dead }` for the dead letters.
+    #[graphql(guard(and(
+        DenyRoleGuard(role = "Role::User"),
+        DenyRoleGuard(role = "Role::Guest")
+    )))]
+    pub async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        filter: Option<Vec<WebhookDeliveryFilter>>,
+        order: Option<Vec<WebhookDeliveryOrder>>,
+    ) -> async_graphql::Result<Vec<WebhookDelivery>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let mut query = webhook_delivery::table.into_boxed();
+        if let Some(order) = order {
+            query = WebhookDeliveryOrders::new(order).apply_order(query);
+        }
+        if let Some(filter) = filter {
+            if let Some(filter_exp) = filter.as_expression() {
+                query = query.filter(filter_exp)
+            }
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        if let Some(offset) = offset {
+            query = query.offset(offset);
+        }
+
+        let deliveries = query.load::<WebhookDelivery>(&conn)?;
+
+        Ok(deliveries)
+    }
+}
+
+#[derive(InputObject)]
+pub struct CreateWebhookInput {
+    pub url: String,
+    /// Key signing the payloads, generated randomly if not set
+    pub secret: Option<String>,
+    pub events: Vec<WebhookEvent>,
+    #[graphql(default = true)]
+    pub enabled: bool,
+}
+
+#[derive(Insertable)]
+#[table_name = "webhook"]
+pub struct CreateWebhookData {
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<i32>,
+    pub enabled: bool,
+}
+
+impl From<CreateWebhookInput> for CreateWebhookData {
+    fn from(data: CreateWebhookInput) -> Self {
+        Self {
+            url: data.url,
+            secret: data.secret.unwrap_or_else(random_secret),
+            events: data.events.into_iter().map(|event| event as i32).collect(),
+            enabled: data.enabled,
+        }
+    }
+}
+
+#[derive(InputObject)]
+pub struct UpdateWebhookInput {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<WebhookEvent>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(AsChangeset)]
+#[table_name = "webhook"]
+pub struct UpdateWebhookData {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<i32>>,
+    pub enabled: Option<bool>,
+}
+
+impl From<UpdateWebhookInput> for UpdateWebhookData {
+    fn from(data: UpdateWebhookInput) -> Self {
+        Self {
+            url: data.url,
+            secret: data.secret,
+            events: data
+                .events
+                .map(|events| events.into_iter().map(|event| event as i32).collect()),
+            enabled: data.enabled,
+        }
+    }
+}
+
+fn random_secret() -> String {
+    (0..32)
+        .map(|_| format!("{:02x}", rand::random::<u8>()))
+        .collect()
+}
+
+fn check_url(url: &str) -> async_graphql::Result<()> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(async_graphql::Error::new("Invalid webhook url"));
+    }
+    Ok(())
+}
+
+#[Object]
+impl WebhookMutation {
+    // Create webhook (admin only)
+    #[graphql(guard(and(
+        DenyRoleGuard(role = "Role::User"),
+        DenyRoleGuard(role = "Role::Guest")
+    )))]
+    pub async fn create_webhook(
+        &self,
+        ctx: &Context<'_>,
+        data: CreateWebhookInput,
+    ) -> async_graphql::Result<Webhook> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        check_url(&data.url)?;
+
+        let webhook: Webhook = diesel::insert_into(webhook::table)
+            .values(&CreateWebhookData::from(data))
+            .get_result(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        Ok(webhook)
+    }
+
+    // Update webhook (admin only)
+    #[graphql(guard(and(
+        DenyRoleGuard(role = "Role::User"),
+        DenyRoleGuard(role = "Role::Guest")
+    )))]
+    pub async fn update_webhook(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        set: UpdateWebhookInput,
+    ) -> async_graphql::Result<Webhook> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        if let Some(url) = set.url.as_ref() {
+            check_url(url)?;
+        }
+
+        let webhook: Webhook = diesel::update(webhook::table)
+            .filter(webhook::id.eq(id))
+            .set(UpdateWebhookData::from(set))
+            .get_result(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        Ok(webhook)
+    }
+
+    // Delete webhook along with its deliveries (admin only)
+    #[graphql(guard(and(
+        DenyRoleGuard(role = "Role::User"),
+        DenyRoleGuard(role = "Role::Guest")
+    )))]
+    pub async fn delete_webhook(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Webhook> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let webhook = diesel::delete(webhook::table.filter(webhook::id.eq(id)))
+            .get_result(&conn)
+            .map_err(|err| async_graphql::Error::from(err))?;
+
+        Ok(webhook)
+    }
+
+    // Retry a delivery from the beginning, e.g. a dead one (admin only)
+    #[graphql(guard(and(
+        DenyRoleGuard(role = "Role::User"),
+        DenyRoleGuard(role = "Role::Guest")
+    )))]
+    pub async fn redeliver_webhook_delivery(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<WebhookDelivery> {
+        let ctx = ctx.data::<GlobalCtx>()?;
+
+        let delivery = crate::webhook::redeliver(ctx, id)?;
+
+        Ok(delivery)
+    }
+}
@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use std::io;
+use std::path::PathBuf;
+
+use super::ImageStore;
+
+/// Stores the images as files under the root directory, sharded by the first two
+/// characters of the key.
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn from_env() -> Self {
+        Self {
+            root: PathBuf::from(
+                dotenv::var("IMAGE_STORE_PATH").unwrap_or_else(|_| "./images".to_owned()),
+            ),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key.get(..2).unwrap_or("_")).join(key)
+    }
+}
+
+#[async_trait]
+impl ImageStore for FsStore {
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> anyhow::Result<()> {
+        let path = self.path(key);
+        tokio::task::spawn_blocking(move || -> io::Result<()> {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            // Written aside first so that a partial file is never served, under a name of
+            // its own as the same key may be written concurrently
+            let mut tmp = path.clone().into_os_string();
+            tmp.push(format!(".{:016x}.tmp", rand::random::<u64>()));
+            let tmp = PathBuf::from(tmp);
+            let result = std::fs::write(&tmp, data).and_then(|_| std::fs::rename(&tmp, &path));
+            if result.is_err() {
+                std::fs::remove_file(&tmp).ok();
+            }
+            result
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let path = self.path(key);
+        let result = tokio::task::spawn_blocking(move || std::fs::read(path)).await?;
+
+        match result {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self.path(key).is_file())
+    }
+}
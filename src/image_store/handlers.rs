@@ -0,0 +1,215 @@
+use actix_multipart::Multipart;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Result};
+use diesel::prelude::*;
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::image::VariantFormat;
+use crate::models::Image;
+use crate::schema::image;
+
+use super::{save, sniff_content_type, store, InvalidImage, MAX_SIZE};
+
+#[derive(Serialize, Default)]
+pub struct UploadResponse {
+    error: Option<String>,
+    data: Option<UploadResponseData>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadResponseData {
+    id: i32,
+    hash: String,
+    url: String,
+    content_type: String,
+    size: i32,
+}
+
+impl From<Image> for UploadResponseData {
+    fn from(image: Image) -> Self {
+        Self {
+            id: image.id,
+            url: super::url(&image.hash),
+            hash: image.hash,
+            content_type: image.content_type,
+            size: image.size,
+        }
+    }
+}
+
+fn error_response<E: Into<String>>(status: StatusCode, error: E) -> Result<HttpResponse> {
+    Ok(HttpResponse::build(status).json(UploadResponse {
+        error: Some(error.into()),
+        data: None,
+    }))
+}
+
+/// Uploads the image in the `file` field of the multipart form.
+pub async fn upload(
+    mut payload: Multipart,
+    ctx: web::Data<GlobalCtx>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let reqctx = RequestCtx::from_headers(req.headers());
+    if reqctx.get_role() == Role::Guest {
+        return error_response(StatusCode::UNAUTHORIZED, "User not logged in");
+    }
+
+    let mut data: Option<Vec<u8>> = None;
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let is_file = field
+            .content_disposition()
+            .map(|disposition| disposition.get_name() == Some("file"))
+            .unwrap_or(false);
+        if !is_file {
+            continue;
+        }
+
+        let mut buf = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk?;
+            if buf.len() + chunk.len() > *MAX_SIZE {
+                return error_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Images are limited to {} bytes", *MAX_SIZE),
+                );
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        data = Some(buf);
+        break;
+    }
+
+    let data = match data {
+        Some(data) if !data.is_empty() => data,
+        _ => return error_response(StatusCode::BAD_REQUEST, "No image in the `file` field"),
+    };
+    // The content type declared by the client is not trusted
+    let content_type = match sniff_content_type(&data) {
+        Some(content_type) => content_type,
+        None => {
+            return error_response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Only png, jpeg, gif and webp images are allowed",
+            )
+        }
+    };
+
+    match save(&ctx, data, content_type, reqctx.get_user_id()).await {
+        Ok(image) => {
+            info!(
+                "/images: uploaded {} by {:?}",
+                &image.hash,
+                reqctx.get_user_id()
+            );
+            Ok(HttpResponse::Ok().json(UploadResponse {
+                error: None,
+                data: Some(image.into()),
+            }))
+        }
+        Err(err) if err.is::<InvalidImage>() => {
+            error_response(StatusCode::BAD_REQUEST, err.to_string())
+        }
+        Err(err) => {
+            error!("/images: unable to save the image: {}", err);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Unable to save the image",
+            )
+        }
+    }
+}
+
+/// Parses the key of an image, `<hash>` for the original or `<hash>_<size>.<ext>` for a
+/// variant, into the hash and the content type of the variant.
+fn parse_key(key: &str) -> Option<(&str, Option<&'static str>)> {
+    let (hash, variant) = match key.find('_') {
+        Some(idx) => (&key[..idx], Some(&key[idx + 1..])),
+        None => (key, None),
+    };
+    if hash.len() != 64 || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let content_type = match variant {
+        Some(variant) => {
+            let mut parts = variant.splitn(2, '.');
+            parts.next()?.parse::<u32>().ok()?;
+            Some(VariantFormat::from_extension(parts.next()?)?.content_type())
+        }
+        None => None,
+    };
+
+    Some((hash, content_type))
+}
+
+/// Serves the image or its variant with the key.
+///
+/// Images are addressed by their content, so the key itself is the ETag and they can be
+/// cached forever.
+pub async fn serve(
+    path: web::Path<String>,
+    ctx: web::Data<GlobalCtx>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let key = path.into_inner();
+    let (hash, variant_content_type) = match parse_key(&key) {
+        Some(parsed) => parsed,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let etag = format!("\"{}\"", &key);
+    let cached = req
+        .headers()
+        .get("If-None-Match")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').any(|tag| tag.trim() == etag))
+        .unwrap_or(false);
+    if cached {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(("ETag", etag))
+            .finish());
+    }
+
+    let content_type = match variant_content_type {
+        Some(content_type) => content_type.to_owned(),
+        None => {
+            let content_type: Option<String> = ctx
+                .get_conn()
+                .and_then(|conn| {
+                    image::table
+                        .filter(image::hash.eq(hash))
+                        .select(image::content_type)
+                        .first(&conn)
+                        .optional()
+                        .map_err(anyhow::Error::from)
+                })
+                .map_err(|err| {
+                    error!("/images: {}", err);
+                    actix_web::error::ErrorInternalServerError("Unable to load the image")
+                })?;
+            match content_type {
+                Some(content_type) => content_type,
+                None => return Ok(HttpResponse::NotFound().finish()),
+            }
+        }
+    };
+
+    match store().get(&key).await {
+        Ok(Some(data)) => Ok(HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+            .insert_header(("ETag", etag))
+            .body(data)),
+        // Not moved into the store yet
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => {
+            error!("/images: unable to get {}: {}", &key, err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
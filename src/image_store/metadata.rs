@@ -0,0 +1,287 @@
+//! Lossless removal of the metadata, e.g. EXIF with the location and the camera, from the
+//! uploaded images.
+//!
+//! All the image types allowed are handled. Color profiles are kept, as the images would
+//! render differently without them. Note that the EXIF orientation is dropped along with
+//! the rest.
+
+/// The image without its metadata. Images which cannot be parsed are returned as is.
+pub fn strip(data: Vec<u8>, content_type: &str) -> Vec<u8> {
+    let stripped = match content_type {
+        "image/jpeg" => strip_jpeg(&data),
+        "image/png" => strip_png(&data),
+        "image/gif" => strip_gif(&data),
+        "image/webp" => strip_webp(&data),
+        _ => None,
+    };
+    stripped.unwrap_or(data)
+}
+
+/// Drops the APP1 (EXIF, XMP), APP13 (IPTC) and comment segments.
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    const SOS: u8 = 0xda;
+    const APP1: u8 = 0xe1;
+    const APP13: u8 = 0xed;
+    const COM: u8 = 0xfe;
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(data.get(..2)?);
+
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        if marker == 0xff {
+            // Fill byte
+            i += 1;
+            continue;
+        }
+        if marker == SOS {
+            // The entropy-coded data follows until the end of the image
+            out.extend_from_slice(&data[i..]);
+            return Some(out);
+        }
+
+        let len = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+        let end = i + 2 + len;
+        let segment = data.get(i..end)?;
+        if ![APP1, APP13, COM].contains(&marker) {
+            out.extend_from_slice(segment);
+        }
+        i = end;
+    }
+}
+
+/// Drops the text, time and EXIF chunks.
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    const DROPPED: [&[u8]; 5] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(data.get(..8)?);
+
+    let mut i = 8;
+    loop {
+        let len = u32::from_be_bytes([
+            *data.get(i)?,
+            *data.get(i + 1)?,
+            *data.get(i + 2)?,
+            *data.get(i + 3)?,
+        ]) as usize;
+        // Length, type, data and CRC
+        let end = i + 12 + len;
+        let chunk = data.get(i..end)?;
+        let kind = &chunk[4..8];
+        if !DROPPED.contains(&kind) {
+            out.extend_from_slice(chunk);
+        }
+        if kind == b"IEND" {
+            return Some(out);
+        }
+        i = end;
+    }
+}
+
+/// Drops the comment extensions and the application extensions (XMP, ...) other than the
+/// looping and color profile ones.
+fn strip_gif(data: &[u8]) -> Option<Vec<u8>> {
+    const KEPT_APPLICATIONS: [&[u8]; 3] = [b"NETSCAPE2.0", b"ANIMEXTS1.0", b"ICCRGBG1012"];
+
+    /// End of the data sub-blocks starting at `i`
+    fn sub_blocks_end(data: &[u8], mut i: usize) -> Option<usize> {
+        loop {
+            let len = *data.get(i)? as usize;
+            i += 1 + len;
+            if len == 0 {
+                return Some(i);
+            }
+        }
+    }
+
+    /// Size of the color table following a descriptor with the flags
+    fn color_table_len(flags: u8) -> usize {
+        if flags & 0x80 != 0 {
+            3 << ((flags & 0x07) + 1)
+        } else {
+            0
+        }
+    }
+
+    // Header, logical screen descriptor and global color table
+    let mut i = 13 + color_table_len(*data.get(10)?);
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(data.get(..i)?);
+
+    loop {
+        match *data.get(i)? {
+            // Extension
+            0x21 => {
+                let label = *data.get(i + 1)?;
+                let end = sub_blocks_end(data, i + 2)?;
+                let block = data.get(i..end)?;
+                let kept = match label {
+                    0xfe => false,
+                    0xff => KEPT_APPLICATIONS.contains(&block.get(3..14)?),
+                    _ => true,
+                };
+                if kept {
+                    out.extend_from_slice(block);
+                }
+                i = end;
+            }
+            // Image descriptor, color table and LZW minimum code size
+            0x2c => {
+                let start = i + 11 + color_table_len(*data.get(i + 9)?);
+                let end = sub_blocks_end(data, start)?;
+                out.extend_from_slice(data.get(i..end)?);
+                i = end;
+            }
+            // Trailer
+            0x3b => {
+                out.push(0x3b);
+                return Some(out);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Drops the EXIF and XMP chunks, along with their flags in the extended header.
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    const DROPPED: [&[u8]; 2] = [b"EXIF", b"XMP "];
+    const EXIF_FLAG: u8 = 0x08;
+    const XMP_FLAG: u8 = 0x04;
+
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let riff_end = 8 + u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let data = data.get(..riff_end)?;
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..12]);
+
+    let mut i = 12;
+    while i < data.len() {
+        let len = u32::from_le_bytes([
+            *data.get(i + 4)?,
+            *data.get(i + 5)?,
+            *data.get(i + 6)?,
+            *data.get(i + 7)?,
+        ]) as usize;
+        // Type, size and data padded to an even size
+        let chunk = data.get(i..i + 8 + len)?;
+        let end = i + 8 + len + len % 2;
+        let chunk = data.get(i..end).unwrap_or(chunk);
+        let kind = &chunk[..4];
+        if kind == b"VP8X" {
+            let mut chunk = chunk.to_vec();
+            *chunk.get_mut(8)? &= !(EXIF_FLAG | XMP_FLAG);
+            out.extend_from_slice(&chunk);
+        } else if !DROPPED.contains(&kind) {
+            out.extend_from_slice(chunk);
+        }
+        i = end;
+    }
+
+    let riff_len = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg_segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xff, marker];
+        segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    fn webp_chunk(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(payload);
+        if payload.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn webp(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        data.extend_from_slice(b"WEBP");
+        data.extend_from_slice(&body);
+        data
+    }
+
+    #[test]
+    fn jpeg_keeps_the_color_profile() {
+        let app0 = jpeg_segment(0xe0, b"JFIF\0");
+        let exif = jpeg_segment(0xe1, b"Exif\0\0location");
+        let icc = jpeg_segment(0xe2, b"ICC_PROFILE\0");
+        let iptc = jpeg_segment(0xed, b"Photoshop 3.0\0");
+        let adobe = jpeg_segment(0xee, b"Adobe\0");
+        let comment = jpeg_segment(0xfe, b"comment");
+        let scan = [0xff, 0xda, 0x00, 0x02, 0x12, 0x34, 0xff, 0xd9];
+
+        let data = [
+            &[0xff, 0xd8][..],
+            &app0,
+            &exif,
+            &icc,
+            &iptc,
+            &adobe,
+            &comment,
+            &scan,
+        ]
+        .concat();
+        let expected = [&[0xff, 0xd8][..], &app0, &icc, &adobe, &scan].concat();
+        assert_eq!(strip(data, "image/jpeg"), expected);
+    }
+
+    #[test]
+    fn webp_drops_exif_and_xmp() {
+        // Extended header with the ICC, EXIF and XMP flags, and the canvas size
+        let vp8x = |flags| webp_chunk(b"VP8X", &[flags, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let iccp = webp_chunk(b"ICCP", b"profile");
+        let vp8 = webp_chunk(b"VP8 ", b"bitstream");
+        let exif = webp_chunk(b"EXIF", b"location");
+        let xmp = webp_chunk(b"XMP ", b"<x:xmpmeta/>");
+
+        let data = webp(&[vp8x(0x2c), iccp.clone(), vp8.clone(), exif, xmp]);
+        let expected = webp(&[vp8x(0x20), iccp, vp8]);
+        assert_eq!(strip(data, "image/webp"), expected);
+    }
+
+    #[test]
+    fn gif_drops_comments_and_xmp() {
+        // Header and logical screen descriptor without a global color table
+        let header: &[u8] = b"GIF89a\x01\x00\x01\x00\x00\x00\x00";
+        let netscape: &[u8] = b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00";
+        let comment: &[u8] = b"\x21\xfe\x07comment\x00";
+        let xmp: &[u8] = b"\x21\xff\x0bXMP DataXMP\x04<x/>\x00";
+        let control: &[u8] = b"\x21\xf9\x04\x00\x00\x00\x00\x00";
+        // Image descriptor with a local color table of 2 colors, and the image data
+        let image: &[u8] = b"\x2c\x00\x00\x00\x00\x01\x00\x01\x00\x80\
+            \x00\x00\x00\xff\xff\xff\x02\x02\x44\x01\x00";
+        let trailer: &[u8] = b"\x3b";
+
+        let data = [header, netscape, comment, xmp, control, image, trailer].concat();
+        let expected = [header, netscape, control, image, trailer].concat();
+        assert_eq!(strip(data, "image/gif"), expected);
+    }
+
+    #[test]
+    fn unparsable_images_are_kept() {
+        let data = b"\xff\xd8\xff\xe1\x00".to_vec();
+        assert_eq!(strip(data.clone(), "image/jpeg"), data);
+        let data = b"RIFF\x04\x00\x00\x00WEBP".to_vec();
+        assert_eq!(strip(data.clone(), "image/webp"), data);
+    }
+}
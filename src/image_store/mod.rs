@@ -0,0 +1,285 @@
+//! Storage of the uploaded images.
+//!
+//! Images are addressed by the hex-encoded SHA-256 of their content, so that the same image
+//! is stored once. The backend is chosen by `IMAGE_STORE`: `fs` (default) keeps the images
+//! under `IMAGE_STORE_PATH`, and `s3` in a bucket of any S3-compatible service, e.g. MinIO.
+use async_trait::async_trait;
+use diesel::prelude::*;
+use ring::digest;
+use std::fmt;
+
+use crate::context::GlobalCtx;
+use crate::models::{Image, ID};
+use crate::schema::{image, image_pending, image_upload, image_variant};
+
+mod fs;
+mod handlers;
+mod metadata;
+mod s3;
+mod variant;
+
+pub use handlers::{serve, upload};
+
+const DEFAULT_MAX_SIZE: usize = 5 * 1024 * 1024;
+
+/// Key-value storage of the image data, modeled after S3 objects
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> anyhow::Result<()>;
+    /// Data of the key, or `None` if it does not exist
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    async fn exists(&self, key: &str) -> anyhow::Result<bool>;
+}
+
+lazy_static! {
+    static ref STORE: Box<dyn ImageStore> = match dotenv::var("IMAGE_STORE").as_deref() {
+        Ok("s3") => Box::new(s3::S3Store::from_env()),
+        _ => Box::new(fs::FsStore::from_env()),
+    };
+    /// Maximum size of an uploaded image in bytes
+    pub static ref MAX_SIZE: usize = dotenv::var("IMAGE_MAX_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE);
+    /// Base url the images are served from
+    static ref PUBLIC_URL: String = dotenv::var("IMAGE_PUBLIC_URL")
+        .map(|url| url.trim_end_matches('/').to_owned())
+        .unwrap_or_else(|_| "/images".to_owned());
+}
+
+pub fn store() -> &'static dyn ImageStore {
+    STORE.as_ref()
+}
+
+/// Url of the image with the hash.
+pub fn url(hash: &str) -> String {
+    format!("{}/{}", *PUBLIC_URL, hash)
+}
+
+/// Hex-encoded SHA-256 of the data.
+pub fn hash(data: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, data).as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Detects the content type of the image from its magic bytes, or `None` if it is not an
+/// image type allowed.
+pub fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Error of an image which cannot be decoded
+#[derive(Debug)]
+pub struct InvalidImage(::image::ImageError);
+
+impl fmt::Display for InvalidImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid image: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidImage {}
+
+/// Saves the image without its metadata along with its variants, or returns the existing
+/// one with the same content. Either way the user is recorded as an uploader of it.
+pub async fn save(
+    ctx: &GlobalCtx,
+    data: Vec<u8>,
+    content_type: &str,
+    user_id: Option<ID>,
+) -> anyhow::Result<Image> {
+    let data = metadata::strip(data, content_type);
+    let hash = hash(&data);
+    let size = data.len() as i32;
+
+    let existing: Option<Image> = image::table
+        .filter(image::hash.eq(&hash))
+        .first(&ctx.get_conn()?)
+        .optional()?;
+    if let Some(existing) = existing {
+        insert_upload(&ctx.get_conn()?, existing.id, user_id)?;
+        return Ok(existing);
+    }
+
+    let (data, variants) = render_variants(data).await?;
+    let variants = variants.map_err(InvalidImage)?;
+    store().put(&hash, data, content_type).await?;
+    put_variants(&hash, &variants).await?;
+
+    // The same image may have been saved in the meantime
+    let conn = ctx.get_conn()?;
+    diesel::insert_into(image::table)
+        .values((
+            image::hash.eq(&hash),
+            image::content_type.eq(content_type),
+            image::size.eq(size),
+            image::user_id.eq(user_id),
+        ))
+        .on_conflict(image::hash)
+        .do_nothing()
+        .execute(&conn)?;
+    let image: Image = image::table.filter(image::hash.eq(&hash)).first(&conn)?;
+    insert_variants(&conn, image.id, &variants)?;
+    insert_upload(&conn, image.id, user_id)?;
+
+    Ok(image)
+}
+
+fn insert_upload(conn: &PgConnection, image_id: ID, user_id: Option<ID>) -> QueryResult<usize> {
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(0),
+    };
+
+    diesel::insert_into(image_upload::table)
+        .values((
+            image_upload::image_id.eq(image_id),
+            image_upload::user_id.eq(user_id),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)
+}
+
+/// Renders the variants in the blocking threads, handing the data back.
+async fn render_variants(
+    data: Vec<u8>,
+) -> anyhow::Result<(Vec<u8>, ::image::ImageResult<Vec<variant::RenderedVariant>>)> {
+    let result = tokio::task::spawn_blocking(move || {
+        let variants = variant::render(&data);
+        (data, variants)
+    })
+    .await?;
+
+    Ok(result)
+}
+
+async fn put_variants(hash: &str, variants: &[variant::RenderedVariant]) -> anyhow::Result<()> {
+    for variant in variants {
+        let key = variant.format.key(hash, variant.size as i32);
+        store()
+            .put(&key, variant.data.clone(), variant.format.content_type())
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn insert_variants(
+    conn: &PgConnection,
+    image_id: ID,
+    variants: &[variant::RenderedVariant],
+) -> QueryResult<usize> {
+    let values: Vec<_> = variants
+        .iter()
+        .map(|variant| {
+            (
+                image_variant::image_id.eq(image_id),
+                image_variant::size.eq(variant.size as i32),
+                image_variant::format.eq(variant.format),
+                image_variant::width.eq(variant.width as i32),
+                image_variant::height.eq(variant.height as i32),
+                image_variant::byte_size.eq(variant.data.len() as i32),
+            )
+        })
+        .collect();
+    if values.is_empty() {
+        return Ok(0);
+    }
+
+    diesel::insert_into(image_variant::table)
+        .values(&values)
+        .on_conflict_do_nothing()
+        .execute(conn)
+}
+
+/// Spawns the task moving the images left by the migration from the database into the
+/// store, without their metadata.
+pub fn init(ctx: GlobalCtx) {
+    tokio::spawn(async move {
+        if let Err(err) = move_pending(&ctx).await {
+            error!("Image store: unable to move the pending images: {}", err);
+        }
+    });
+}
+
+async fn move_pending(ctx: &GlobalCtx) -> anyhow::Result<()> {
+    let ids: Vec<ID> = image_pending::table
+        .select(image_pending::image_id)
+        .load(&ctx.get_conn()?)?;
+    if ids.is_empty() {
+        return Ok(());
+    }
+    info!("Image store: moving {} pending images", ids.len());
+
+    // One at a time, as the images can be large. The ones failing are left pending, to be
+    // moved on the next startup.
+    for id in ids {
+        if let Err(err) = move_one_pending(ctx, id).await {
+            error!(
+                "Image store: unable to move the pending image {}: {}",
+                id, err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the pending image into the store, rendering its variants.
+async fn move_one_pending(ctx: &GlobalCtx, id: ID) -> anyhow::Result<()> {
+    let pending: Option<(String, Vec<u8>)> = image_pending::table
+        .inner_join(image::table)
+        .filter(image_pending::image_id.eq(id))
+        .select((image::hash, image_pending::data))
+        .first(&ctx.get_conn()?)
+        .optional()?;
+    let (hash, data) = match pending {
+        Some(pending) => pending,
+        // Moved by another instance
+        None => return Ok(()),
+    };
+    let content_type = sniff_content_type(&data).unwrap_or("application/octet-stream");
+    // Still stored under the hash of the data migrated, which the urls are made of
+    let data = metadata::strip(data, content_type);
+    let size = data.len() as i32;
+
+    let (data, variants) = render_variants(data).await?;
+    let variants = variants.unwrap_or_else(|err| {
+        warn!(
+            "Image store: unable to render the variants of {}: {}",
+            id, err
+        );
+        Vec::new()
+    });
+    if !store().exists(&hash).await? {
+        store().put(&hash, data, content_type).await?;
+    }
+    put_variants(&hash, &variants).await?;
+
+    let conn = ctx.get_conn()?;
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        insert_variants(&conn, id, &variants)?;
+        diesel::update(image::table)
+            .filter(image::id.eq(id))
+            .set((image::content_type.eq(content_type), image::size.eq(size)))
+            .execute(&conn)?;
+        diesel::delete(image_pending::table.filter(image_pending::image_id.eq(id)))
+            .execute(&conn)?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Method, StatusCode, Url};
+use ring::{digest, hmac};
+use std::time::Duration;
+
+use super::{hex, ImageStore};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Stores the images as objects of a bucket in an S3-compatible service, addressed by
+/// path-style urls and authorized with AWS Signature Version 4.
+pub struct S3Store {
+    client: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Store {
+    pub fn from_env() -> Self {
+        let var =
+            |key: &str| dotenv::var(key).unwrap_or_else(|_| panic!("Invalid {} env var", key));
+
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .build()
+                .expect("Unable to build the S3 client"),
+            endpoint: Url::parse(&var("S3_ENDPOINT")).expect("Invalid S3_ENDPOINT env var"),
+            bucket: var("S3_BUCKET"),
+            region: dotenv::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_owned()),
+            access_key: var("S3_ACCESS_KEY_ID"),
+            secret_key: var("S3_SECRET_ACCESS_KEY"),
+        }
+    }
+
+    /// Sends the signed request of the object.
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> anyhow::Result<reqwest::Response> {
+        let path = format!("/{}/{}", self.bucket, key);
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(anyhow::anyhow!("No host in S3_ENDPOINT")),
+        };
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(digest::digest(&digest::SHA256, &body).as_ref());
+
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, SIGNED_HEADERS, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let signature = hex(&self.signing_key(&date).sign(string_to_sign.as_bytes()));
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key, scope, SIGNED_HEADERS, signature
+                ),
+            );
+        if let Some(content_type) = content_type {
+            request = request.header("Content-Type", content_type);
+        }
+
+        Ok(request.body(body).send().await?)
+    }
+
+    fn signing_key(&self, date: &str) -> SigningKey {
+        SigningKey(format!("AWS4{}", self.secret_key).into_bytes())
+            .derive(date.as_bytes())
+            .derive(self.region.as_bytes())
+            .derive(b"s3")
+            .derive(b"aws4_request")
+    }
+}
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Key of the HMAC-SHA256 chain deriving the signing key
+struct SigningKey(Vec<u8>);
+
+impl SigningKey {
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &self.0);
+        hmac::sign(&key, data).as_ref().to_vec()
+    }
+
+    fn derive(&self, data: &[u8]) -> SigningKey {
+        SigningKey(self.sign(data))
+    }
+}
+
+#[async_trait]
+impl ImageStore for S3Store {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> anyhow::Result<()> {
+        self.send(Method::PUT, key, data, Some(content_type))
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let response = self.send(Method::GET, key, Vec::new(), None).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let data = response.error_for_status()?.bytes().await?;
+
+        Ok(Some(data.to_vec()))
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        let response = self.send(Method::HEAD, key, Vec::new(), None).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        response.error_for_status()?;
+
+        Ok(true)
+    }
+}
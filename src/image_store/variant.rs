@@ -0,0 +1,104 @@
+//! Resized variants of the images, e.g. thumbnails in the puzzle lists.
+//!
+//! Every image is rendered as WebP and JPEG within each of `IMAGE_VARIANT_SIZES`, keeping
+//! its aspect ratio and never upscaled. Re-encoding also leaves out any metadata.
+//!
+//! Images larger than `IMAGE_MAX_PIXELS` are refused before being decoded, as a small file
+//! may claim huge dimensions.
+use image::{
+    error::{LimitError, LimitErrorKind},
+    io::Reader,
+    DynamicImage, GenericImageView, ImageError, ImageOutputFormat,
+};
+use std::io::Cursor;
+
+use crate::models::image::VariantFormat;
+
+const DEFAULT_SIZES: &str = "128,512";
+const DEFAULT_QUALITY: u8 = 80;
+const DEFAULT_MAX_PIXELS: u64 = 50_000_000;
+
+lazy_static! {
+    /// Maximum widths and heights of the variants
+    pub static ref SIZES: Vec<u32> = dotenv::var("IMAGE_VARIANT_SIZES")
+        .unwrap_or_else(|_| DEFAULT_SIZES.to_owned())
+        .split(',')
+        .filter_map(|size| size.trim().parse().ok())
+        .filter(|size| *size > 0)
+        .collect();
+    /// Quality of the lossy encodings, from 1 to 100
+    static ref QUALITY: u8 = dotenv::var("IMAGE_VARIANT_QUALITY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|quality| (1..=100).contains(quality))
+        .unwrap_or(DEFAULT_QUALITY);
+    /// Maximum number of pixels of the images decoded
+    static ref MAX_PIXELS: u64 = dotenv::var("IMAGE_MAX_PIXELS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MAX_PIXELS);
+}
+
+/// A variant rendered, to be saved into the store
+pub struct RenderedVariant {
+    pub size: u32,
+    pub format: VariantFormat,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Decodes the image, checking its dimensions in the header first.
+fn decode(data: &[u8]) -> image::ImageResult<DynamicImage> {
+    let reader = || Reader::new(Cursor::new(data)).with_guessed_format();
+
+    let (width, height) = reader()?.into_dimensions()?;
+    if width as u64 * height as u64 > *MAX_PIXELS {
+        return Err(ImageError::Limits(LimitError::from_kind(
+            LimitErrorKind::DimensionError,
+        )));
+    }
+
+    reader()?.decode()
+}
+
+/// Renders the variants of the image. Fails if the image cannot be decoded or is too large.
+pub fn render(data: &[u8]) -> image::ImageResult<Vec<RenderedVariant>> {
+    let image = decode(data)?;
+
+    let mut variants = Vec::with_capacity(SIZES.len() * 2);
+    for &size in SIZES.iter() {
+        let resized = if image.width() <= size && image.height() <= size {
+            image.clone()
+        } else {
+            image.thumbnail(size, size)
+        };
+        let (width, height) = resized.dimensions();
+        let variant = |format, data| RenderedVariant {
+            size,
+            format,
+            width,
+            height,
+            data,
+        };
+
+        // Only 8-bit RGB(A) can be encoded as WebP
+        let webp = if resized.color().has_alpha() {
+            DynamicImage::ImageRgba8(resized.to_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(resized.to_rgb8())
+        };
+        let webp = webp::Encoder::from_image(&webp)
+            .encode(*QUALITY as f32)
+            .to_vec();
+        variants.push(variant(VariantFormat::Webp, webp));
+
+        // JPEG has no alpha channel
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(resized.to_rgb8())
+            .write_to(&mut jpeg, ImageOutputFormat::Jpeg(*QUALITY))?;
+        variants.push(variant(VariantFormat::Jpeg, jpeg));
+    }
+
+    Ok(variants)
+}
@@ -0,0 +1,149 @@
+//! Outgoing mails, sent through the transport specified with `MAIL_TRANSPORT`.
+use anyhow::{anyhow, Context};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::RwLock;
+
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mail {
+    /// Formats the mail as a plain text message.
+    pub fn to_message(&self) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            &*MAIL_FROM, self.to, self.subject, self.body
+        )
+    }
+}
+
+/// Transport delivering mails.
+pub trait MailTransport: Send + Sync {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()>;
+}
+
+/// The default transport, which only logs the mails.
+pub struct NoopTransport;
+
+impl MailTransport for NoopTransport {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        debug!("Mail to {} is not sent: {}", mail.to, mail.subject);
+        Ok(())
+    }
+}
+
+/// Transport writing each mail to a file in a directory, for development and testing.
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl MailTransport for FileTransport {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let filename = format!(
+            "{}-{:08x}.eml",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            rand::random::<u32>()
+        );
+        std::fs::write(self.dir.join(filename), mail.to_message())?;
+        Ok(())
+    }
+}
+
+/// Transport piping mails to a sendmail-compatible command.
+pub struct SendmailTransport {
+    command: String,
+}
+
+impl SendmailTransport {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+impl MailTransport for SendmailTransport {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        let mut child = Command::new(&self.command)
+            .arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Unable to run `{}`", &self.command))?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("No stdin"))?
+            .write_all(mail.to_message().as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("`{}` exited with {}", &self.command, status));
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref TRANSPORT: RwLock<Box<dyn MailTransport>> = RwLock::new(Box::new(NoopTransport));
+    static ref MAIL_FROM: String =
+        dotenv::var("MAIL_FROM").unwrap_or_else(|_| "noreply@localhost".to_string());
+}
+
+/// Sets up the mail transport specified with `MAIL_TRANSPORT`.
+pub fn init() {
+    let transport = dotenv::var("MAIL_TRANSPORT").unwrap_or_else(|_| "none".to_string());
+    let backend: Box<dyn MailTransport> = match transport.as_str() {
+        "none" => Box::new(NoopTransport),
+        "file" => Box::new(FileTransport::new(
+            dotenv::var("MAIL_FILE_DIR")
+                .unwrap_or_else(|_| "mails".to_string())
+                .into(),
+        )),
+        "sendmail" => Box::new(SendmailTransport::new(
+            dotenv::var("MAIL_SENDMAIL_COMMAND").unwrap_or_else(|_| "sendmail".to_string()),
+        )),
+        _ => panic!("Invalid MAIL_TRANSPORT env var: {}", transport),
+    };
+    *TRANSPORT.write().unwrap() = backend;
+    info!("Mail transport: {}", transport);
+}
+
+/// Sends the mail with the configured transport.
+pub fn send(mail: &Mail) -> anyhow::Result<()> {
+    TRANSPORT.read().unwrap().send(mail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_transport_writes_the_message() {
+        let dir = std::env::temp_dir().join(format!("cindy-mail-{:08x}", rand::random::<u32>()));
+        let mail = Mail {
+            to: "alice@example.com".to_string(),
+            subject: "Digest".to_string(),
+            body: "Hello".to_string(),
+        };
+
+        FileTransport::new(dir.clone()).send(&mail).unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let message = std::fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert_eq!(message, mail.to_message());
+        assert!(message.contains("To: alice@example.com\r\nSubject: Digest\r\n"));
+        assert!(message.ends_with("\r\n\r\nHello"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
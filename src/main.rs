@@ -25,9 +25,16 @@ mod auth;
 mod broker;
 pub mod context;
 pub mod db;
+mod digest;
 pub mod gql_schema;
+mod image_store;
+mod mail;
+mod persisted_query;
+mod presence;
+mod scheduler;
 mod schema;
 mod schema_view;
+mod webhook;
 
 use auth::{login, signup, Role};
 use context::{GlobalCtx, RequestCtx};
@@ -45,20 +52,7 @@ async fn index(schema: web::Data<CindySchema>, req: HttpRequest, gql_req: Reques
     let connection_info = req.connection_info();
 
     // Authorization info
-    let token = headers.get("Authorization").and_then(|value| {
-        value
-            .to_str()
-            .ok()
-            // Drop `Bearer `
-            .and_then(|v| v.splitn(2, ' ').nth(1))
-            .map(|v| v.to_string())
-    });
-    let admin_secret = headers
-        .get("X-CINDY-ADMIN-SECRET")
-        .and_then(|value| value.to_str().map(|v| v.to_owned()).ok());
-    let ctx = RequestCtx::default()
-        .with_token(token)
-        .with_secret(admin_secret);
+    let ctx = RequestCtx::from_headers(headers);
 
     // Logging the IP address
     let gql_req = gql_req.into_inner();
@@ -107,7 +101,35 @@ async fn index_ws(
     req: HttpRequest,
     payload: web::Payload,
 ) -> Result<HttpResponse> {
-    WSSubscription::start(Schema::clone(&*schema), &req, payload)
+    WSSubscription::start_with_initializer(
+        Schema::clone(&*schema),
+        &req,
+        payload,
+        |init_payload| async move {
+            let mut data = async_graphql::Data::default();
+            data.insert(ws_request_ctx(&init_payload));
+            Ok(data)
+        },
+    )
+}
+
+/// Builds the request context from the `connection_init` payload of a websocket, which
+/// carries the same authorization info as the headers of a http request.
+fn ws_request_ctx(init_payload: &serde_json::Value) -> RequestCtx {
+    let get_str = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| init_payload.get(key).and_then(|value| value.as_str()))
+            .map(|value| value.to_string())
+    };
+
+    let token = get_str(&["Authorization", "authorization"])
+        // Drop `Bearer `
+        .and_then(|v| v.splitn(2, ' ').nth(1).map(|v| v.to_string()));
+    let admin_secret = get_str(&["X-CINDY-ADMIN-SECRET", "x-cindy-admin-secret"]);
+
+    RequestCtx::default()
+        .with_token(token)
+        .with_secret(admin_secret)
 }
 
 #[actix_rt::main]
@@ -141,14 +163,25 @@ async fn main() -> std::io::Result<()> {
         })
         .init();
 
+    persisted_query::init();
+    mail::init();
+
     let endpoint = dotenv::var("ENDPOINT").unwrap_or("127.0.0.1:8000".to_string());
     let ctx = GlobalCtx::default();
+    broker::init(&ctx);
+    presence::init();
+    digest::init(ctx.clone());
+    webhook::init(ctx.clone());
+    image_store::init(ctx.clone());
+    // Spawn job scheduler
+    scheduler::init(ctx.clone());
     let schema = Schema::build(
         QueryRoot::default(),
         MutationRoot::default(),
         SubscriptionRoot::default(),
     )
     .data(ctx.clone())
+    .extension(persisted_query::PersistedQuery)
     .finish();
 
     info!("Server started on: http://{}/graphql", &endpoint);
@@ -209,6 +242,16 @@ async fn main() -> std::io::Result<()> {
             .service(web::resource("/graphql").guard(guard::Post()).to(index))
             .service(web::resource("/login").guard(guard::Post()).to(login))
             .service(web::resource("/signup").guard(guard::Post()).to(signup))
+            .service(
+                web::resource("/images")
+                    .guard(guard::Post())
+                    .to(image_store::upload),
+            )
+            .service(
+                web::resource("/images/{key}")
+                    .guard(guard::Get())
+                    .to(image_store::serve),
+            )
             .service(
                 web::resource("/graphql")
                     .guard(guard::Get())
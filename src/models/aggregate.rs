@@ -0,0 +1,188 @@
+use async_graphql::{self, Enum, Object};
+use diesel::{
+    pg::PgConnection,
+    query_builder::{AstPass, Query, QueryFragment, QueryId},
+    sql_types::{BigInt, Double, Nullable},
+    QueryResult, RunQueryDsl,
+};
+use std::marker::PhantomData;
+
+use super::generics::*;
+
+/// Available aggregate functions
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// A column a table can be grouped by in the aggregate query
+pub trait AggregateGroupBy: Copy {
+    /// Index of the group column in the result row
+    fn column(self) -> usize;
+    /// SQL expression of the group key, evaluated on the filtered rows `t`
+    fn expression(self) -> &'static str;
+}
+
+/// A column a table can be aggregated on with sum/avg/min/max
+pub trait AggregateField: Copy {
+    /// SQL expression of the numeric value, evaluated on the filtered rows `t`
+    fn expression(self) -> &'static str;
+}
+
+/// Result row of an aggregate query
+pub trait Aggregate {
+    type SqlType;
+    type GroupBy: AggregateGroupBy;
+    type Field: AggregateField;
+
+    /// Typed NULLs selected for the group columns which are not grouped by
+    const GROUP_COLUMNS: &'static [&'static str];
+}
+
+pub type AggregateValuesSqlType = (
+    Nullable<BigInt>,
+    Nullable<Double>,
+    Nullable<Double>,
+    Nullable<Double>,
+    Nullable<Double>,
+);
+
+/// Values of the aggregate functions.
+///
+/// Functions not requested are always `null`.
+#[derive(Queryable, Clone, Debug)]
+pub struct AggregateValues {
+    pub count: Option<i64>,
+    pub sum: Option<f64>,
+    pub avg: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[Object]
+impl AggregateValues {
+    async fn count(&self) -> Option<i64> {
+        self.count
+    }
+    async fn sum(&self) -> Option<f64> {
+        self.sum
+    }
+    async fn avg(&self) -> Option<f64> {
+        self.avg
+    }
+    async fn min(&self) -> Option<f64> {
+        self.min
+    }
+    async fn max(&self) -> Option<f64> {
+        self.max
+    }
+}
+
+/// Query wrapping a filtered query as a subquery, aggregating its rows.
+///
+/// The generated SQL looks like
+/// `SELECT <group columns>, <aggregates> FROM (<query>) t GROUP BY <group columns>`.
+pub struct AggregateQuery<Q, A> {
+    query: Q,
+    selection: String,
+    group_by: Vec<&'static str>,
+    _marker: PhantomData<A>,
+}
+
+impl<Q, A: Aggregate> AggregateQuery<Q, A> {
+    pub fn new(
+        query: Q,
+        group_by: Vec<A::GroupBy>,
+        functions: Vec<AggregateFunction>,
+        field: Option<A::Field>,
+    ) -> async_graphql::Result<Self> {
+        let mut columns: Vec<Option<&'static str>> = vec![None; A::GROUP_COLUMNS.len()];
+        let mut groups = Vec::new();
+        for item in group_by {
+            let expression = item.expression();
+            match columns[item.column()] {
+                Some(exp) if exp == expression => continue,
+                Some(_) => {
+                    return Err(async_graphql::Error::new("Conflicting columns in group_by"))
+                }
+                None => {
+                    columns[item.column()] = Some(expression);
+                    groups.push(expression);
+                }
+            }
+        }
+
+        let mut selection: Vec<String> = columns
+            .iter()
+            .zip(A::GROUP_COLUMNS.iter())
+            .map(|(column, null)| column.unwrap_or(null).to_string())
+            .collect();
+
+        selection.push(if functions.contains(&AggregateFunction::Count) {
+            "count(*)".to_string()
+        } else {
+            "NULL::int8".to_string()
+        });
+        for (function, name) in [
+            (AggregateFunction::Sum, "sum"),
+            (AggregateFunction::Avg, "avg"),
+            (AggregateFunction::Min, "min"),
+            (AggregateFunction::Max, "max"),
+        ]
+        .iter()
+        {
+            selection.push(if functions.contains(function) {
+                let field = field.ok_or_else(|| {
+                    async_graphql::Error::new(format!("A field is required to apply `{}`", name))
+                })?;
+                format!("{}({})::float8", name, field.expression())
+            } else {
+                "NULL::float8".to_string()
+            });
+        }
+
+        Ok(Self {
+            query,
+            selection: selection.join(", "),
+            group_by: groups,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<Q, A> QueryFragment<DB> for AggregateQuery<Q, A>
+where
+    Q: QueryFragment<DB>,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql("SELECT ");
+        out.push_sql(&self.selection);
+        out.push_sql(" FROM (");
+        self.query.walk_ast(out.reborrow())?;
+        out.push_sql(") t");
+        if !self.group_by.is_empty() {
+            let group_by = self.group_by.join(", ");
+            out.push_sql(" GROUP BY ");
+            out.push_sql(&group_by);
+            out.push_sql(" ORDER BY ");
+            out.push_sql(&group_by);
+        }
+        Ok(())
+    }
+}
+
+impl<Q, A> QueryId for AggregateQuery<Q, A> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Q, A: Aggregate> Query for AggregateQuery<Q, A> {
+    type SqlType = A::SqlType;
+}
+
+impl<Q, A> RunQueryDsl<PgConnection> for AggregateQuery<Q, A> {}
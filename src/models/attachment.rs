@@ -0,0 +1,149 @@
+use async_graphql::{self, Context, Enum, Object};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    expression::{helper_types::AsExprOf, AsExpression},
+    prelude::*,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io;
+
+use crate::context::GlobalCtx;
+use crate::schema::attachment;
+
+use super::*;
+
+/// What an image is attached to
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow)]
+pub enum AttachmentTarget {
+    /// Content of the puzzle
+    PuzzleContent = 0,
+    /// Solution of the puzzle, hidden along with it
+    PuzzleSolution = 1,
+    Hint = 2,
+    /// Answer of the dialogue
+    DialogueAnswer = 3,
+    Comment = 4,
+}
+
+impl<DB> ToSql<Integer, DB> for AttachmentTarget
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for AttachmentTarget {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for AttachmentTarget
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(AttachmentTarget::PuzzleContent),
+            1 => Ok(AttachmentTarget::PuzzleSolution),
+            2 => Ok(AttachmentTarget::Hint),
+            3 => Ok(AttachmentTarget::DialogueAnswer),
+            4 => Ok(AttachmentTarget::Comment),
+            v => Err(format!("Invalid value `{}` for attachment target", &v).into()),
+        }
+    }
+}
+
+/// Object for attachment table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "attachment"]
+pub struct Attachment {
+    pub id: ID,
+    pub image_id: ID,
+    pub target: AttachmentTarget,
+    pub puzzle_id: Option<ID>,
+    pub hint_id: Option<ID>,
+    pub dialogue_id: Option<ID>,
+    pub comment_id: Option<ID>,
+    pub position: i32,
+    pub alt_text: String,
+    /// The user attaching the image, not exposed as it would unmask anonymous authors
+    pub user_id: Option<ID>,
+    pub created: Timestamptz,
+}
+
+impl Attachment {
+    /// Attachments of the target in order.
+    pub fn load_of(
+        conn: &PgConnection,
+        target: AttachmentTarget,
+        target_id: ID,
+    ) -> QueryResult<Vec<Attachment>> {
+        let mut query = attachment::table
+            .filter(attachment::target.eq(target))
+            .into_boxed();
+        query = match target {
+            AttachmentTarget::PuzzleContent | AttachmentTarget::PuzzleSolution => {
+                query.filter(attachment::puzzle_id.eq(target_id))
+            }
+            AttachmentTarget::Hint => query.filter(attachment::hint_id.eq(target_id)),
+            AttachmentTarget::DialogueAnswer => query.filter(attachment::dialogue_id.eq(target_id)),
+            AttachmentTarget::Comment => query.filter(attachment::comment_id.eq(target_id)),
+        };
+
+        query
+            .order((attachment::position.asc(), attachment::id.asc()))
+            .load(conn)
+    }
+}
+
+#[Object]
+impl Attachment {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn image_id(&self) -> ID {
+        self.image_id
+    }
+    async fn target(&self) -> AttachmentTarget {
+        self.target
+    }
+    /// Id of the puzzle, hint, dialogue or comment
+    async fn target_id(&self) -> ID {
+        self.puzzle_id
+            .or(self.hint_id)
+            .or(self.dialogue_id)
+            .or(self.comment_id)
+            .unwrap_or_default()
+    }
+    async fn position(&self) -> i32 {
+        self.position
+    }
+    async fn alt_text(&self) -> &str {
+        &self.alt_text
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+
+    async fn image(&self, ctx: &Context<'_>) -> async_graphql::Result<Image> {
+        use crate::schema::image;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let image = image::table
+            .filter(image::id.eq(self.image_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(image)
+    }
+}
@@ -1,9 +1,14 @@
-use async_graphql::{self, Context, InputObject, Object};
-use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
-
-use crate::context::GlobalCtx;
+use async_graphql::{self, Context, Enum, InputObject, Object};
+use diesel::{
+    prelude::*,
+    query_dsl::QueryDsl,
+    sql_types::{Bool, Int2, Int4, Nullable},
+};
+
+use crate::context::{GlobalCtx, RequestCtx};
 use crate::schema::bookmark;
 
+use super::aggregate::*;
 use super::*;
 
 /// Available orders for bookmark query
@@ -144,12 +149,96 @@ impl Bookmark {
         use crate::schema::puzzle;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let puzzle = puzzle::table
+        let puzzle: Puzzle = puzzle::table
             .filter(puzzle::id.eq(self.puzzle_id))
             .limit(1)
             .first(&conn)?;
+        if !puzzle.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
 
         Ok(puzzle)
     }
 }
+
+/// Available group-by columns for bookmark aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum BookmarkAggregateGroupBy {
+    UserId,
+    PuzzleId,
+    Value,
+}
+
+impl AggregateGroupBy for BookmarkAggregateGroupBy {
+    fn column(self) -> usize {
+        match self {
+            BookmarkAggregateGroupBy::UserId => 0,
+            BookmarkAggregateGroupBy::PuzzleId => 1,
+            BookmarkAggregateGroupBy::Value => 2,
+        }
+    }
+
+    fn expression(self) -> &'static str {
+        match self {
+            BookmarkAggregateGroupBy::UserId => "t.user_id",
+            BookmarkAggregateGroupBy::PuzzleId => "t.puzzle_id",
+            BookmarkAggregateGroupBy::Value => "t.value",
+        }
+    }
+}
+
+/// Available fields to apply sum/avg/min/max in bookmark aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum BookmarkAggregateField {
+    Value,
+}
+
+impl AggregateField for BookmarkAggregateField {
+    fn expression(self) -> &'static str {
+        match self {
+            BookmarkAggregateField::Value => "t.value",
+        }
+    }
+}
+
+/// A row of bookmark aggregate query.
+///
+/// Group columns not grouped by are always `null`.
+#[derive(Queryable, Clone, Debug)]
+pub struct BookmarkAggregate {
+    pub user_id: Option<ID>,
+    pub puzzle_id: Option<ID>,
+    pub value: Option<i16>,
+    pub values: AggregateValues,
+}
+
+impl Aggregate for BookmarkAggregate {
+    type SqlType = (
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Int2>,
+        AggregateValuesSqlType,
+    );
+    type GroupBy = BookmarkAggregateGroupBy;
+    type Field = BookmarkAggregateField;
+
+    const GROUP_COLUMNS: &'static [&'static str] = &["NULL::int4", "NULL::int4", "NULL::int2"];
+}
+
+#[Object]
+impl BookmarkAggregate {
+    async fn user_id(&self) -> Option<ID> {
+        self.user_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        self.puzzle_id
+    }
+    async fn value(&self) -> Option<i16> {
+        self.value
+    }
+    async fn values(&self) -> &AggregateValues {
+        &self.values
+    }
+}
@@ -1,7 +1,10 @@
 use async_graphql::{self, Context, InputObject, Object};
 use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
+use serde::{Deserialize, Serialize};
 
-use crate::context::GlobalCtx;
+use crate::auth::Role;
+use crate::broker::{BrokerMessage, Event, EventId};
+use crate::context::{GlobalCtx, RequestCtx};
 use crate::schema::chatmessage;
 
 use super::*;
@@ -83,25 +86,44 @@ impl CindyFilter<chatmessage::table, DB> for ChatmessageFilter {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum ChatmessageSub {
     Created(Chatmessage),
     Updated(Chatmessage, Chatmessage),
+    Deleted(Chatmessage),
+    Resync,
 }
 
-#[Object]
-impl ChatmessageSub {
+impl BrokerMessage for ChatmessageSub {
+    const TOPIC: &'static str = "chatmessage";
+    const DURABLE: bool = true;
+
+    fn resync() -> Option<Self> {
+        Some(ChatmessageSub::Resync)
+    }
+}
+
+#[Object(name = "ChatmessageSub")]
+impl Event<ChatmessageSub> {
+    async fn event_id(&self) -> Option<EventId> {
+        self.id
+    }
+
     async fn op(&self) -> DbOp {
-        match &self {
+        match &self.msg {
             ChatmessageSub::Created(_) => DbOp::Created,
             ChatmessageSub::Updated(_, _) => DbOp::Updated,
+            ChatmessageSub::Deleted(_) => DbOp::Deleted,
+            ChatmessageSub::Resync => DbOp::Resync,
         }
     }
 
-    async fn data(&self) -> Chatmessage {
-        match &self {
-            ChatmessageSub::Created(cm) => cm.clone(),
-            ChatmessageSub::Updated(_, cm) => cm.clone(),
+    async fn data(&self) -> Option<Chatmessage> {
+        match &self.msg {
+            ChatmessageSub::Created(cm) => Some(cm.clone()),
+            ChatmessageSub::Updated(_, cm) => Some(cm.clone()),
+            ChatmessageSub::Deleted(cm) => Some(cm.clone()),
+            ChatmessageSub::Resync => None,
         }
     }
 }
@@ -133,7 +155,7 @@ impl CindyFilter<chatmessage::table, DB> for ChatmessageCountFilter {
 }
 
 /// Object for chatmessage table
-#[derive(Queryable, Identifiable, Clone, Debug)]
+#[derive(Queryable, Identifiable, Clone, Debug, Serialize, Deserialize)]
 #[table_name = "chatmessage"]
 pub struct Chatmessage {
     pub id: ID,
@@ -146,6 +168,39 @@ pub struct Chatmessage {
     pub modified: Timestamptz,
 }
 
+impl Chatmessage {
+    /// Expression of the messages visible to the request, or `None` if all of them are.
+    ///
+    /// Messages in private chatrooms are hidden as in `Chatroom::is_visible_to`.
+    pub fn visible_expression(
+        reqctx: &RequestCtx,
+    ) -> Option<Box<dyn BoxableExpression<chatmessage::table, DB, SqlType = Bool> + Send>> {
+        use crate::schema::{chatroom, favorite_chatroom};
+
+        let public = chatroom::table
+            .filter(chatroom::private.eq(false))
+            .select(chatroom::id);
+        match (reqctx.get_role(), reqctx.get_user_id()) {
+            (Role::Admin, _) => None,
+            (Role::User, Some(user_id)) => Some(Box::new(
+                chatmessage::chatroom_id
+                    .eq_any(public)
+                    .or(chatmessage::chatroom_id.eq_any(
+                        chatroom::table
+                            .filter(chatroom::user_id.eq(user_id))
+                            .select(chatroom::id),
+                    ))
+                    .or(chatmessage::chatroom_id.eq_any(
+                        favorite_chatroom::table
+                            .filter(favorite_chatroom::user_id.eq(user_id))
+                            .select(favorite_chatroom::chatroom_id),
+                    )),
+            )),
+            _ => Some(Box::new(chatmessage::chatroom_id.eq_any(public))),
+        }
+    }
+}
+
 #[Object]
 impl Chatmessage {
     async fn id(&self) -> ID {
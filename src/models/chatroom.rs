@@ -1,7 +1,8 @@
 use async_graphql::{self, Context, InputObject, Object};
-use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
+use diesel::{pg::PgConnection, prelude::*, query_dsl::QueryDsl, sql_types::Bool};
 
-use crate::context::GlobalCtx;
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
 use crate::schema::chatroom;
 
 use super::chatmessage::{ChatmessageFilter, ChatmessageOrder};
@@ -96,6 +97,41 @@ pub struct Chatroom {
     pub private: bool,
 }
 
+impl Chatroom {
+    /// Whether the messages in the chatroom are visible to the request.
+    ///
+    /// Private chatrooms are only visible to admins, the owner and the members, who are
+    /// the users the owner added the chatroom to the favorites of.
+    pub fn is_visible_to(&self, conn: &PgConnection, reqctx: &RequestCtx) -> QueryResult<bool> {
+        use crate::schema::favorite_chatroom;
+
+        if !self.private {
+            return Ok(true);
+        }
+
+        match reqctx.get_role() {
+            Role::Admin => Ok(true),
+            Role::Guest => Ok(false),
+            Role::User => {
+                let user_id = match reqctx.get_user_id() {
+                    Some(user_id) => user_id,
+                    None => return Ok(false),
+                };
+                if user_id == self.user_id {
+                    return Ok(true);
+                }
+
+                let favorited: i64 = favorite_chatroom::table
+                    .filter(favorite_chatroom::chatroom_id.eq(self.id))
+                    .filter(favorite_chatroom::user_id.eq(user_id))
+                    .count()
+                    .get_result(conn)?;
+                Ok(favorited > 0)
+            }
+        }
+    }
+}
+
 #[Object]
 impl Chatroom {
     async fn id(&self) -> ID {
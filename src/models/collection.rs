@@ -0,0 +1,281 @@
+use async_graphql::{self, Context, InputObject, Object};
+use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::schema::{collection, collection_editor, collection_puzzle, puzzle};
+
+use super::*;
+
+/// Available orders for collection query
+#[derive(InputObject, Clone)]
+pub struct CollectionOrder {
+    id: Option<Ordering>,
+    title: Option<Ordering>,
+    created: Option<Ordering>,
+    modified: Option<Ordering>,
+}
+
+/// Helper object to apply the order to the query
+pub struct CollectionOrders(Vec<CollectionOrder>);
+
+impl Default for CollectionOrders {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl CollectionOrders {
+    pub fn new(orders: Vec<CollectionOrder>) -> Self {
+        Self(orders)
+    }
+
+    pub fn apply_order<'a>(
+        self,
+        query_dsl: collection::BoxedQuery<'a, DB>,
+    ) -> collection::BoxedQuery<'a, DB> {
+        use crate::schema::collection::dsl::*;
+
+        let mut query = query_dsl;
+
+        for obj in self.0 {
+            gen_order!(obj, id, query);
+            gen_order!(obj, title, query);
+            gen_order!(obj, created, query);
+            gen_order!(obj, modified, query);
+        }
+
+        query
+    }
+}
+
+/// Available filters for collection query
+#[derive(InputObject, Clone, Default)]
+pub struct CollectionFilter {
+    pub id: Option<I32Filtering>,
+    pub user_id: Option<I32Filtering>,
+    pub title: Option<StringFiltering>,
+    pub description: Option<StringFiltering>,
+    pub public: Option<bool>,
+    pub created: Option<TimestamptzFiltering>,
+    pub modified: Option<TimestamptzFiltering>,
+}
+
+impl CindyFilter<collection::table, DB> for CollectionFilter {
+    fn as_expression(
+        self,
+    ) -> Option<Box<dyn BoxableExpression<collection::table, DB, SqlType = Bool> + Send>> {
+        use crate::schema::collection::dsl::*;
+
+        let mut filter: Option<Box<dyn BoxableExpression<collection, DB, SqlType = Bool> + Send>> =
+            None;
+        let CollectionFilter {
+            id: obj_id,
+            user_id: obj_user_id,
+            title: obj_title,
+            description: obj_description,
+            public: obj_public,
+            created: obj_created,
+            modified: obj_modified,
+        } = self;
+        gen_number_filter!(obj_id: I32Filtering, id, filter);
+        gen_number_filter!(obj_user_id: I32Filtering, user_id, filter);
+        gen_string_filter!(obj_title, title, filter);
+        gen_string_filter!(obj_description, description, filter);
+        gen_bool_filter!(obj_public, public, filter);
+        gen_number_filter!(obj_created: TimestamptzFiltering, created, filter);
+        gen_number_filter!(obj_modified: TimestamptzFiltering, modified, filter);
+        filter
+    }
+}
+
+/// Object for collection table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "collection"]
+pub struct Collection {
+    pub id: ID,
+    /// The owner
+    pub user_id: ID,
+    pub title: String,
+    pub description: String,
+    /// Private collections are only visible to the owner, the editors and admins
+    pub public: bool,
+    pub created: Timestamptz,
+    pub modified: Timestamptz,
+}
+
+impl Collection {
+    /// Whether the user is one of the editors of the collection.
+    pub fn is_editor(&self, conn: &PgConnection, user_id: Option<ID>) -> QueryResult<bool> {
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None => return Ok(false),
+        };
+        let count: i64 = collection_editor::table
+            .filter(collection_editor::collection_id.eq(self.id))
+            .filter(collection_editor::user_id.eq(user_id))
+            .count()
+            .get_result(conn)?;
+
+        Ok(count > 0)
+    }
+
+    /// Whether the collection is visible to the request.
+    pub fn is_visible_to(&self, conn: &PgConnection, reqctx: &RequestCtx) -> QueryResult<bool> {
+        if self.public
+            || reqctx.get_role() == Role::Admin
+            || reqctx.get_user_id() == Some(self.user_id)
+        {
+            return Ok(true);
+        }
+
+        self.is_editor(conn, reqctx.get_user_id())
+    }
+
+    /// Expression of the collections visible to the request, or `None` if all of them are.
+    pub fn visible_expression(
+        reqctx: &RequestCtx,
+    ) -> Option<Box<dyn BoxableExpression<collection::table, DB, SqlType = Bool> + Send>> {
+        if reqctx.get_role() == Role::Admin {
+            return None;
+        }
+        Some(match reqctx.get_user_id() {
+            Some(user_id) => Box::new(
+                collection::public
+                    .eq(true)
+                    .or(collection::user_id.eq(user_id))
+                    .or(collection::id.eq_any(
+                        collection_editor::table
+                            .filter(collection_editor::user_id.eq(user_id))
+                            .select(collection_editor::collection_id),
+                    )),
+            ),
+            None => Box::new(collection::public.eq(true)),
+        })
+    }
+
+    /// Expression of the puzzles in the collection, if the collection is visible to the
+    /// request. Only public collections are visible without the request.
+    pub fn puzzles_expression(
+        collection_id: ID,
+        reqctx: Option<&RequestCtx>,
+    ) -> Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send> {
+        let items = collection_puzzle::table
+            .filter(collection_puzzle::collection_id.eq(collection_id))
+            .select(collection_puzzle::puzzle_id);
+
+        // The visibility is spelled out in each arm, as boxed expressions are not
+        // allowed in subqueries
+        match reqctx.map(|reqctx| (reqctx.get_role(), reqctx.get_user_id())) {
+            Some((Role::Admin, _)) => Box::new(puzzle::id.eq_any(items)),
+            Some((_, Some(user_id))) => Box::new(
+                puzzle::id.eq_any(
+                    items.filter(
+                        collection_puzzle::collection_id.eq_any(
+                            collection::table
+                                .filter(
+                                    collection::public
+                                        .eq(true)
+                                        .or(collection::user_id.eq(user_id))
+                                        .or(collection::id.eq_any(
+                                            collection_editor::table
+                                                .filter(collection_editor::user_id.eq(user_id))
+                                                .select(collection_editor::collection_id),
+                                        )),
+                                )
+                                .select(collection::id),
+                        ),
+                    ),
+                ),
+            ),
+            _ => Box::new(
+                puzzle::id.eq_any(
+                    items.filter(
+                        collection_puzzle::collection_id.eq_any(
+                            collection::table
+                                .filter(collection::public.eq(true))
+                                .select(collection::id),
+                        ),
+                    ),
+                ),
+            ),
+        }
+    }
+}
+
+#[Object]
+impl Collection {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn title(&self) -> &str {
+        &self.title
+    }
+    async fn description(&self) -> &str {
+        &self.description
+    }
+    async fn public(&self) -> bool {
+        self.public
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+    async fn modified(&self) -> Timestamptz {
+        self.modified
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user = user::table
+            .filter(user::id.eq(self.user_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(user)
+    }
+
+    async fn editors(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<User>> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let editors = collection_editor::table
+            .inner_join(user::table)
+            .filter(collection_editor::collection_id.eq(self.id))
+            .order(collection_editor::id.asc())
+            .select(user::all_columns)
+            .load(&conn)?;
+
+        Ok(editors)
+    }
+
+    /// Puzzles of the collection in order, leaving out the ones not visible, e.g. drafts
+    async fn puzzles(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Puzzle>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let ids: Vec<ID> = collection_puzzle::table
+            .filter(collection_puzzle::collection_id.eq(self.id))
+            .order((
+                collection_puzzle::position.asc(),
+                collection_puzzle::id.asc(),
+            ))
+            .select(collection_puzzle::puzzle_id)
+            .load(&conn)?;
+
+        let mut query = puzzle::table.filter(puzzle::id.eq_any(&ids)).into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let mut puzzles: Vec<Puzzle> = query.load(&conn)?;
+        puzzles.sort_by_key(|puzzle| ids.iter().position(|id| *id == puzzle.id));
+
+        Ok(puzzles)
+    }
+}
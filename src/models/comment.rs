@@ -1,12 +1,13 @@
-use async_graphql::{self, Context, InputObject, Object};
-use diesel::sql_types::Bool;
+use async_graphql::{self, Context, Enum, InputObject, Object};
+use diesel::sql_types::{Bool, Int4, Nullable};
 use diesel::{prelude::*, query_dsl::QueryDsl};
 
-use crate::context::GlobalCtx;
+use crate::context::{GlobalCtx, RequestCtx};
 use crate::schema::comment;
 
+use super::aggregate::*;
 use super::generics::*;
-use super::{Puzzle, User};
+use super::{Attachment, AttachmentTarget, Puzzle, User};
 
 /// Available orders for comment query
 #[derive(InputObject, Clone)]
@@ -140,15 +141,28 @@ impl Comment {
         use crate::schema::puzzle;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let puzzle = puzzle::table
+        let puzzle: Puzzle = puzzle::table
             .filter(puzzle::id.eq(self.puzzle_id))
             .limit(1)
             .first(&conn)?;
+        if !puzzle.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
 
         Ok(puzzle)
     }
 
+    /// Images attached to the comment in order
+    async fn attachments(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Attachment>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let attachments = Attachment::load_of(&conn, AttachmentTarget::Comment, self.id)?;
+
+        Ok(attachments)
+    }
+
     async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
         use crate::schema::user;
 
@@ -162,3 +176,83 @@ impl Comment {
         Ok(user)
     }
 }
+
+/// Available group-by columns for comment aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum CommentAggregateGroupBy {
+    UserId,
+    PuzzleId,
+    Spoiler,
+}
+
+impl AggregateGroupBy for CommentAggregateGroupBy {
+    fn column(self) -> usize {
+        match self {
+            CommentAggregateGroupBy::UserId => 0,
+            CommentAggregateGroupBy::PuzzleId => 1,
+            CommentAggregateGroupBy::Spoiler => 2,
+        }
+    }
+
+    fn expression(self) -> &'static str {
+        match self {
+            CommentAggregateGroupBy::UserId => "t.user_id",
+            CommentAggregateGroupBy::PuzzleId => "t.puzzle_id",
+            CommentAggregateGroupBy::Spoiler => "t.spoiler",
+        }
+    }
+}
+
+/// Available fields to apply sum/avg/min/max in comment aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum CommentAggregateField {
+    Spoiler,
+}
+
+impl AggregateField for CommentAggregateField {
+    fn expression(self) -> &'static str {
+        match self {
+            CommentAggregateField::Spoiler => "t.spoiler::int",
+        }
+    }
+}
+
+/// A row of comment aggregate query.
+///
+/// Group columns not grouped by are always `null`.
+#[derive(Queryable, Clone, Debug)]
+pub struct CommentAggregate {
+    pub user_id: Option<ID>,
+    pub puzzle_id: Option<ID>,
+    pub spoiler: Option<bool>,
+    pub values: AggregateValues,
+}
+
+impl Aggregate for CommentAggregate {
+    type SqlType = (
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Bool>,
+        AggregateValuesSqlType,
+    );
+    type GroupBy = CommentAggregateGroupBy;
+    type Field = CommentAggregateField;
+
+    const GROUP_COLUMNS: &'static [&'static str] = &["NULL::int4", "NULL::int4", "NULL::bool"];
+}
+
+#[Object]
+impl CommentAggregate {
+    async fn user_id(&self) -> Option<ID> {
+        self.user_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        self.puzzle_id
+    }
+    async fn spoiler(&self) -> Option<bool> {
+        self.spoiler
+    }
+    async fn values(&self) -> &AggregateValues {
+        &self.values
+    }
+}
@@ -1,14 +1,16 @@
-use async_graphql::{self, Context, InputObject, Object};
+use async_graphql::{self, Context, Enum, InputObject, Object};
 use diesel::sql_types::Bool;
 use diesel::{
     prelude::*,
     query_dsl::QueryDsl,
-    sql_types::{BigInt, Int4},
+    sql_types::{self, BigInt, Int4, Nullable},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::context::GlobalCtx;
 use crate::schema::dialogue;
 
+use super::aggregate::*;
 use super::*;
 
 /// Available orders for dialogue query
@@ -143,7 +145,7 @@ impl UserMaxYamiDialogueCountResult {
 }
 
 /// Object for dialogue table
-#[derive(Queryable, Identifiable, Clone, Debug)]
+#[derive(Queryable, Identifiable, Clone, Debug, Serialize, Deserialize)]
 #[table_name = "dialogue"]
 pub struct Dialogue {
     pub id: ID,
@@ -210,6 +212,18 @@ impl Dialogue {
         self.modified
     }
 
+    /// Images attached to the answer in order
+    async fn answer_attachments(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<Attachment>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let attachments = Attachment::load_of(&conn, AttachmentTarget::DialogueAnswer, self.id)?;
+
+        Ok(attachments)
+    }
+
     async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
         use crate::schema::user;
 
@@ -236,3 +250,130 @@ impl Dialogue {
         Ok(puzzle_inst)
     }
 }
+
+/// Available group-by columns for dialogue aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum DialogueAggregateGroupBy {
+    UserId,
+    PuzzleId,
+    Good,
+    True,
+    Answered,
+    CreatedYear,
+    CreatedMonth,
+    CreatedDay,
+}
+
+impl AggregateGroupBy for DialogueAggregateGroupBy {
+    fn column(self) -> usize {
+        match self {
+            DialogueAggregateGroupBy::UserId => 0,
+            DialogueAggregateGroupBy::PuzzleId => 1,
+            DialogueAggregateGroupBy::Good => 2,
+            DialogueAggregateGroupBy::True => 3,
+            DialogueAggregateGroupBy::Answered => 4,
+            DialogueAggregateGroupBy::CreatedYear
+            | DialogueAggregateGroupBy::CreatedMonth
+            | DialogueAggregateGroupBy::CreatedDay => 5,
+        }
+    }
+
+    fn expression(self) -> &'static str {
+        match self {
+            DialogueAggregateGroupBy::UserId => "t.user_id",
+            DialogueAggregateGroupBy::PuzzleId => "t.puzzle_id",
+            DialogueAggregateGroupBy::Good => "t.good",
+            DialogueAggregateGroupBy::True => "t.\"true\"",
+            DialogueAggregateGroupBy::Answered => "(t.answeredtime IS NOT NULL)",
+            DialogueAggregateGroupBy::CreatedYear => "date_trunc('year', t.created)",
+            DialogueAggregateGroupBy::CreatedMonth => "date_trunc('month', t.created)",
+            DialogueAggregateGroupBy::CreatedDay => "date_trunc('day', t.created)",
+        }
+    }
+}
+
+/// Available fields to apply sum/avg/min/max in dialogue aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum DialogueAggregateField {
+    Good,
+    True,
+    Qno,
+    /// Seconds between the question and the answer
+    AnswerDelay,
+}
+
+impl AggregateField for DialogueAggregateField {
+    fn expression(self) -> &'static str {
+        match self {
+            DialogueAggregateField::Good => "t.good::int",
+            DialogueAggregateField::True => "t.\"true\"::int",
+            DialogueAggregateField::Qno => "t.qno",
+            DialogueAggregateField::AnswerDelay => "extract(epoch from t.answeredtime - t.created)",
+        }
+    }
+}
+
+/// A row of dialogue aggregate query.
+///
+/// Group columns not grouped by are always `null`.
+#[derive(Queryable, Clone, Debug)]
+pub struct DialogueAggregate {
+    pub user_id: Option<ID>,
+    pub puzzle_id: Option<ID>,
+    pub is_good: Option<bool>,
+    pub is_true: Option<bool>,
+    pub answered: Option<bool>,
+    pub created: Option<Timestamptz>,
+    pub values: AggregateValues,
+}
+
+impl Aggregate for DialogueAggregate {
+    type SqlType = (
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Bool>,
+        Nullable<Bool>,
+        Nullable<Bool>,
+        Nullable<sql_types::Timestamptz>,
+        AggregateValuesSqlType,
+    );
+    type GroupBy = DialogueAggregateGroupBy;
+    type Field = DialogueAggregateField;
+
+    const GROUP_COLUMNS: &'static [&'static str] = &[
+        "NULL::int4",
+        "NULL::int4",
+        "NULL::bool",
+        "NULL::bool",
+        "NULL::bool",
+        "NULL::timestamptz",
+    ];
+}
+
+#[Object]
+impl DialogueAggregate {
+    async fn user_id(&self) -> Option<ID> {
+        self.user_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        self.puzzle_id
+    }
+    #[graphql(name = "good")]
+    async fn is_good(&self) -> Option<bool> {
+        self.is_good
+    }
+    #[graphql(name = "true")]
+    async fn is_true(&self) -> Option<bool> {
+        self.is_true
+    }
+    async fn answered(&self) -> Option<bool> {
+        self.answered
+    }
+    /// Start of the time unit in `created_*` group
+    async fn created(&self) -> Option<Timestamptz> {
+        self.created
+    }
+    async fn values(&self) -> &AggregateValues {
+        &self.values
+    }
+}
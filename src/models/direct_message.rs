@@ -1,6 +1,8 @@
 use async_graphql::{self, Context, InputObject, Object};
 use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
+use serde::{Deserialize, Serialize};
 
+use crate::broker::{BrokerMessage, Event, EventId};
 use crate::context::GlobalCtx;
 use crate::schema::direct_message;
 
@@ -84,31 +86,50 @@ impl CindyFilter<direct_message::table, DB> for DirectMessageFilter {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum DirectMessageSub {
     Created(DirectMessage),
     Updated(DirectMessage, DirectMessage),
+    Deleted(DirectMessage),
+    Resync,
 }
 
-#[Object]
-impl DirectMessageSub {
+impl BrokerMessage for DirectMessageSub {
+    const TOPIC: &'static str = "directMessage";
+    const DURABLE: bool = true;
+
+    fn resync() -> Option<Self> {
+        Some(DirectMessageSub::Resync)
+    }
+}
+
+#[Object(name = "DirectMessageSub")]
+impl Event<DirectMessageSub> {
+    async fn event_id(&self) -> Option<EventId> {
+        self.id
+    }
+
     async fn op(&self) -> DbOp {
-        match &self {
+        match &self.msg {
             DirectMessageSub::Created(_) => DbOp::Created,
             DirectMessageSub::Updated(_, _) => DbOp::Updated,
+            DirectMessageSub::Deleted(_) => DbOp::Deleted,
+            DirectMessageSub::Resync => DbOp::Resync,
         }
     }
 
-    async fn data(&self) -> DirectMessage {
-        match &self {
-            DirectMessageSub::Created(dm) => dm.clone(),
-            DirectMessageSub::Updated(_, dm) => dm.clone(),
+    async fn data(&self) -> Option<DirectMessage> {
+        match &self.msg {
+            DirectMessageSub::Created(dm) => Some(dm.clone()),
+            DirectMessageSub::Updated(_, dm) => Some(dm.clone()),
+            DirectMessageSub::Deleted(dm) => Some(dm.clone()),
+            DirectMessageSub::Resync => None,
         }
     }
 }
 
 /// Object for direct_message table
-#[derive(Queryable, Identifiable, Clone, Debug)]
+#[derive(Queryable, Identifiable, Clone, Debug, Serialize, Deserialize)]
 #[table_name = "direct_message"]
 pub struct DirectMessage {
     pub id: ID,
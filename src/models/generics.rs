@@ -22,6 +22,8 @@ pub enum DbOp {
     Created,
     Updated,
     Deleted,
+    /// Some events are missed and the data should be refetched
+    Resync,
 }
 
 #[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
@@ -156,6 +158,36 @@ impl I32Filtering {
     }
 }
 
+#[derive(InputObject, Clone, Debug, Eq, PartialEq, Default)]
+pub struct I64Filtering {
+    pub eq: Option<i64>,
+    pub gt: Option<i64>,
+    pub lt: Option<i64>,
+    pub ge: Option<i64>,
+    pub le: Option<i64>,
+    pub eq_any: Option<Vec<i64>>,
+}
+
+impl RawFilter<i64> for I64Filtering {
+    fn check(&self, item: &i64) -> bool {
+        if let Some(eq) = self.eq.as_ref() {
+            item == eq
+        } else if let Some(gt) = self.gt.as_ref() {
+            item > gt
+        } else if let Some(lt) = self.lt.as_ref() {
+            item < lt
+        } else if let Some(ge) = self.ge.as_ref() {
+            item >= ge
+        } else if let Some(le) = self.le.as_ref() {
+            item <= le
+        } else if let Some(eq_any) = self.eq_any.as_ref() {
+            eq_any.iter().any(|el| el == item)
+        } else {
+            true
+        }
+    }
+}
+
 #[derive(InputObject, Clone, Debug)]
 pub struct NullableI32Filtering {
     pub is_null: Option<bool>,
@@ -264,7 +296,7 @@ impl RawFilter<Date> for DateFiltering {
     }
 }
 
-#[derive(InputObject, Clone, Debug)]
+#[derive(InputObject, Eq, PartialEq, Clone, Debug)]
 pub struct NullableTimestamptzFiltering {
     pub is_null: Option<bool>,
     pub eq: Option<Timestamptz>,
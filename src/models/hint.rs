@@ -1,5 +1,6 @@
 use async_graphql::{self, Context, InputObject, Object};
 use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
+use serde::{Deserialize, Serialize};
 
 use crate::context::GlobalCtx;
 use crate::schema::hint;
@@ -83,7 +84,7 @@ impl CindyFilter<hint::table, DB> for HintFilter {
 }
 
 /// Object for hint table
-#[derive(Queryable, Identifiable, Clone, Debug)]
+#[derive(Queryable, Identifiable, Clone, Debug, Serialize, Deserialize)]
 #[table_name = "hint"]
 pub struct Hint {
     pub id: ID,
@@ -120,6 +121,15 @@ impl Hint {
         self.modified
     }
 
+    /// Images attached to the hint in order
+    async fn attachments(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Attachment>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let attachments = Attachment::load_of(&conn, AttachmentTarget::Hint, self.id)?;
+
+        Ok(attachments)
+    }
+
     async fn receiver(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
         if let Some(receiver_id) = self.receiver_id {
             use crate::schema::user;
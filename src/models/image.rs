@@ -0,0 +1,292 @@
+use async_graphql::{self, Context, Enum, Object};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    expression::{helper_types::AsExprOf, AsExpression},
+    prelude::*,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io;
+
+use crate::auth::Role;
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::schema::{image, image_upload, image_variant};
+
+use super::*;
+
+/// Encoding of an image variant
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow)]
+pub enum VariantFormat {
+    Webp = 0,
+    Jpeg = 1,
+}
+
+impl VariantFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            VariantFormat::Webp => "webp",
+            VariantFormat::Jpeg => "jpg",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "webp" => Some(VariantFormat::Webp),
+            "jpg" => Some(VariantFormat::Jpeg),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            VariantFormat::Webp => "image/webp",
+            VariantFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    /// Key of the variant of the image in the image store
+    pub fn key(self, hash: &str, size: i32) -> String {
+        format!("{}_{}.{}", hash, size, self.extension())
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for VariantFormat
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for VariantFormat {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for VariantFormat
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(VariantFormat::Webp),
+            1 => Ok(VariantFormat::Jpeg),
+            v => Err(format!("Invalid value `{}` for variant format", &v).into()),
+        }
+    }
+}
+
+/// Object for image table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "image"]
+pub struct Image {
+    pub id: ID,
+    /// Hex-encoded SHA-256 of the content, which is the key in the image store
+    pub hash: String,
+    pub content_type: String,
+    pub size: i32,
+    /// The uploader, only exposed to themselves and admins as images are shared by puzzles,
+    /// attachments and comments of possibly anonymous authors
+    pub user_id: Option<ID>,
+    pub created: Timestamptz,
+}
+
+impl Image {
+    /// Whether the uploader of the image is visible to the request.
+    pub fn uploader_visible_to(&self, reqctx: &RequestCtx) -> bool {
+        reqctx.get_role() == Role::Admin
+            || (self.user_id.is_some() && reqctx.get_user_id() == self.user_id)
+    }
+}
+
+/// Asserts the user is allowed to use the image, i.e. they have uploaded it, so that the
+/// images of the others (e.g. in hidden solutions) cannot be referenced by their ids.
+pub fn image_guard(ctx: &Context<'_>, conn: &PgConnection, id: ID) -> async_graphql::Result<()> {
+    let reqctx = ctx.data::<RequestCtx>()?;
+
+    // Assert that the image exists
+    image::table
+        .filter(image::id.eq(id))
+        .select(image::id)
+        .first::<ID>(conn)?;
+
+    match (reqctx.get_role(), reqctx.get_user_id()) {
+        (Role::Admin, _) => Ok(()),
+        (Role::User, Some(user_id)) => {
+            let count: i64 = image_upload::table
+                .filter(image_upload::image_id.eq(id))
+                .filter(image_upload::user_id.eq(user_id))
+                .count()
+                .get_result(conn)?;
+            if count == 0 {
+                return Err(async_graphql::Error::new(
+                    "The image is not uploaded by the user",
+                ));
+            }
+            Ok(())
+        }
+        _ => Err(async_graphql::Error::new("Not logged in")),
+    }
+}
+
+#[Object]
+impl Image {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn hash(&self) -> &str {
+        &self.hash
+    }
+    async fn content_type(&self) -> &str {
+        &self.content_type
+    }
+    async fn size(&self) -> i32 {
+        self.size
+    }
+    /// The uploader, or null unless the request is from them or an admin
+    async fn user_id(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ID>> {
+        let reqctx = ctx.data::<RequestCtx>()?;
+        if !self.uploader_visible_to(reqctx) {
+            return Ok(None);
+        }
+
+        Ok(self.user_id)
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+    async fn url(&self) -> String {
+        crate::image_store::url(&self.hash)
+    }
+
+    /// Resized variants of the image, from the smallest one
+    async fn variants(
+        &self,
+        ctx: &Context<'_>,
+        format: Option<VariantFormat>,
+    ) -> async_graphql::Result<Vec<ImageVariant>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let mut query = image_variant::table
+            .filter(image_variant::image_id.eq(self.id))
+            .into_boxed();
+        if let Some(format) = format {
+            query = query.filter(image_variant::format.eq(format));
+        }
+        let variants: Vec<ImageVariant> = query.order(image_variant::size.asc()).load(&conn)?;
+
+        Ok(variants)
+    }
+
+    /// The smallest variant fitting `size`, or the largest one if none of them does
+    async fn variant(
+        &self,
+        ctx: &Context<'_>,
+        size: i32,
+        #[graphql(default_with = "VariantFormat::Webp")] format: VariantFormat,
+    ) -> async_graphql::Result<Option<ImageVariant>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let query = image_variant::table
+            .filter(image_variant::image_id.eq(self.id))
+            .filter(image_variant::format.eq(format));
+        let variant: Option<ImageVariant> = query
+            .clone()
+            .filter(image_variant::size.ge(size))
+            .order(image_variant::size.asc())
+            .first(&conn)
+            .optional()?;
+        let variant = match variant {
+            Some(variant) => Some(variant),
+            None => query
+                .order(image_variant::size.desc())
+                .first(&conn)
+                .optional()?,
+        };
+
+        Ok(variant)
+    }
+
+    /// The uploader, or null unless the request is from them or an admin
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        use crate::schema::user;
+
+        let reqctx = ctx.data::<RequestCtx>()?;
+        if !self.uploader_visible_to(reqctx) {
+            return Ok(None);
+        }
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user = match self.user_id {
+            Some(user_id) => user::table
+                .filter(user::id.eq(user_id))
+                .limit(1)
+                .first(&conn)
+                .optional()?,
+            None => None,
+        };
+
+        Ok(user)
+    }
+}
+
+/// Object for image_variant table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "image_variant"]
+pub struct ImageVariant {
+    pub id: ID,
+    pub image_id: ID,
+    /// Maximum width and height the variant is rendered within
+    pub size: i32,
+    pub format: VariantFormat,
+    pub width: i32,
+    pub height: i32,
+    pub byte_size: i32,
+}
+
+#[Object]
+impl ImageVariant {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn image_id(&self) -> ID {
+        self.image_id
+    }
+    async fn size(&self) -> i32 {
+        self.size
+    }
+    async fn format(&self) -> VariantFormat {
+        self.format
+    }
+    async fn width(&self) -> i32 {
+        self.width
+    }
+    async fn height(&self) -> i32 {
+        self.height
+    }
+    async fn byte_size(&self) -> i32 {
+        self.byte_size
+    }
+    async fn content_type(&self) -> &str {
+        self.format.content_type()
+    }
+
+    async fn url(&self, ctx: &Context<'_>) -> async_graphql::Result<String> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let hash: String = image::table
+            .filter(image::id.eq(self.image_id))
+            .select(image::hash)
+            .first(&conn)?;
+
+        Ok(crate::image_store::url(&self.format.key(&hash, self.size)))
+    }
+}
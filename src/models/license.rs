@@ -83,6 +83,10 @@ pub struct License {
     pub description: String,
     pub url: Option<String>,
     pub contract: Option<String>,
+    /// Puzzles under the license cannot be remixed
+    pub no_derivatives: bool,
+    /// Remixes of puzzles under the license must be under the same license
+    pub share_alike: bool,
 }
 
 #[Object]
@@ -105,6 +109,12 @@ impl License {
     async fn contract(&self) -> Option<&String> {
         self.contract.as_ref()
     }
+    async fn no_derivatives(&self) -> bool {
+        self.no_derivatives
+    }
+    async fn share_alike(&self) -> bool {
+        self.share_alike
+    }
 
     async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
         use crate::schema::user;
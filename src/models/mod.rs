@@ -1,43 +1,64 @@
 #[macro_use]
 mod generics;
 
+pub mod aggregate;
+pub mod attachment;
 pub mod award;
 pub mod bookmark;
 pub mod chatmessage;
 pub mod chatroom;
+pub mod collection;
 pub mod comment;
 pub mod dialogue;
 pub mod direct_message;
 pub mod dm_read;
 pub mod favchat;
 pub mod hint;
+pub mod image;
 pub mod license;
+pub mod notification;
+pub mod presence;
 pub mod puzzle;
+pub mod puzzle_coauthor;
+pub mod puzzle_derivation;
 pub mod puzzle_log;
+pub mod puzzle_revision;
 pub mod puzzle_tag;
+pub mod schedule;
 pub mod star;
 pub mod tag;
 pub mod user;
 pub mod user_award;
+pub mod webhook;
 
+pub use aggregate::{AggregateFunction, AggregateQuery};
 pub use generics::*;
 
+pub use attachment::{Attachment, AttachmentTarget};
 pub use award::Award;
 pub use bookmark::Bookmark;
 pub use chatmessage::Chatmessage;
 pub use chatroom::Chatroom;
+pub use collection::Collection;
 pub use comment::Comment;
 pub use dialogue::Dialogue;
 pub use direct_message::DirectMessage;
 pub use dm_read::DmRead;
 pub use favchat::Favchat;
 pub use hint::Hint;
+pub use image::{image_guard, Image};
 pub use license::License;
-pub use puzzle::{Genre, Puzzle, Status, Yami};
+pub use puzzle::{published_guard, published_puzzles, Genre, Puzzle, Status, Yami};
+pub use puzzle_coauthor::{
+    puzzle_answerer_guard, puzzle_author_guard, CoauthorRole, PuzzleCoauthor,
+};
+pub use puzzle_derivation::PuzzleDerivation;
 pub use puzzle_tag::PuzzleTag;
+pub use schedule::Schedule;
 pub use star::Star;
 pub use tag::Tag;
 pub use user::User;
 pub use user_award::UserAward;
+pub use webhook::{Webhook, WebhookDelivery};
 
 pub use puzzle_log::PuzzleLog;
@@ -0,0 +1,439 @@
+use async_graphql::{self, Context, Enum, InputObject, Object};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    expression::{helper_types::AsExprOf, AsExpression},
+    prelude::*,
+    query_dsl::QueryDsl,
+    serialize::{self, Output, ToSql},
+    sql_types::{Bool, Integer},
+};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::broker::{BrokerMessage, Event, EventId};
+use crate::context::GlobalCtx;
+use crate::schema::{notification, notification_profile, notification_setting};
+
+use super::*;
+
+/// Available orders for notification query
+#[derive(InputObject, Clone)]
+pub struct NotificationOrder {
+    id: Option<Ordering>,
+    created: Option<Ordering>,
+}
+
+/// Helper object to apply the order to the query
+pub struct NotificationOrders(Vec<NotificationOrder>);
+
+impl Default for NotificationOrders {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl NotificationOrders {
+    pub fn new(orders: Vec<NotificationOrder>) -> Self {
+        Self(orders)
+    }
+
+    pub fn apply_order<'a>(
+        self,
+        query_dsl: crate::schema::notification::BoxedQuery<'a, DB>,
+    ) -> crate::schema::notification::BoxedQuery<'a, DB> {
+        use crate::schema::notification::dsl::*;
+
+        let mut query = query_dsl;
+
+        for obj in self.0 {
+            gen_order!(obj, id, query);
+            gen_order!(obj, created, query);
+        }
+
+        query
+    }
+}
+
+/// Available filters for notification query
+#[derive(InputObject, Clone, Default)]
+pub struct NotificationFilter {
+    pub id: Option<I32Filtering>,
+    pub user_id: Option<I32Filtering>,
+    pub kind: Option<NotificationKindFiltering>,
+    pub puzzle_id: Option<NullableI32Filtering>,
+    pub read: Option<bool>,
+    pub created: Option<TimestamptzFiltering>,
+}
+
+impl CindyFilter<notification::table, DB> for NotificationFilter {
+    fn as_expression(
+        self,
+    ) -> Option<Box<dyn BoxableExpression<notification::table, DB, SqlType = Bool> + Send>> {
+        use crate::schema::notification::dsl::*;
+
+        let mut filter: Option<
+            Box<dyn BoxableExpression<notification, DB, SqlType = Bool> + Send>,
+        > = None;
+        let NotificationFilter {
+            id: obj_id,
+            user_id: obj_user_id,
+            kind: obj_kind,
+            puzzle_id: obj_puzzle_id,
+            read: obj_read,
+            created: obj_created,
+        } = self;
+        gen_number_filter!(obj_id: I32Filtering, id, filter);
+        gen_number_filter!(obj_user_id: I32Filtering, user_id, filter);
+        gen_enum_filter!(obj_kind: NotificationKindFiltering, kind, filter);
+        gen_nullable_number_filter!(obj_puzzle_id: NullableI32Filtering, puzzle_id, filter);
+        gen_bool_filter!(obj_read, read, filter);
+        gen_number_filter!(obj_created: TimestamptzFiltering, created, filter);
+
+        filter
+    }
+}
+
+/// Event a notification is generated from
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow, Serialize, Deserialize)]
+pub enum NotificationKind {
+    /// A question of the user is answered. `target_id` refers to the dialogue.
+    Answered = 0,
+    /// A hint is sent to the user. `target_id` refers to the hint.
+    Hint = 1,
+    /// A puzzle of the user is commented. `target_id` refers to the comment.
+    Comment = 2,
+    /// A puzzle of the user is starred. `target_id` refers to the star.
+    Star = 3,
+    /// An award is granted to the user. `target_id` refers to the user_award.
+    Award = 4,
+    /// A puzzle of the user is going to be dazed soon.
+    DazeWarning = 5,
+}
+
+#[derive(InputObject, Eq, PartialEq, Clone)]
+pub struct NotificationKindFiltering {
+    pub eq: Option<NotificationKind>,
+    pub ne: Option<NotificationKind>,
+    pub eq_any: Option<Vec<NotificationKind>>,
+    pub ne_all: Option<Vec<NotificationKind>>,
+}
+
+impl<DB> ToSql<Integer, DB> for NotificationKind
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for NotificationKind {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for NotificationKind
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(NotificationKind::Answered),
+            1 => Ok(NotificationKind::Hint),
+            2 => Ok(NotificationKind::Comment),
+            3 => Ok(NotificationKind::Star),
+            4 => Ok(NotificationKind::Award),
+            5 => Ok(NotificationKind::DazeWarning),
+            v => Err(format!("Invalid value `{}` for notification kind", &v).into()),
+        }
+    }
+}
+
+/// Object for notification table
+#[derive(Queryable, Identifiable, Clone, Debug, Serialize, Deserialize)]
+#[table_name = "notification"]
+pub struct Notification {
+    pub id: ID,
+    pub user_id: ID,
+    pub kind: NotificationKind,
+    pub actor_id: Option<ID>,
+    pub puzzle_id: Option<ID>,
+    pub target_id: Option<ID>,
+    pub read: bool,
+    pub created: Timestamptz,
+    /// Whether the in-app channel is disabled for the notification, which is only kept
+    /// for the other channels
+    pub hidden: bool,
+}
+
+#[Object]
+impl Notification {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn kind(&self) -> NotificationKind {
+        self.kind
+    }
+    async fn actor_id(&self) -> Option<ID> {
+        self.actor_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        self.puzzle_id
+    }
+    async fn target_id(&self) -> Option<ID> {
+        self.target_id
+    }
+    async fn read(&self) -> bool {
+        self.read
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+
+    async fn actor(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        if let Some(actor_id) = self.actor_id {
+            let user = user::table
+                .filter(user::id.eq(actor_id))
+                .limit(1)
+                .first(&conn)?;
+            Ok(Some(user))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn puzzle(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Puzzle>> {
+        use crate::schema::puzzle;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        if let Some(puzzle_id) = self.puzzle_id {
+            let puzzle = puzzle::table
+                .filter(puzzle::id.eq(puzzle_id))
+                .limit(1)
+                .first(&conn)?;
+            Ok(Some(puzzle))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Channel notifications are delivered through
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow, Serialize, Deserialize)]
+pub enum NotificationChannel {
+    InApp = 0,
+    /// Batched into digest emails
+    Email = 1,
+    /// Posted to the webhook url in the notification profile
+    Webhook = 2,
+}
+
+impl NotificationChannel {
+    pub const ALL: [NotificationChannel; 3] = [
+        NotificationChannel::InApp,
+        NotificationChannel::Email,
+        NotificationChannel::Webhook,
+    ];
+
+    /// Whether the channel is enabled without explicit settings
+    pub fn enabled_by_default(self) -> bool {
+        match self {
+            NotificationChannel::InApp => true,
+            NotificationChannel::Email | NotificationChannel::Webhook => false,
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for NotificationChannel
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for NotificationChannel {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for NotificationChannel
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(NotificationChannel::InApp),
+            1 => Ok(NotificationChannel::Email),
+            2 => Ok(NotificationChannel::Webhook),
+            v => Err(format!("Invalid value `{}` for notification channel", &v).into()),
+        }
+    }
+}
+
+/// Object for notification_setting table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "notification_setting"]
+pub struct NotificationSetting {
+    pub id: ID,
+    pub user_id: ID,
+    pub kind: NotificationKind,
+    pub channel: NotificationChannel,
+    pub enabled: bool,
+}
+
+#[Object]
+impl NotificationSetting {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn kind(&self) -> NotificationKind {
+        self.kind
+    }
+    async fn channel(&self) -> NotificationChannel {
+        self.channel
+    }
+    async fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Interval of the digest emails
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow)]
+pub enum DigestFrequency {
+    Never = 0,
+    Daily = 1,
+    Weekly = 2,
+}
+
+impl DigestFrequency {
+    pub fn period(self) -> Option<chrono::Duration> {
+        match self {
+            DigestFrequency::Never => None,
+            DigestFrequency::Daily => Some(chrono::Duration::days(1)),
+            DigestFrequency::Weekly => Some(chrono::Duration::weeks(1)),
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for DigestFrequency
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for DigestFrequency {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for DigestFrequency
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(DigestFrequency::Never),
+            1 => Ok(DigestFrequency::Daily),
+            2 => Ok(DigestFrequency::Weekly),
+            v => Err(format!("Invalid value `{}` for digest frequency", &v).into()),
+        }
+    }
+}
+
+/// Object for notification_profile table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "notification_profile"]
+#[primary_key(user_id)]
+pub struct NotificationProfile {
+    pub user_id: ID,
+    pub digest_frequency: DigestFrequency,
+    pub digest_sent: Option<Timestamptz>,
+    pub webhook_url: Option<String>,
+}
+
+#[Object]
+impl NotificationProfile {
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn digest_frequency(&self) -> DigestFrequency {
+        self.digest_frequency
+    }
+    async fn digest_sent(&self) -> Option<Timestamptz> {
+        self.digest_sent
+    }
+    async fn webhook_url(&self) -> Option<&str> {
+        self.webhook_url.as_deref()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum NotificationSub {
+    Created(Notification),
+    Updated(Notification),
+    Resync,
+}
+
+impl BrokerMessage for NotificationSub {
+    const TOPIC: &'static str = "notification";
+    const DURABLE: bool = true;
+
+    fn resync() -> Option<Self> {
+        Some(NotificationSub::Resync)
+    }
+}
+
+#[Object(name = "NotificationSub")]
+impl Event<NotificationSub> {
+    async fn event_id(&self) -> Option<EventId> {
+        self.id
+    }
+
+    async fn op(&self) -> DbOp {
+        match &self.msg {
+            NotificationSub::Created(_) => DbOp::Created,
+            NotificationSub::Updated(_) => DbOp::Updated,
+            NotificationSub::Resync => DbOp::Resync,
+        }
+    }
+
+    async fn data(&self) -> Option<Notification> {
+        match &self.msg {
+            NotificationSub::Created(notification) => Some(notification.clone()),
+            NotificationSub::Updated(notification) => Some(notification.clone()),
+            NotificationSub::Resync => None,
+        }
+    }
+}
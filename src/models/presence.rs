@@ -0,0 +1,212 @@
+use async_graphql::{self, Context, Enum, InputObject, Object};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::broker::BrokerMessage;
+use crate::context::{GlobalCtx, RequestCtx};
+
+use super::*;
+
+/// Where a user is present
+#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PresenceLocation {
+    /// Anywhere on the site
+    Site,
+    Puzzle(ID),
+    Chatroom(ID),
+}
+
+impl PresenceLocation {
+    /// Broker key of the presence events at the location
+    pub fn key(&self) -> String {
+        match self {
+            PresenceLocation::Site => "presence".to_string(),
+            PresenceLocation::Puzzle(id) => format!("presence<puzzle:{}>", id),
+            PresenceLocation::Chatroom(id) => format!("presence<chatroom:{}>", id),
+        }
+    }
+}
+
+/// Location of the presence, which is the whole site if neither field is set
+#[derive(InputObject, Clone, Default)]
+pub struct PresenceLocationInput {
+    pub puzzle_id: Option<ID>,
+    pub chatroom_id: Option<ID>,
+}
+
+impl PresenceLocationInput {
+    pub fn into_location(self) -> async_graphql::Result<PresenceLocation> {
+        match (self.puzzle_id, self.chatroom_id) {
+            (None, None) => Ok(PresenceLocation::Site),
+            (Some(puzzle_id), None) => Ok(PresenceLocation::Puzzle(puzzle_id)),
+            (None, Some(chatroom_id)) => Ok(PresenceLocation::Chatroom(chatroom_id)),
+            (Some(_), Some(_)) => Err(async_graphql::Error::new(
+                "Only one of puzzle_id and chatroom_id can be set",
+            )),
+        }
+    }
+}
+
+/// A heartbeat of a user at a location, shared by all server instances
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PresenceHeartbeat {
+    pub user_id: ID,
+    pub location: PresenceLocation,
+    /// Whether the user is leaving the location
+    pub leave: bool,
+}
+
+impl BrokerMessage for PresenceHeartbeat {
+    const TOPIC: &'static str = "presenceHeartbeat";
+}
+
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum PresenceOp {
+    Joined,
+    Left,
+}
+
+/// A user joining or leaving a location
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PresenceSub {
+    pub op: PresenceOp,
+    pub user_id: ID,
+    pub location: PresenceLocation,
+}
+
+impl BrokerMessage for PresenceSub {
+    const TOPIC: &'static str = "presence";
+    // Derived from the heartbeats by every instance on its own
+    const RELAY: bool = false;
+}
+
+#[Object]
+impl PresenceSub {
+    async fn op(&self) -> PresenceOp {
+        self.op
+    }
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        match self.location {
+            PresenceLocation::Puzzle(id) => Some(id),
+            _ => None,
+        }
+    }
+    async fn chatroom_id(&self) -> Option<ID> {
+        match self.location {
+            PresenceLocation::Chatroom(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user = user::table
+            .filter(user::id.eq(self.user_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(user)
+    }
+}
+
+/// A user typing in a chatroom or in the questions of a puzzle
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TypingSub {
+    pub user_id: ID,
+    pub location: PresenceLocation,
+    /// Whether the user stopped typing
+    pub stopped: bool,
+    /// Time after which the indicator should be hidden without further events
+    pub expires: Timestamptz,
+}
+
+impl BrokerMessage for TypingSub {
+    const TOPIC: &'static str = "typing";
+}
+
+impl TypingSub {
+    /// Broker key of the typing events at the location
+    pub fn key(location: PresenceLocation) -> String {
+        match location {
+            PresenceLocation::Site => "typing".to_string(),
+            PresenceLocation::Puzzle(id) => format!("typing<puzzle:{}>", id),
+            PresenceLocation::Chatroom(id) => format!("typing<chatroom:{}>", id),
+        }
+    }
+
+    /// Whether the typing user is an author of the puzzle masked from the request, as
+    /// authors answer the questions of their anonymous puzzles.
+    fn author_masked(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        use crate::schema::puzzle;
+
+        let puzzle_id = match self.location {
+            PresenceLocation::Puzzle(puzzle_id) => puzzle_id,
+            _ => return Ok(false),
+        };
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let puzzle_inst: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(puzzle_id))
+            .limit(1)
+            .first(&conn)?;
+        if puzzle_inst.author_visible_to(reqctx) {
+            return Ok(false);
+        }
+
+        Ok(self.user_id == puzzle_inst.user_id
+            || puzzle_inst
+                .coauthor_role(&conn, Some(self.user_id))?
+                .is_some())
+    }
+}
+
+#[Object]
+impl TypingSub {
+    async fn user_id(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ID>> {
+        if self.author_masked(ctx)? {
+            return Ok(None);
+        }
+        Ok(Some(self.user_id))
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        match self.location {
+            PresenceLocation::Puzzle(id) => Some(id),
+            _ => None,
+        }
+    }
+    async fn chatroom_id(&self) -> Option<ID> {
+        match self.location {
+            PresenceLocation::Chatroom(id) => Some(id),
+            _ => None,
+        }
+    }
+    async fn stopped(&self) -> bool {
+        self.stopped
+    }
+    async fn expires(&self) -> Timestamptz {
+        self.expires
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        use crate::schema::user;
+
+        if self.author_masked(ctx)? {
+            return Ok(None);
+        }
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user = user::table
+            .filter(user::id.eq(self.user_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(Some(user))
+    }
+}
@@ -3,34 +3,102 @@ use diesel::{
     backend::Backend,
     deserialize::{self, FromSql},
     dsl::{max, sum},
-    expression::{helper_types::AsExprOf, AsExpression},
+    expression::{helper_types::AsExprOf, AppearsOnTable, AsExpression, Expression, NonAggregate},
+    pg::Pg,
     prelude::*,
+    query_builder::{AstPass, QueryFragment},
     query_dsl::QueryDsl,
     serialize::{self, Output, ToSql},
-    sql_types::{BigInt, Bool, Int4, Integer, Text},
+    sql_types::{self, BigInt, Bool, Int4, Integer, Nullable, Text},
 };
+use serde::{Deserialize, Serialize};
 use std::io;
 
-use crate::context::GlobalCtx;
+use crate::auth::Role;
+use crate::broker::{BrokerMessage, Event, EventId};
+use crate::context::{GlobalCtx, RequestCtx};
 use crate::schema::puzzle;
 
+use super::aggregate::*;
 use super::bookmark::{BookmarkFilter, BookmarkOrder};
 use super::comment::{CommentFilter, CommentOrder};
 use super::dialogue::{DialogueFilter, DialogueOrder};
 use super::hint::{HintFilter, HintOrder};
+use super::puzzle_revision::{PuzzleRevision, PuzzleRevisionDiff};
 use super::puzzle_tag::{PuzzleTagFilter, PuzzleTagOrder};
 use super::star::{StarFilter, StarOrder};
 use super::*;
 
+/// Placeholder of the author id of the anonymous puzzles in redacted messages
+pub const ANONYMOUS_USER_ID: ID = 0;
+
+lazy_static! {
+    /// Whether the author of an anonymous puzzle is revealed once it is finished
+    pub static ref ANONYMOUS_REVEAL_ON_SOLVE: bool = dotenv::var("ANONYMOUS_REVEAL_ON_SOLVE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(true);
+}
+
+/// Generate an expression of an aggregate computed from a subquery correlated to the puzzle.
+macro_rules! gen_puzzle_aggregate {
+    ($name:ident, $sql:expr) => {
+        #[derive(Debug, Clone, Copy, QueryId)]
+        pub struct $name;
+
+        impl Expression for $name {
+            type SqlType = BigInt;
+        }
+
+        impl QueryFragment<Pg> for $name {
+            fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+                out.push_sql($sql);
+                Ok(())
+            }
+        }
+
+        impl AppearsOnTable<puzzle::table> for $name {}
+        impl SelectableExpression<puzzle::table> for $name {}
+        impl NonAggregate for $name {}
+    };
+}
+
+gen_puzzle_aggregate!(
+    StarCount,
+    "(SELECT count(*) FROM star s WHERE s.puzzle_id = puzzle.id)"
+);
+gen_puzzle_aggregate!(
+    StarSum,
+    "(SELECT COALESCE(sum(s.value), 0) FROM star s WHERE s.puzzle_id = puzzle.id)"
+);
+gen_puzzle_aggregate!(
+    DialogueCount,
+    "(SELECT count(*) FROM dialogue d WHERE d.puzzle_id = puzzle.id)"
+);
+gen_puzzle_aggregate!(
+    BookmarkCount,
+    "(SELECT count(*) FROM bookmark b WHERE b.puzzle_id = puzzle.id)"
+);
+gen_puzzle_aggregate!(
+    CommentCount,
+    "(SELECT count(*) FROM comment c WHERE c.puzzle_id = puzzle.id)"
+);
+
 /// Available orders for puzzle query
 #[derive(InputObject, Clone)]
 pub struct PuzzleOrder {
     id: Option<Ordering>,
     created: Option<Ordering>,
     modified: Option<Ordering>,
+    solved_at: Option<Ordering>,
     yami: Option<Ordering>,
     genre: Option<Ordering>,
     status: Option<Ordering>,
+    star_count: Option<Ordering>,
+    star_sum: Option<Ordering>,
+    dialogue_count: Option<Ordering>,
+    bookmark_count: Option<Ordering>,
+    comment_count: Option<Ordering>,
 }
 
 /// Helper object to apply the order to the query
@@ -54,6 +122,11 @@ impl PuzzleOrders {
         use crate::schema::puzzle::dsl::*;
 
         let mut query = query_dsl;
+        let star_count = StarCount;
+        let star_sum = StarSum;
+        let dialogue_count = DialogueCount;
+        let bookmark_count = BookmarkCount;
+        let comment_count = CommentCount;
 
         for obj in self.0 {
             gen_order!(obj, id, query);
@@ -61,7 +134,13 @@ impl PuzzleOrders {
             gen_order!(obj, genre, query);
             gen_order!(obj, created, query);
             gen_order!(obj, modified, query);
+            gen_order!(obj, solved_at, query);
             gen_order!(obj, status, query);
+            gen_order!(obj, star_count, query);
+            gen_order!(obj, star_sum, query);
+            gen_order!(obj, dialogue_count, query);
+            gen_order!(obj, bookmark_count, query);
+            gen_order!(obj, comment_count, query);
         }
 
         query
@@ -82,8 +161,16 @@ pub struct PuzzleFilter {
     pub user_id: Option<I32Filtering>,
     pub created: Option<TimestamptzFiltering>,
     pub modified: Option<TimestamptzFiltering>,
+    pub solved_at: Option<NullableTimestamptzFiltering>,
     pub dazed_on: Option<DateFiltering>,
     pub license_id: Option<NullableI32Filtering>,
+    pub star_count: Option<I64Filtering>,
+    pub star_sum: Option<I64Filtering>,
+    pub dialogue_count: Option<I64Filtering>,
+    pub bookmark_count: Option<I64Filtering>,
+    pub comment_count: Option<I64Filtering>,
+    /// The puzzles in the collection
+    pub collection_id: Option<ID>,
 }
 
 impl CindyFilter<puzzle::table, DB> for PuzzleFilter {
@@ -106,9 +193,21 @@ impl CindyFilter<puzzle::table, DB> for PuzzleFilter {
             user_id: obj_user_id,
             created: obj_created,
             modified: obj_modified,
+            solved_at: obj_solved_at,
             dazed_on: obj_dazed_on,
             license_id: obj_license_id,
+            star_count: obj_star_count,
+            star_sum: obj_star_sum,
+            dialogue_count: obj_dialogue_count,
+            bookmark_count: obj_bookmark_count,
+            comment_count: obj_comment_count,
+            collection_id: obj_collection_id,
         } = self;
+        let star_count = StarCount;
+        let star_sum = StarSum;
+        let dialogue_count = DialogueCount;
+        let bookmark_count = BookmarkCount;
+        let comment_count = CommentCount;
         gen_number_filter!(obj_id: I32Filtering, id, filter);
         gen_bool_filter!(obj_anonymous, anonymous, filter);
         gen_string_filter!(obj_title, title, filter);
@@ -120,8 +219,69 @@ impl CindyFilter<puzzle::table, DB> for PuzzleFilter {
         gen_number_filter!(obj_user_id: I32Filtering, user_id, filter);
         gen_number_filter!(obj_created: TimestamptzFiltering, created, filter);
         gen_number_filter!(obj_modified: TimestamptzFiltering, modified, filter);
+        gen_nullable_number_filter!(obj_solved_at: NullableTimestamptzFiltering, solved_at, filter);
         gen_number_filter!(obj_dazed_on: DateFiltering, dazed_on, filter);
         gen_nullable_number_filter!(obj_license_id: NullableI32Filtering, license_id, filter);
+        gen_number_filter!(obj_star_count: I64Filtering, star_count, filter);
+        gen_number_filter!(obj_star_sum: I64Filtering, star_sum, filter);
+        gen_number_filter!(obj_dialogue_count: I64Filtering, dialogue_count, filter);
+        gen_number_filter!(obj_bookmark_count: I64Filtering, bookmark_count, filter);
+        gen_number_filter!(obj_comment_count: I64Filtering, comment_count, filter);
+        if let Some(obj_collection_id) = obj_collection_id {
+            // Only public collections without the request
+            let item = Collection::puzzles_expression(obj_collection_id, None);
+            filter = Some(if let Some(filt_) = filter {
+                Box::new(filt_.and(item))
+            } else {
+                item
+            });
+        }
+        filter
+    }
+}
+
+impl PuzzleFilter {
+    /// Builds the expression of the filters, restricting the ones on `solution` to the
+    /// puzzles whose solution is visible to the request, the ones on `user_id` to the
+    /// puzzles whose author is visible to the request, and the ones on `collection_id` to
+    /// the collections visible to the request.
+    pub fn as_guarded_expression(
+        filters: Vec<PuzzleFilter>,
+        reqctx: &RequestCtx,
+    ) -> Option<Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send>> {
+        let mut filter: Option<
+            Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send>,
+        > = None;
+        for mut item in filters {
+            let probes_solution = item.solution.is_some();
+            let probes_author = item.user_id.is_some();
+            let in_collection = item
+                .collection_id
+                .take()
+                .map(|collection_id| Collection::puzzles_expression(collection_id, Some(reqctx)));
+            let mut item: Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send> =
+                match (item.as_expression(), in_collection) {
+                    (Some(item), Some(in_collection)) => Box::new(item.and(in_collection)),
+                    (Some(item), None) => item,
+                    (None, Some(in_collection)) => in_collection,
+                    (None, None) => continue,
+                };
+            if probes_solution {
+                if let Some(visible) = Puzzle::solution_visible_expression(reqctx) {
+                    item = Box::new(item.and(visible));
+                }
+            }
+            if probes_author {
+                if let Some(visible) = Puzzle::author_visible_expression(reqctx) {
+                    item = Box::new(item.and(visible));
+                }
+            }
+            filter = Some(if let Some(filter_) = filter {
+                Box::new(filter_.or(item))
+            } else {
+                item
+            });
+        }
         filter
     }
 }
@@ -150,7 +310,7 @@ impl RawFilter<Yami> for YamiFiltering {
     }
 }
 
-#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow)]
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow, Serialize, Deserialize)]
 pub enum Yami {
     None = 0,
     Normal = 1,
@@ -198,7 +358,7 @@ pub struct GenreFiltering {
     pub ne_all: Option<Vec<Genre>>,
 }
 
-#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug, FromSqlRow)]
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug, FromSqlRow, Serialize, Deserialize)]
 pub enum Genre {
     Classic = 0,
     TwentyQuestions = 1,
@@ -256,13 +416,24 @@ where
     }
 }
 
-#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow)]
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow, Serialize, Deserialize)]
 pub enum Status {
     Undergoing = 0,
     Solved = 1,
     Dazed = 2,
     Hidden = 3,
     ForceHidden = 4,
+    /// Only visible to the author and admins until it is published
+    Draft = 5,
+}
+
+impl Status {
+    /// Statuses of the puzzles which are not finished yet
+    pub const UNFINISHED: [Status; 2] = [Status::Undergoing, Status::Draft];
+
+    pub fn is_finished(self) -> bool {
+        !Status::UNFINISHED.contains(&self)
+    }
 }
 
 #[derive(InputObject, Eq, PartialEq, Clone)]
@@ -319,34 +490,207 @@ where
             2 => Ok(Status::Dazed),
             3 => Ok(Status::Hidden),
             4 => Ok(Status::ForceHidden),
+            5 => Ok(Status::Draft),
             v => Err(format!("Invalid value `{}` for genre", &v).into()),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PuzzleSub {
     Created(Puzzle),
     Updated(Puzzle, Puzzle),
+    Deleted(Puzzle),
+    Resync,
 }
 
-#[Object]
 impl PuzzleSub {
+    /// Hides the solution and memo of the undergoing puzzles, for delivering the message
+    /// outside of the GraphQL resolvers.
+    pub fn redacted(self) -> Self {
+        match self {
+            PuzzleSub::Created(puzzle) => PuzzleSub::Created(puzzle.redacted()),
+            PuzzleSub::Updated(orig, puzzle) => {
+                PuzzleSub::Updated(orig.redacted(), puzzle.redacted())
+            }
+            PuzzleSub::Deleted(puzzle) => PuzzleSub::Deleted(puzzle.redacted()),
+            PuzzleSub::Resync => PuzzleSub::Resync,
+        }
+    }
+}
+
+impl BrokerMessage for PuzzleSub {
+    const TOPIC: &'static str = "puzzle";
+    const DURABLE: bool = true;
+
+    fn resync() -> Option<Self> {
+        Some(PuzzleSub::Resync)
+    }
+}
+
+#[Object(name = "PuzzleSub")]
+impl Event<PuzzleSub> {
+    async fn event_id(&self) -> Option<EventId> {
+        self.id
+    }
+
     async fn op(&self) -> DbOp {
-        match &self {
+        match &self.msg {
             PuzzleSub::Created(_) => DbOp::Created,
             PuzzleSub::Updated(_, _) => DbOp::Updated,
+            PuzzleSub::Deleted(_) => DbOp::Deleted,
+            PuzzleSub::Resync => DbOp::Resync,
         }
     }
 
-    async fn data(&self) -> Puzzle {
-        match &self {
-            PuzzleSub::Created(puzzle) => puzzle.clone(),
-            PuzzleSub::Updated(_, puzzle) => puzzle.clone(),
+    async fn data(&self) -> Option<Puzzle> {
+        match &self.msg {
+            PuzzleSub::Created(puzzle) => Some(puzzle.clone()),
+            PuzzleSub::Updated(_, puzzle) => Some(puzzle.clone()),
+            PuzzleSub::Deleted(puzzle) => Some(puzzle.clone()),
+            PuzzleSub::Resync => None,
         }
     }
 }
 
+/// Available group-by columns for puzzle aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PuzzleAggregateGroupBy {
+    UserId,
+    Genre,
+    Yami,
+    Status,
+    Anonymous,
+    Grotesque,
+    LicenseId,
+    CreatedYear,
+    CreatedMonth,
+    CreatedDay,
+}
+
+impl AggregateGroupBy for PuzzleAggregateGroupBy {
+    fn column(self) -> usize {
+        match self {
+            PuzzleAggregateGroupBy::UserId => 0,
+            PuzzleAggregateGroupBy::Genre => 1,
+            PuzzleAggregateGroupBy::Yami => 2,
+            PuzzleAggregateGroupBy::Status => 3,
+            PuzzleAggregateGroupBy::Anonymous => 4,
+            PuzzleAggregateGroupBy::Grotesque => 5,
+            PuzzleAggregateGroupBy::LicenseId => 6,
+            PuzzleAggregateGroupBy::CreatedYear
+            | PuzzleAggregateGroupBy::CreatedMonth
+            | PuzzleAggregateGroupBy::CreatedDay => 7,
+        }
+    }
+
+    fn expression(self) -> &'static str {
+        match self {
+            PuzzleAggregateGroupBy::UserId => "t.user_id",
+            PuzzleAggregateGroupBy::Genre => "t.genre",
+            PuzzleAggregateGroupBy::Yami => "t.yami",
+            PuzzleAggregateGroupBy::Status => "t.status",
+            PuzzleAggregateGroupBy::Anonymous => "t.anonymous",
+            PuzzleAggregateGroupBy::Grotesque => "t.grotesque",
+            PuzzleAggregateGroupBy::LicenseId => "t.license_id",
+            PuzzleAggregateGroupBy::CreatedYear => "date_trunc('year', t.created)",
+            PuzzleAggregateGroupBy::CreatedMonth => "date_trunc('month', t.created)",
+            PuzzleAggregateGroupBy::CreatedDay => "date_trunc('day', t.created)",
+        }
+    }
+}
+
+/// Available fields to apply sum/avg/min/max in puzzle aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PuzzleAggregateField {
+    Anonymous,
+    Grotesque,
+}
+
+impl AggregateField for PuzzleAggregateField {
+    fn expression(self) -> &'static str {
+        match self {
+            PuzzleAggregateField::Anonymous => "t.anonymous::int",
+            PuzzleAggregateField::Grotesque => "t.grotesque::int",
+        }
+    }
+}
+
+/// A row of puzzle aggregate query.
+///
+/// Group columns not grouped by are always `null`.
+#[derive(Queryable, Clone, Debug)]
+pub struct PuzzleAggregate {
+    pub user_id: Option<ID>,
+    pub genre: Option<Genre>,
+    pub yami: Option<Yami>,
+    pub status: Option<Status>,
+    pub anonymous: Option<bool>,
+    pub grotesque: Option<bool>,
+    pub license_id: Option<ID>,
+    pub created: Option<Timestamptz>,
+    pub values: AggregateValues,
+}
+
+impl Aggregate for PuzzleAggregate {
+    type SqlType = (
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Bool>,
+        Nullable<Bool>,
+        Nullable<Int4>,
+        Nullable<sql_types::Timestamptz>,
+        AggregateValuesSqlType,
+    );
+    type GroupBy = PuzzleAggregateGroupBy;
+    type Field = PuzzleAggregateField;
+
+    const GROUP_COLUMNS: &'static [&'static str] = &[
+        "NULL::int4",
+        "NULL::int4",
+        "NULL::int4",
+        "NULL::int4",
+        "NULL::bool",
+        "NULL::bool",
+        "NULL::int4",
+        "NULL::timestamptz",
+    ];
+}
+
+#[Object]
+impl PuzzleAggregate {
+    async fn user_id(&self) -> Option<ID> {
+        self.user_id
+    }
+    async fn genre(&self) -> Option<Genre> {
+        self.genre
+    }
+    async fn yami(&self) -> Option<Yami> {
+        self.yami
+    }
+    async fn status(&self) -> Option<Status> {
+        self.status
+    }
+    async fn anonymous(&self) -> Option<bool> {
+        self.anonymous
+    }
+    async fn grotesque(&self) -> Option<bool> {
+        self.grotesque
+    }
+    async fn license_id(&self) -> Option<ID> {
+        self.license_id
+    }
+    /// Start of the time unit in `created_*` group
+    async fn created(&self) -> Option<Timestamptz> {
+        self.created
+    }
+    async fn values(&self) -> &AggregateValues {
+        &self.values
+    }
+}
+
 #[derive(QueryableByName, Clone, Debug)]
 pub struct PuzzleCountByGenre {
     #[sql_type = "Integer"]
@@ -438,7 +782,7 @@ impl PuzzleParticipant {
 }
 
 /// Object for user table
-#[derive(Queryable, QueryableByName, Identifiable, Clone, Debug)]
+#[derive(Queryable, QueryableByName, Identifiable, Clone, Debug, Serialize, Deserialize)]
 #[table_name = "puzzle"]
 pub struct Puzzle {
     pub id: ID,
@@ -456,7 +800,198 @@ pub struct Puzzle {
     pub dazed_on: Date,
     pub grotesque: bool,
     pub license_id: Option<ID>,
-    pub content_image: Option<Vec<u8>>,
+    /// Time the puzzle left `Undergoing`
+    pub solved_at: Option<Timestamptz>,
+    pub content_image_id: Option<ID>,
+}
+
+/// Subquery of the published puzzles, i.e. the ones visible to everyone.
+pub fn published_puzzles() -> diesel::dsl::Select<
+    diesel::dsl::Filter<puzzle::table, diesel::dsl::NotEq<puzzle::status, Status>>,
+    puzzle::id,
+> {
+    puzzle::table
+        .filter(puzzle::status.ne(Status::Draft))
+        .select(puzzle::id)
+}
+
+/// Asserts the puzzle is published, so that users cannot interact with drafts by id.
+pub fn published_guard(conn: &PgConnection, puzzle_id: ID) -> async_graphql::Result<()> {
+    let status: Status = puzzle::table
+        .filter(puzzle::id.eq(puzzle_id))
+        .select(puzzle::status)
+        .first(conn)?;
+    if status == Status::Draft {
+        return Err(async_graphql::Error::new("The puzzle is not published yet"));
+    }
+
+    Ok(())
+}
+
+/// Subquery of the puzzles the user is a co-author or an answerer of.
+fn coauthored_by(
+    user_id: ID,
+) -> diesel::dsl::Select<
+    diesel::dsl::Filter<
+        crate::schema::puzzle_coauthor::table,
+        diesel::dsl::Eq<crate::schema::puzzle_coauthor::user_id, ID>,
+    >,
+    crate::schema::puzzle_coauthor::puzzle_id,
+> {
+    use crate::schema::puzzle_coauthor;
+
+    puzzle_coauthor::table
+        .filter(puzzle_coauthor::user_id.eq(user_id))
+        .select(puzzle_coauthor::puzzle_id)
+}
+
+impl Puzzle {
+    /// Role of the user among the co-authors of the puzzle, or `None` if they are not one.
+    pub fn coauthor_role(
+        &self,
+        conn: &PgConnection,
+        user_id: Option<ID>,
+    ) -> QueryResult<Option<CoauthorRole>> {
+        use crate::schema::puzzle_coauthor;
+
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None => return Ok(None),
+        };
+        puzzle_coauthor::table
+            .filter(puzzle_coauthor::puzzle_id.eq(self.id))
+            .filter(puzzle_coauthor::user_id.eq(user_id))
+            .select(puzzle_coauthor::role)
+            .first(conn)
+            .optional()
+    }
+
+    /// Ids of the author and the co-authors of the puzzle.
+    pub fn author_ids(&self, conn: &PgConnection) -> QueryResult<Vec<ID>> {
+        use crate::schema::puzzle_coauthor;
+
+        let mut author_ids: Vec<ID> = puzzle_coauthor::table
+            .filter(puzzle_coauthor::puzzle_id.eq(self.id))
+            .select(puzzle_coauthor::user_id)
+            .load(conn)?;
+        author_ids.push(self.user_id);
+
+        Ok(author_ids)
+    }
+
+    /// Whether the puzzle is visible to the request.
+    ///
+    /// Drafts are only visible to the authors and admins.
+    pub fn is_visible_to(&self, conn: &PgConnection, reqctx: &RequestCtx) -> QueryResult<bool> {
+        if self.status != Status::Draft
+            || reqctx.get_role() == Role::Admin
+            || reqctx.get_user_id() == Some(self.user_id)
+        {
+            return Ok(true);
+        }
+
+        Ok(self.coauthor_role(conn, reqctx.get_user_id())?.is_some())
+    }
+
+    /// Expression of the puzzles visible to the request, or `None` if all of them are.
+    pub fn visible_expression(
+        reqctx: &RequestCtx,
+    ) -> Option<Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send>> {
+        if reqctx.get_role() == Role::Admin {
+            return None;
+        }
+        let published = puzzle::status.ne(Status::Draft);
+        Some(match reqctx.get_user_id() {
+            Some(user_id) => Box::new(
+                published
+                    .or(puzzle::user_id.eq(user_id))
+                    .or(puzzle::id.eq_any(coauthored_by(user_id))),
+            ),
+            None => Box::new(published),
+        })
+    }
+
+    /// Whether the solution and memo are visible to the request.
+    ///
+    /// They are hidden while the puzzle is unfinished, except for the authors and admins.
+    pub fn solution_visible_to(
+        &self,
+        conn: &PgConnection,
+        reqctx: &RequestCtx,
+    ) -> QueryResult<bool> {
+        if self.status.is_finished()
+            || reqctx.get_role() == Role::Admin
+            || reqctx.get_user_id() == Some(self.user_id)
+        {
+            return Ok(true);
+        }
+
+        Ok(self.coauthor_role(conn, reqctx.get_user_id())?.is_some())
+    }
+
+    /// Expression of the puzzles whose solution is visible to the request, or `None` if
+    /// all of them are.
+    pub fn solution_visible_expression(
+        reqctx: &RequestCtx,
+    ) -> Option<Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send>> {
+        if reqctx.get_role() == Role::Admin {
+            return None;
+        }
+        let finished = diesel::dsl::not(puzzle::status.eq_any(Status::UNFINISHED.to_vec()));
+        Some(match reqctx.get_user_id() {
+            Some(user_id) => Box::new(
+                finished
+                    .or(puzzle::user_id.eq(user_id))
+                    .or(puzzle::id.eq_any(coauthored_by(user_id))),
+            ),
+            None => Box::new(finished),
+        })
+    }
+
+    /// Whether the author of the puzzle is visible to the request.
+    ///
+    /// The author of an anonymous puzzle is only visible to themselves and admins, until
+    /// the puzzle is finished if `ANONYMOUS_REVEAL_ON_SOLVE` is set.
+    pub fn author_visible_to(&self, reqctx: &RequestCtx) -> bool {
+        !self.anonymous
+            || (*ANONYMOUS_REVEAL_ON_SOLVE && self.status.is_finished())
+            || reqctx.get_role() == Role::Admin
+            || reqctx.get_user_id() == Some(self.user_id)
+    }
+
+    /// Expression of the puzzles whose author is visible to the request, or `None` if
+    /// all of them are.
+    pub fn author_visible_expression(
+        reqctx: &RequestCtx,
+    ) -> Option<Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send>> {
+        if reqctx.get_role() == Role::Admin {
+            return None;
+        }
+        let mut visible: Box<dyn BoxableExpression<puzzle::table, DB, SqlType = Bool> + Send> =
+            Box::new(puzzle::anonymous.eq(false));
+        if *ANONYMOUS_REVEAL_ON_SOLVE {
+            visible = Box::new(visible.or(diesel::dsl::not(
+                puzzle::status.eq_any(Status::UNFINISHED.to_vec()),
+            )));
+        }
+        if let Some(user_id) = reqctx.get_user_id() {
+            visible = Box::new(visible.or(puzzle::user_id.eq(user_id)));
+        }
+        Some(visible)
+    }
+
+    /// The puzzle with the solution and memo cleared if it is unfinished, and the author
+    /// replaced with `ANONYMOUS_USER_ID` if it is masked.
+    pub fn redacted(mut self) -> Self {
+        if !self.status.is_finished() {
+            self.solution = String::new();
+            self.memo = String::new();
+        }
+        if !self.author_visible_to(&RequestCtx::default()) {
+            self.user_id = ANONYMOUS_USER_ID;
+        }
+        self
+    }
 }
 
 #[Object]
@@ -476,8 +1011,16 @@ impl Puzzle {
     async fn content(&self) -> &str {
         &self.content
     }
-    async fn solution(&self) -> &str {
-        &self.solution
+    /// The solution, which is `null` while the puzzle is undergoing unless the request is
+    /// from the authors or an admin
+    async fn solution(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<&str>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        if self.solution_visible_to(&conn, reqctx)? {
+            Ok(Some(&self.solution))
+        } else {
+            Ok(None)
+        }
     }
     async fn created(&self) -> Timestamptz {
         self.created
@@ -488,11 +1031,24 @@ impl Puzzle {
     async fn status(&self) -> Status {
         self.status
     }
-    async fn memo(&self) -> &str {
-        &self.memo
+    /// The memo, which is hidden in the same way as `solution`
+    async fn memo(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<&str>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+        if self.solution_visible_to(&conn, reqctx)? {
+            Ok(Some(&self.memo))
+        } else {
+            Ok(None)
+        }
     }
-    async fn user_id(&self) -> ID {
-        self.user_id
+    /// The author, which is `null` if the puzzle is anonymous and the author is masked
+    async fn user_id(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ID>> {
+        let reqctx = ctx.data::<RequestCtx>()?;
+        if self.author_visible_to(reqctx) {
+            Ok(Some(self.user_id))
+        } else {
+            Ok(None)
+        }
     }
     async fn anonymous(&self) -> bool {
         self.anonymous
@@ -506,21 +1062,29 @@ impl Puzzle {
     async fn license_id(&self) -> Option<ID> {
         self.license_id
     }
-    async fn content_image(&self) -> &Option<Vec<u8>> {
-        &self.content_image
+    async fn solved_at(&self) -> Option<Timestamptz> {
+        self.solved_at
+    }
+    async fn content_image_id(&self) -> Option<ID> {
+        self.content_image_id
     }
 
-    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
         use crate::schema::user;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        if !self.author_visible_to(reqctx) {
+            return Ok(None);
+        }
 
         let user = user::table
             .filter(user::id.eq(self.user_id))
             .limit(1)
             .first(&conn)?;
 
-        Ok(user)
+        Ok(Some(user))
     }
 
     async fn license(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<License>> {
@@ -541,6 +1105,161 @@ impl Puzzle {
         Ok(license)
     }
 
+    async fn content_image(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Image>> {
+        use crate::schema::image;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let image = if let Some(id) = self.content_image_id {
+            image::table
+                .filter(image::id.eq(id))
+                .limit(1)
+                .first(&conn)
+                .optional()?
+        } else {
+            None
+        };
+
+        Ok(image)
+    }
+
+    /// Images attached to the content in order
+    async fn attachments(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Attachment>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let attachments = Attachment::load_of(&conn, AttachmentTarget::PuzzleContent, self.id)?;
+
+        Ok(attachments)
+    }
+
+    /// Images attached to the solution in order, which are hidden along with the solution
+    async fn solution_attachments(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<Vec<Attachment>>> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        if !self.solution_visible_to(&conn, reqctx)? {
+            return Ok(None);
+        }
+        let attachments = Attachment::load_of(&conn, AttachmentTarget::PuzzleSolution, self.id)?;
+
+        Ok(Some(attachments))
+    }
+
+    /// Co-authors and answerers of the puzzle, which are hidden along with the author
+    async fn coauthors(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<PuzzleCoauthor>> {
+        use crate::schema::puzzle_coauthor;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        if !self.author_visible_to(reqctx) {
+            return Ok(Vec::new());
+        }
+        let coauthors = puzzle_coauthor::table
+            .filter(puzzle_coauthor::puzzle_id.eq(self.id))
+            .order(puzzle_coauthor::id.asc())
+            .load(&conn)?;
+
+        Ok(coauthors)
+    }
+
+    /// The puzzle this one is a remix of, if it is visible
+    async fn derived_from(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Puzzle>> {
+        use crate::schema::puzzle_derivation;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let source_id: Option<Option<ID>> = puzzle_derivation::table
+            .filter(puzzle_derivation::puzzle_id.eq(self.id))
+            .select(puzzle_derivation::source_id)
+            .first(&conn)
+            .optional()?;
+        let source: Option<Puzzle> = match source_id.flatten() {
+            Some(source_id) => puzzle::table
+                .filter(puzzle::id.eq(source_id))
+                .limit(1)
+                .first(&conn)
+                .optional()?,
+            None => None,
+        };
+        let source = match source {
+            Some(source) if source.is_visible_to(&conn, reqctx)? => Some(source),
+            _ => None,
+        };
+
+        Ok(source)
+    }
+
+    /// Attribution of the source if the puzzle is a remix, which is kept after the source
+    /// is deleted
+    async fn attribution(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<String>> {
+        use crate::schema::puzzle_derivation;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let attribution = puzzle_derivation::table
+            .filter(puzzle_derivation::puzzle_id.eq(self.id))
+            .select(puzzle_derivation::attribution)
+            .first(&conn)
+            .optional()?;
+
+        Ok(attribution)
+    }
+
+    /// Remixes of the puzzle, leaving out the ones not visible
+    async fn derivatives(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Puzzle>> {
+        use crate::schema::puzzle_derivation;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = puzzle::table
+            .filter(
+                puzzle::id.eq_any(
+                    puzzle_derivation::table
+                        .filter(puzzle_derivation::source_id.eq(self.id))
+                        .select(puzzle_derivation::puzzle_id),
+                ),
+            )
+            .order(puzzle::id.asc())
+            .into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let derivatives = query.load(&conn)?;
+
+        Ok(derivatives)
+    }
+
+    /// Collections containing the puzzle, leaving out the ones not visible
+    async fn collections(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Collection>> {
+        use crate::schema::{collection, collection_puzzle};
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let mut query = collection::table
+            .filter(
+                collection::id.eq_any(
+                    collection_puzzle::table
+                        .filter(collection_puzzle::puzzle_id.eq(self.id))
+                        .select(collection_puzzle::collection_id),
+                ),
+            )
+            .order(collection::id.asc())
+            .into_boxed();
+        if let Some(visible) = Collection::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let collections = query.load(&conn)?;
+
+        Ok(collections)
+    }
+
     async fn bookmarks(
         &self,
         ctx: &Context<'_>,
@@ -806,4 +1525,52 @@ impl Puzzle {
 
         Ok(result)
     }
+
+    /// Revisions of the text fields, from the oldest one
+    async fn revisions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<PuzzleRevisionDiff>> {
+        use crate::schema::puzzle_revision;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let revisions: Vec<PuzzleRevision> = puzzle_revision::table
+            .filter(puzzle_revision::puzzle_id.eq(self.id))
+            .order(puzzle_revision::id.asc())
+            .load(&conn)?;
+
+        let solution_visible = self.solution_visible_to(&conn, reqctx)?;
+        // Editors to be masked, i.e. the authors of an anonymous puzzle
+        let masked_ids = if self.author_visible_to(reqctx) {
+            Vec::new()
+        } else {
+            self.author_ids(&conn)?
+        };
+        let mut next = (
+            self.content.clone(),
+            self.solution.clone(),
+            self.memo.clone(),
+        );
+        let mut results: Vec<PuzzleRevisionDiff> = revisions
+            .into_iter()
+            .rev()
+            .map(|revision| {
+                let prev = (
+                    revision.content.clone(),
+                    revision.solution.clone(),
+                    revision.memo.clone(),
+                );
+                PuzzleRevisionDiff {
+                    user_visible: revision
+                        .user_id
+                        .map_or(true, |user_id| !masked_ids.contains(&user_id)),
+                    revision,
+                    next: std::mem::replace(&mut next, prev),
+                    solution_visible,
+                }
+            })
+            .collect();
+        results.reverse();
+
+        Ok(results)
+    }
 }
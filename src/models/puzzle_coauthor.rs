@@ -0,0 +1,137 @@
+use async_graphql::{self, Context, Enum, Object};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    expression::{helper_types::AsExprOf, AsExpression},
+    prelude::*,
+    serialize::{self, Output, ToSql},
+    sql_types::Integer,
+};
+use std::io;
+
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::schema::puzzle_coauthor;
+
+use super::*;
+
+/// Rights of a co-author of the puzzle
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow)]
+pub enum CoauthorRole {
+    /// Shares all the rights of the author
+    Coauthor = 0,
+    /// Only answers the questions
+    Answerer = 1,
+}
+
+impl<DB> ToSql<Integer, DB> for CoauthorRole
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for CoauthorRole {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for CoauthorRole
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(CoauthorRole::Coauthor),
+            1 => Ok(CoauthorRole::Answerer),
+            v => Err(format!("Invalid value `{}` for coauthor role", &v).into()),
+        }
+    }
+}
+
+/// Object for puzzle_coauthor table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "puzzle_coauthor"]
+pub struct PuzzleCoauthor {
+    pub id: ID,
+    pub puzzle_id: ID,
+    pub user_id: ID,
+    pub role: CoauthorRole,
+    pub created: Timestamptz,
+}
+
+#[Object]
+impl PuzzleCoauthor {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn puzzle_id(&self) -> ID {
+        self.puzzle_id
+    }
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn role(&self) -> CoauthorRole {
+        self.role
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user = user::table
+            .filter(user::id.eq(self.user_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(user)
+    }
+}
+
+/// Asserts the user is the author or a co-author of the puzzle, who can edit it along with
+/// its hints.
+pub fn puzzle_author_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    puzzle: &Puzzle,
+) -> async_graphql::Result<()> {
+    if user_id_guard(ctx, puzzle.user_id).is_ok() {
+        return Ok(());
+    }
+    let reqctx = ctx.data::<RequestCtx>()?;
+    match puzzle.coauthor_role(conn, reqctx.get_user_id())? {
+        Some(CoauthorRole::Coauthor) => Ok(()),
+        _ => Err(async_graphql::Error::new(
+            "Only the authors can edit the puzzle",
+        )),
+    }
+}
+
+/// Asserts the user is the author, a co-author or an answerer of the puzzle, who can
+/// answer its questions.
+pub fn puzzle_answerer_guard(
+    ctx: &Context<'_>,
+    conn: &PgConnection,
+    puzzle: &Puzzle,
+) -> async_graphql::Result<()> {
+    if user_id_guard(ctx, puzzle.user_id).is_ok() {
+        return Ok(());
+    }
+    let reqctx = ctx.data::<RequestCtx>()?;
+    match puzzle.coauthor_role(conn, reqctx.get_user_id())? {
+        Some(_) => Ok(()),
+        None => Err(async_graphql::Error::new(
+            "Only the authors can answer the questions",
+        )),
+    }
+}
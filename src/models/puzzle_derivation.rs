@@ -0,0 +1,63 @@
+use diesel::prelude::*;
+
+use crate::schema::{license, puzzle, puzzle_derivation};
+
+use super::*;
+
+/// Object for puzzle_derivation table.
+///
+/// A derivation links a remix to the puzzle it is derived from, along with the attribution
+/// of the source.
+#[derive(Queryable, Identifiable, Insertable, Clone, Debug)]
+#[table_name = "puzzle_derivation"]
+#[primary_key(puzzle_id)]
+pub struct PuzzleDerivation {
+    pub puzzle_id: ID,
+    /// The source, which is `None` once it is deleted
+    pub source_id: Option<ID>,
+    pub attribution: String,
+    pub created: Timestamptz,
+}
+
+impl PuzzleDerivation {
+    /// Attribution of the source puzzle, naming its author unless it is masked.
+    pub fn attribution(
+        source: &Puzzle,
+        author: Option<&User>,
+        license: Option<&License>,
+    ) -> String {
+        let author = match author {
+            Some(author) => author.nickname.as_str(),
+            None => "an anonymous author",
+        };
+        let mut attribution = format!(
+            "Derived from \"{}\" (#{}) by {}",
+            &source.title, source.id, author
+        );
+        match license {
+            Some(License {
+                name,
+                url: Some(url),
+                ..
+            }) => attribution.push_str(&format!(", used under {} ({})", name, url)),
+            Some(License { name, .. }) => attribution.push_str(&format!(", used under {}", name)),
+            None => {}
+        }
+        attribution
+    }
+
+    /// The license required on the puzzle, i.e. the license of its source if it is
+    /// share-alike.
+    pub fn required_license_id(conn: &PgConnection, puzzle_id: ID) -> QueryResult<Option<ID>> {
+        let license_id: Option<Option<ID>> = puzzle_derivation::table
+            .inner_join(puzzle::table.on(puzzle::id.nullable().eq(puzzle_derivation::source_id)))
+            .inner_join(license::table.on(puzzle::license_id.eq(license::id.nullable())))
+            .filter(puzzle_derivation::puzzle_id.eq(puzzle_id))
+            .filter(license::share_alike.eq(true))
+            .select(puzzle::license_id)
+            .first(conn)
+            .optional()?;
+
+        Ok(license_id.flatten())
+    }
+}
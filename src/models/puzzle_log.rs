@@ -1,7 +1,10 @@
 use async_graphql::{InputObject, Interface, Object};
 use diesel::prelude::*;
 use diesel::sql_types::Bool;
+use serde::{Deserialize, Serialize};
 
+use crate::broker::{BrokerMessage, Event, EventId};
+use crate::models::puzzle::ANONYMOUS_USER_ID;
 use crate::schema::{dialogue, hint};
 
 use super::*;
@@ -109,34 +112,106 @@ pub enum PuzzleLog {
     Hint(Hint),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PuzzleLogSub {
     DialogueCreated(Dialogue),
     DialogueUpdated(Dialogue, Dialogue),
     HintCreated(Hint),
     HintUpdated(Hint, Hint),
+    DialogueDeleted(Dialogue),
+    HintDeleted(Hint),
+    Resync,
 }
 
-#[Object]
 impl PuzzleLogSub {
+    /// Id of the puzzle the message belongs to.
+    pub fn puzzle_id(&self) -> Option<ID> {
+        match self {
+            PuzzleLogSub::DialogueCreated(dialogue)
+            | PuzzleLogSub::DialogueUpdated(_, dialogue)
+            | PuzzleLogSub::DialogueDeleted(dialogue) => Some(dialogue.puzzle_id),
+            PuzzleLogSub::HintCreated(hint)
+            | PuzzleLogSub::HintUpdated(_, hint)
+            | PuzzleLogSub::HintDeleted(hint) => Some(hint.puzzle_id),
+            PuzzleLogSub::Resync => None,
+        }
+    }
+
+    /// Hides the answers and private hints of the undergoing puzzle, and the authors
+    /// among `author_ids` asking questions, for delivering the message outside of the
+    /// GraphQL resolvers.
+    pub fn redacted(self, puzzle: &Puzzle, author_ids: &[ID]) -> Self {
+        let finished = puzzle.status.is_finished();
+        let dialogue = |mut dialogue: Dialogue| {
+            if !finished {
+                dialogue.answer = String::new();
+            }
+            if author_ids.contains(&dialogue.user_id) {
+                dialogue.user_id = ANONYMOUS_USER_ID;
+            }
+            dialogue
+        };
+        let hint = |mut hint: Hint| {
+            if !finished && hint.receiver_id.is_some() {
+                hint.content = String::new();
+            }
+            hint
+        };
+
+        match self {
+            PuzzleLogSub::DialogueCreated(d) => PuzzleLogSub::DialogueCreated(dialogue(d)),
+            PuzzleLogSub::DialogueUpdated(orig, d) => {
+                PuzzleLogSub::DialogueUpdated(dialogue(orig), dialogue(d))
+            }
+            PuzzleLogSub::DialogueDeleted(d) => PuzzleLogSub::DialogueDeleted(dialogue(d)),
+            PuzzleLogSub::HintCreated(h) => PuzzleLogSub::HintCreated(hint(h)),
+            PuzzleLogSub::HintUpdated(orig, h) => PuzzleLogSub::HintUpdated(hint(orig), hint(h)),
+            PuzzleLogSub::HintDeleted(h) => PuzzleLogSub::HintDeleted(hint(h)),
+            PuzzleLogSub::Resync => PuzzleLogSub::Resync,
+        }
+    }
+}
+
+/// Published once to the keys of the puzzle, i.e. with the prefix `puzzleLog<{puzzle_id}`,
+/// as each message published is logged and exported to the webhooks.
+impl BrokerMessage for PuzzleLogSub {
+    const TOPIC: &'static str = "puzzleLog";
+    const DURABLE: bool = true;
+
+    fn resync() -> Option<Self> {
+        Some(PuzzleLogSub::Resync)
+    }
+}
+
+#[Object(name = "PuzzleLogSub")]
+impl Event<PuzzleLogSub> {
+    async fn event_id(&self) -> Option<EventId> {
+        self.id
+    }
+
     async fn op(&self) -> DbOp {
-        match &self {
+        match &self.msg {
             PuzzleLogSub::DialogueCreated(_) | PuzzleLogSub::HintCreated(_) => DbOp::Created,
             PuzzleLogSub::DialogueUpdated(_, _) | PuzzleLogSub::HintUpdated(_, _) => DbOp::Updated,
+            PuzzleLogSub::DialogueDeleted(_) | PuzzleLogSub::HintDeleted(_) => DbOp::Deleted,
+            PuzzleLogSub::Resync => DbOp::Resync,
         }
     }
 
-    async fn data(&self) -> PuzzleLog {
-        match &self {
-            PuzzleLogSub::DialogueCreated(obj) => PuzzleLog::Dialogue(obj.clone()),
-            PuzzleLogSub::HintCreated(obj) => PuzzleLog::Hint(obj.clone()),
-            PuzzleLogSub::DialogueUpdated(_, obj) => PuzzleLog::Dialogue(obj.clone()),
-            PuzzleLogSub::HintUpdated(_, obj) => PuzzleLog::Hint(obj.clone()),
+    async fn data(&self) -> Option<PuzzleLog> {
+        match &self.msg {
+            PuzzleLogSub::DialogueCreated(obj) => Some(PuzzleLog::Dialogue(obj.clone())),
+            PuzzleLogSub::HintCreated(obj) => Some(PuzzleLog::Hint(obj.clone())),
+            PuzzleLogSub::DialogueUpdated(_, obj) => Some(PuzzleLog::Dialogue(obj.clone())),
+            PuzzleLogSub::HintUpdated(_, obj) => Some(PuzzleLog::Hint(obj.clone())),
+            PuzzleLogSub::DialogueDeleted(obj) => Some(PuzzleLog::Dialogue(obj.clone())),
+            PuzzleLogSub::HintDeleted(obj) => Some(PuzzleLog::Hint(obj.clone())),
+            PuzzleLogSub::Resync => None,
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct UnsolvedPuzzleStatsSub {
     pub puzzle_id: i32,
     pub dialogue_count: i64,
@@ -144,6 +219,10 @@ pub struct UnsolvedPuzzleStatsSub {
     pub dialogue_max_answered_time: Timestamptz,
 }
 
+impl BrokerMessage for UnsolvedPuzzleStatsSub {
+    const TOPIC: &'static str = "unsolvedPuzzleStats";
+}
+
 #[Object]
 impl UnsolvedPuzzleStatsSub {
     async fn puzzle_id(&self) -> i32 {
@@ -0,0 +1,438 @@
+use async_graphql::{self, Context, Enum, Object, SimpleObject};
+use diesel::prelude::*;
+use std::ops::{Index, IndexMut};
+
+use crate::context::GlobalCtx;
+use crate::schema::puzzle_revision;
+
+use super::*;
+
+/// Object for puzzle_revision table.
+///
+/// A revision holds the text fields of the puzzle before an update changing them.
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "puzzle_revision"]
+pub struct PuzzleRevision {
+    pub id: ID,
+    pub puzzle_id: ID,
+    /// The user making the change
+    pub user_id: Option<ID>,
+    pub content: String,
+    pub solution: String,
+    pub memo: String,
+    pub created: Timestamptz,
+}
+
+#[derive(Insertable)]
+#[table_name = "puzzle_revision"]
+pub struct CreatePuzzleRevisionData<'a> {
+    pub puzzle_id: ID,
+    pub user_id: Option<ID>,
+    pub content: &'a str,
+    pub solution: &'a str,
+    pub memo: &'a str,
+}
+
+impl<'a> CreatePuzzleRevisionData<'a> {
+    /// Revision of the current text fields of the puzzle, changed by the user.
+    pub fn new(puzzle: &'a Puzzle, user_id: Option<ID>) -> Self {
+        Self {
+            puzzle_id: puzzle.id,
+            user_id,
+            content: &puzzle.content,
+            solution: &puzzle.solution,
+            memo: &puzzle.memo,
+        }
+    }
+}
+
+/// Kind of a line in a diff
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug)]
+pub enum DiffTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    pub text: String,
+}
+
+/// Furthest reaching x on each diagonal k of the edit graph, for k in `-max_d..max_d`
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        Self {
+            offset: max_d as isize,
+            v: vec![0; 2 * max_d],
+        }
+    }
+}
+
+impl Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+/// Number of the edit steps after which the forward and backward paths must meet
+fn max_d(old_len: usize, new_len: usize) -> usize {
+    (old_len + new_len).div_ceil(2) + 1
+}
+
+/// Start of the middle snake of the shortest edit script, searched for from both ends at
+/// once. Both `old` and `new` should not be empty.
+fn middle_snake(old: &[&str], new: &[&str], vf: &mut V, vb: &mut V) -> Option<(usize, usize)> {
+    let n = old.len();
+    let m = new.len();
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    vf[1] = 0;
+    vb[1] = 0;
+
+    for d in 0..max_d(n, m) as isize {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += old[x..]
+                    .iter()
+                    .zip(&new[y..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+            }
+            vf[k] = x;
+            if odd && (k - delta).abs() < d && vf[k] + vb[-(k - delta)] >= n {
+                return Some((x0, y0));
+            }
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let advance = old[..n - x]
+                    .iter()
+                    .rev()
+                    .zip(new[..m - y].iter().rev())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                x += advance;
+                y += advance;
+            }
+            vb[k] = x;
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[-(k - delta)] >= n {
+                return Some((n - x, m - y));
+            }
+        }
+    }
+
+    None
+}
+
+/// Appends the edit script from `old` to `new`, splitting it at the middle snakes.
+fn conquer<'a>(
+    old: &[&'a str],
+    new: &[&'a str],
+    vf: &mut V,
+    vb: &mut V,
+    ops: &mut Vec<(DiffTag, &'a str)>,
+) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    ops.extend(old[..prefix].iter().map(|text| (DiffTag::Equal, *text)));
+    if old_mid.is_empty() || new_mid.is_empty() {
+        ops.extend(old_mid.iter().map(|text| (DiffTag::Delete, *text)));
+        ops.extend(new_mid.iter().map(|text| (DiffTag::Insert, *text)));
+    } else if let Some((x, y)) = middle_snake(old_mid, new_mid, vf, vb) {
+        conquer(&old_mid[..x], &new_mid[..y], vf, vb, ops);
+        conquer(&old_mid[x..], &new_mid[y..], vf, vb, ops);
+    } else {
+        ops.extend(old_mid.iter().map(|text| (DiffTag::Delete, *text)));
+        ops.extend(new_mid.iter().map(|text| (DiffTag::Insert, *text)));
+    }
+    ops.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|text| (DiffTag::Equal, *text)),
+    );
+}
+
+/// Line diff from `old` to `new`, i.e. the shortest edit script found by the linear space
+/// variant of Myers' algorithm.
+///
+/// Deleted lines are listed before the inserted ones of each change.
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let max_d = max_d(old.len(), new.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    conquer(&old, &new, &mut vf, &mut vb, &mut ops);
+
+    let line = |tag, text: &str| DiffLine {
+        tag,
+        text: text.to_owned(),
+    };
+    let mut diff = Vec::with_capacity(ops.len());
+    let mut inserted = Vec::new();
+    for (tag, text) in ops {
+        match tag {
+            DiffTag::Insert => inserted.push(text),
+            DiffTag::Delete => diff.push(line(DiffTag::Delete, text)),
+            DiffTag::Equal => {
+                diff.extend(inserted.drain(..).map(|text| line(DiffTag::Insert, text)));
+                diff.push(line(DiffTag::Equal, text));
+            }
+        }
+    }
+    diff.extend(inserted.drain(..).map(|text| line(DiffTag::Insert, text)));
+
+    diff
+}
+
+/// A revision of the puzzle along with the changes made from it.
+///
+/// The solution and memo follow the visibility of the puzzle solution, and the editor is
+/// masked along with the author of an anonymous puzzle.
+pub struct PuzzleRevisionDiff {
+    pub revision: PuzzleRevision,
+    /// Text fields after the change, i.e. of the next revision or of the puzzle
+    pub next: (String, String, String),
+    pub solution_visible: bool,
+    pub user_visible: bool,
+}
+
+#[Object]
+impl PuzzleRevisionDiff {
+    async fn id(&self) -> ID {
+        self.revision.id
+    }
+    async fn puzzle_id(&self) -> ID {
+        self.revision.puzzle_id
+    }
+    async fn user_id(&self) -> Option<ID> {
+        self.revision.user_id.filter(|_| self.user_visible)
+    }
+    async fn created(&self) -> Timestamptz {
+        self.revision.created
+    }
+    /// The content before the change
+    async fn content(&self) -> &str {
+        &self.revision.content
+    }
+    /// The solution before the change, hidden in the same way as `Puzzle.solution`
+    async fn solution(&self) -> Option<&str> {
+        Some(self.revision.solution.as_str()).filter(|_| self.solution_visible)
+    }
+    /// The memo before the change, hidden in the same way as `Puzzle.solution`
+    async fn memo(&self) -> Option<&str> {
+        Some(self.revision.memo.as_str()).filter(|_| self.solution_visible)
+    }
+    async fn content_diff(&self) -> Vec<DiffLine> {
+        line_diff(&self.revision.content, &self.next.0)
+    }
+    async fn solution_diff(&self) -> Option<Vec<DiffLine>> {
+        if self.solution_visible {
+            Some(line_diff(&self.revision.solution, &self.next.1))
+        } else {
+            None
+        }
+    }
+    async fn memo_diff(&self) -> Option<Vec<DiffLine>> {
+        if self.solution_visible {
+            Some(line_diff(&self.revision.memo, &self.next.2))
+        } else {
+            None
+        }
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user_id = match self.revision.user_id.filter(|_| self.user_visible) {
+            Some(user_id) => user_id,
+            None => return Ok(None),
+        };
+        let user = user::table
+            .filter(user::id.eq(user_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(Some(user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> Vec<(DiffTag, String)> {
+        line_diff(old, new)
+            .into_iter()
+            .map(|line| (line.tag, line.text))
+            .collect()
+    }
+
+    fn lines(tag: DiffTag, texts: &[&str]) -> Vec<(DiffTag, String)> {
+        texts.iter().map(|text| (tag, text.to_string())).collect()
+    }
+
+    /// Length of the longest common subsequence, computed naively
+    fn lcs_len(old: &[&str], new: &[&str]) -> usize {
+        let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        lcs[0][0]
+    }
+
+    #[test]
+    fn diff_of_empty_strings() {
+        assert!(diff("", "").is_empty());
+        assert_eq!(diff("", "a\nb"), lines(DiffTag::Insert, &["a", "b"]));
+        assert_eq!(diff("a\nb", ""), lines(DiffTag::Delete, &["a", "b"]));
+    }
+
+    #[test]
+    fn diff_of_equal_strings() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nb\nc"),
+            lines(DiffTag::Equal, &["a", "b", "c"])
+        );
+    }
+
+    #[test]
+    fn diff_of_inserted_lines() {
+        assert_eq!(
+            diff("a\nd", "a\nb\nc\nd"),
+            vec![
+                (DiffTag::Equal, "a".to_string()),
+                (DiffTag::Insert, "b".to_string()),
+                (DiffTag::Insert, "c".to_string()),
+                (DiffTag::Equal, "d".to_string()),
+            ]
+        );
+        assert_eq!(
+            diff("b", "a\nb\nc"),
+            vec![
+                (DiffTag::Insert, "a".to_string()),
+                (DiffTag::Equal, "b".to_string()),
+                (DiffTag::Insert, "c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_of_deleted_lines() {
+        assert_eq!(
+            diff("a\nb\nc\nd", "a\nd"),
+            vec![
+                (DiffTag::Equal, "a".to_string()),
+                (DiffTag::Delete, "b".to_string()),
+                (DiffTag::Delete, "c".to_string()),
+                (DiffTag::Equal, "d".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_of_replaced_lines() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nx\ny\nc"),
+            vec![
+                (DiffTag::Equal, "a".to_string()),
+                (DiffTag::Delete, "b".to_string()),
+                (DiffTag::Insert, "x".to_string()),
+                (DiffTag::Insert, "y".to_string()),
+                (DiffTag::Equal, "c".to_string()),
+            ]
+        );
+        assert_eq!(
+            diff("a", "b"),
+            vec![
+                (DiffTag::Delete, "a".to_string()),
+                (DiffTag::Insert, "b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_is_a_shortest_edit_script() {
+        // Deterministic pseudo-random texts over a small alphabet
+        let mut seed: u32 = 12345;
+        let mut text = |len: usize| {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    ["a", "b", "c", "d"][(seed >> 16) as usize % 4]
+                })
+                .collect::<Vec<_>>()
+        };
+        for (old_len, new_len) in &[(1, 7), (7, 1), (10, 10), (13, 8), (30, 25), (64, 64)] {
+            let old = text(*old_len);
+            let new = text(*new_len);
+            let result = diff(&old.join("\n"), &new.join("\n"));
+
+            let from: Vec<&str> = result
+                .iter()
+                .filter(|(tag, _)| *tag != DiffTag::Insert)
+                .map(|(_, text)| text.as_str())
+                .collect();
+            let to: Vec<&str> = result
+                .iter()
+                .filter(|(tag, _)| *tag != DiffTag::Delete)
+                .map(|(_, text)| text.as_str())
+                .collect();
+            assert_eq!(from, old);
+            assert_eq!(to, new);
+
+            let equal = result
+                .iter()
+                .filter(|(tag, _)| *tag == DiffTag::Equal)
+                .count();
+            assert_eq!(equal, lcs_len(&old, &new));
+        }
+    }
+}
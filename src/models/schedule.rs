@@ -0,0 +1,147 @@
+use async_graphql::{self, Context, InputObject, Object};
+use diesel::{prelude::*, query_dsl::QueryDsl, sql_types::Bool};
+
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::schema::schedule;
+
+use super::*;
+
+/// Available orders for schedule query
+#[derive(InputObject, Clone)]
+pub struct ScheduleOrder {
+    id: Option<Ordering>,
+    scheduled: Option<Ordering>,
+}
+
+/// Helper object to apply the order to the query
+pub struct ScheduleOrders(Vec<ScheduleOrder>);
+
+impl Default for ScheduleOrders {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl ScheduleOrders {
+    pub fn new(orders: Vec<ScheduleOrder>) -> Self {
+        Self(orders)
+    }
+
+    pub fn apply_order<'a>(
+        self,
+        query_dsl: schedule::BoxedQuery<'a, DB>,
+    ) -> schedule::BoxedQuery<'a, DB> {
+        use crate::schema::schedule::dsl::*;
+
+        let mut query = query_dsl;
+
+        for obj in self.0 {
+            gen_order!(obj, id, query);
+            gen_order!(obj, scheduled, query);
+        }
+
+        query
+    }
+}
+
+/// Available filters for schedule query
+#[derive(InputObject, Clone, Default)]
+pub struct ScheduleFilter {
+    pub id: Option<I32Filtering>,
+    pub user_id: Option<I32Filtering>,
+    pub puzzle_id: Option<NullableI32Filtering>,
+    pub scheduled: Option<TimestamptzFiltering>,
+}
+
+impl CindyFilter<schedule::table, DB> for ScheduleFilter {
+    fn as_expression(
+        self,
+    ) -> Option<Box<dyn BoxableExpression<schedule::table, DB, SqlType = Bool> + Send>> {
+        use crate::schema::schedule::dsl::*;
+
+        let mut filter: Option<Box<dyn BoxableExpression<schedule, DB, SqlType = Bool> + Send>> =
+            None;
+        let ScheduleFilter {
+            id: obj_id,
+            user_id: obj_user_id,
+            puzzle_id: obj_puzzle_id,
+            scheduled: obj_scheduled,
+        } = self;
+        gen_number_filter!(obj_id: I32Filtering, id, filter);
+        gen_number_filter!(obj_user_id: I32Filtering, user_id, filter);
+        gen_nullable_number_filter!(obj_puzzle_id: NullableI32Filtering, puzzle_id, filter);
+        gen_number_filter!(obj_scheduled: TimestamptzFiltering, scheduled, filter);
+        filter
+    }
+}
+
+/// Object for schedule table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "schedule"]
+pub struct Schedule {
+    pub id: ID,
+    pub content: String,
+    pub created: Timestamptz,
+    /// Time to publish the puzzle at
+    pub scheduled: Timestamptz,
+    pub user_id: ID,
+    /// The draft puzzle to publish
+    pub puzzle_id: Option<ID>,
+}
+
+#[Object]
+impl Schedule {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn content(&self) -> &str {
+        &self.content
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+    async fn scheduled(&self) -> Timestamptz {
+        self.scheduled
+    }
+    async fn user_id(&self) -> ID {
+        self.user_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        self.puzzle_id
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
+        use crate::schema::user;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let user = user::table
+            .filter(user::id.eq(self.user_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(user)
+    }
+
+    async fn puzzle(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Puzzle>> {
+        use crate::schema::puzzle;
+
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
+
+        let puzzle_id = match self.puzzle_id {
+            Some(puzzle_id) => puzzle_id,
+            None => return Ok(None),
+        };
+        let puzzle: Puzzle = puzzle::table
+            .filter(puzzle::id.eq(puzzle_id))
+            .limit(1)
+            .first(&conn)?;
+
+        if !puzzle.is_visible_to(&conn, reqctx)? {
+            return Ok(None);
+        }
+
+        Ok(Some(puzzle))
+    }
+}
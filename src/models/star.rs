@@ -1,10 +1,11 @@
-use async_graphql::{self, Context, InputObject, Object};
-use diesel::sql_types::Bool;
+use async_graphql::{self, Context, Enum, InputObject, Object};
+use diesel::sql_types::{Bool, Int2, Int4, Nullable};
 use diesel::{prelude::*, query_dsl::QueryDsl};
 
-use crate::context::GlobalCtx;
+use crate::context::{GlobalCtx, RequestCtx};
 use crate::schema::star;
 
+use super::aggregate::*;
 use super::generics::*;
 use super::{Puzzle, User};
 
@@ -129,11 +130,15 @@ impl Star {
         use crate::schema::puzzle;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let puzzle = puzzle::table
+        let puzzle: Puzzle = puzzle::table
             .filter(puzzle::id.eq(self.puzzle_id))
             .limit(1)
             .first(&conn)?;
+        if !puzzle.is_visible_to(&conn, reqctx)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
 
         Ok(puzzle)
     }
@@ -151,3 +156,83 @@ impl Star {
         Ok(user)
     }
 }
+
+/// Available group-by columns for star aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StarAggregateGroupBy {
+    UserId,
+    PuzzleId,
+    Value,
+}
+
+impl AggregateGroupBy for StarAggregateGroupBy {
+    fn column(self) -> usize {
+        match self {
+            StarAggregateGroupBy::UserId => 0,
+            StarAggregateGroupBy::PuzzleId => 1,
+            StarAggregateGroupBy::Value => 2,
+        }
+    }
+
+    fn expression(self) -> &'static str {
+        match self {
+            StarAggregateGroupBy::UserId => "t.user_id",
+            StarAggregateGroupBy::PuzzleId => "t.puzzle_id",
+            StarAggregateGroupBy::Value => "t.value",
+        }
+    }
+}
+
+/// Available fields to apply sum/avg/min/max in star aggregate query
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StarAggregateField {
+    Value,
+}
+
+impl AggregateField for StarAggregateField {
+    fn expression(self) -> &'static str {
+        match self {
+            StarAggregateField::Value => "t.value",
+        }
+    }
+}
+
+/// A row of star aggregate query.
+///
+/// Group columns not grouped by are always `null`.
+#[derive(Queryable, Clone, Debug)]
+pub struct StarAggregate {
+    pub user_id: Option<ID>,
+    pub puzzle_id: Option<ID>,
+    pub value: Option<i16>,
+    pub values: AggregateValues,
+}
+
+impl Aggregate for StarAggregate {
+    type SqlType = (
+        Nullable<Int4>,
+        Nullable<Int4>,
+        Nullable<Int2>,
+        AggregateValuesSqlType,
+    );
+    type GroupBy = StarAggregateGroupBy;
+    type Field = StarAggregateField;
+
+    const GROUP_COLUMNS: &'static [&'static str] = &["NULL::int4", "NULL::int4", "NULL::int2"];
+}
+
+#[Object]
+impl StarAggregate {
+    async fn user_id(&self) -> Option<ID> {
+        self.user_id
+    }
+    async fn puzzle_id(&self) -> Option<ID> {
+        self.puzzle_id
+    }
+    async fn value(&self) -> Option<i16> {
+        self.value
+    }
+    async fn values(&self) -> &AggregateValues {
+        &self.values
+    }
+}
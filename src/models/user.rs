@@ -355,9 +355,17 @@ impl User {
         use crate::schema::puzzle::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let result = puzzle
-            .filter(user_id.eq(self.id))
+        // Drafts and masked anonymous puzzles are not counted, as `puzzles` does
+        let mut query = puzzle.filter(user_id.eq(self.id)).into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        if let Some(visible) = Puzzle::author_visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let result = query
             .count()
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
@@ -369,10 +377,19 @@ impl User {
         use crate::schema::puzzle::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let result = puzzle
+        let mut query = puzzle
             .filter(user_id.eq(self.id))
             .filter(not(yami.eq(0)))
+            .into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        if let Some(visible) = Puzzle::author_visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let result = query
             .count()
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
@@ -387,9 +404,16 @@ impl User {
         use crate::schema::puzzle::dsl::*;
 
         let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+        let reqctx = ctx.data::<RequestCtx>()?;
 
-        let result = puzzle
-            .filter(user_id.eq(self.id))
+        let mut query = puzzle.filter(user_id.eq(self.id)).into_boxed();
+        if let Some(visible) = Puzzle::visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        if let Some(visible) = Puzzle::author_visible_expression(reqctx) {
+            query = query.filter(visible);
+        }
+        let result = query
             .select(max(created))
             .get_result(&conn)
             .map_err(|err| async_graphql::Error::from(err))?;
@@ -0,0 +1,301 @@
+use async_graphql::{self, Context, Enum, InputObject, Object};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    expression::{helper_types::AsExprOf, AsExpression},
+    prelude::*,
+    query_dsl::QueryDsl,
+    serialize::{self, Output, ToSql},
+    sql_types::{Bool, Integer},
+};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::broker::BrokerMessage;
+use crate::context::GlobalCtx;
+use crate::schema::{webhook, webhook_delivery};
+
+use super::*;
+
+/// Site event webhooks can subscribe to
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    /// Messages of `PuzzleSub`
+    Puzzle = 0,
+    /// Messages of `PuzzleLogSub`
+    PuzzleLog = 1,
+    /// Messages of `ChatmessageSub`
+    Chatmessage = 2,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::Puzzle,
+        WebhookEvent::PuzzleLog,
+        WebhookEvent::Chatmessage,
+    ];
+
+    /// Broker topic of the event
+    pub fn topic(self) -> &'static str {
+        match self {
+            WebhookEvent::Puzzle => puzzle::PuzzleSub::TOPIC,
+            WebhookEvent::PuzzleLog => puzzle_log::PuzzleLogSub::TOPIC,
+            WebhookEvent::Chatmessage => chatmessage::ChatmessageSub::TOPIC,
+        }
+    }
+}
+
+impl<DB> ToSql<Integer, DB> for WebhookEvent
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for WebhookEvent {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for WebhookEvent
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(WebhookEvent::Puzzle),
+            1 => Ok(WebhookEvent::PuzzleLog),
+            2 => Ok(WebhookEvent::Chatmessage),
+            v => Err(format!("Invalid value `{}` for webhook event", &v).into()),
+        }
+    }
+}
+
+/// Status of a webhook delivery
+#[derive(Enum, Eq, PartialEq, Clone, Copy, Debug, FromSqlRow, Serialize, Deserialize)]
+pub enum WebhookDeliveryStatus {
+    /// Waiting for the next attempt
+    Pending = 0,
+    Delivered = 1,
+    /// All attempts failed. The delivery is kept until it is redelivered.
+    Dead = 2,
+}
+
+#[derive(InputObject, Eq, PartialEq, Clone)]
+pub struct WebhookDeliveryStatusFiltering {
+    pub eq: Option<WebhookDeliveryStatus>,
+    pub ne: Option<WebhookDeliveryStatus>,
+    pub eq_any: Option<Vec<WebhookDeliveryStatus>>,
+    pub ne_all: Option<Vec<WebhookDeliveryStatus>>,
+}
+
+impl<DB> ToSql<Integer, DB> for WebhookDeliveryStatus
+where
+    DB: Backend,
+    i32: ToSql<Integer, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        (*self as i32).to_sql(out)
+    }
+}
+
+impl AsExpression<Integer> for WebhookDeliveryStatus {
+    type Expression = AsExprOf<i32, Integer>;
+
+    fn as_expression(self) -> Self::Expression {
+        <i32 as AsExpression<Integer>>::as_expression(self as i32)
+    }
+}
+
+impl<DB> FromSql<Integer, DB> for WebhookDeliveryStatus
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(WebhookDeliveryStatus::Pending),
+            1 => Ok(WebhookDeliveryStatus::Delivered),
+            2 => Ok(WebhookDeliveryStatus::Dead),
+            v => Err(format!("Invalid value `{}` for webhook delivery status", &v).into()),
+        }
+    }
+}
+
+/// Object for webhook table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "webhook"]
+pub struct Webhook {
+    pub id: ID,
+    pub url: String,
+    /// Key signing the payloads with HMAC-SHA256
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    pub created: Timestamptz,
+}
+
+#[Object]
+impl Webhook {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn url(&self) -> &str {
+        &self.url
+    }
+    async fn secret(&self) -> &str {
+        &self.secret
+    }
+    async fn events(&self) -> &[WebhookEvent] {
+        &self.events
+    }
+    async fn enabled(&self) -> bool {
+        self.enabled
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+}
+
+/// Available orders for webhook_delivery query
+#[derive(InputObject, Clone)]
+pub struct WebhookDeliveryOrder {
+    id: Option<Ordering>,
+    created: Option<Ordering>,
+    next_attempt: Option<Ordering>,
+}
+
+/// Helper object to apply the order to the query
+pub struct WebhookDeliveryOrders(Vec<WebhookDeliveryOrder>);
+
+impl Default for WebhookDeliveryOrders {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl WebhookDeliveryOrders {
+    pub fn new(orders: Vec<WebhookDeliveryOrder>) -> Self {
+        Self(orders)
+    }
+
+    pub fn apply_order<'a>(
+        self,
+        query_dsl: crate::schema::webhook_delivery::BoxedQuery<'a, DB>,
+    ) -> crate::schema::webhook_delivery::BoxedQuery<'a, DB> {
+        use crate::schema::webhook_delivery::dsl::*;
+
+        let mut query = query_dsl;
+
+        for obj in self.0 {
+            gen_order!(obj, id, query);
+            gen_order!(obj, created, query);
+            gen_order!(obj, next_attempt, query);
+        }
+
+        query
+    }
+}
+
+/// Available filters for webhook_delivery query
+#[derive(InputObject, Clone, Default)]
+pub struct WebhookDeliveryFilter {
+    pub id: Option<I32Filtering>,
+    pub webhook_id: Option<I32Filtering>,
+    pub status: Option<WebhookDeliveryStatusFiltering>,
+    pub created: Option<TimestamptzFiltering>,
+}
+
+impl CindyFilter<webhook_delivery::table, DB> for WebhookDeliveryFilter {
+    fn as_expression(
+        self,
+    ) -> Option<Box<dyn BoxableExpression<webhook_delivery::table, DB, SqlType = Bool> + Send>>
+    {
+        use crate::schema::webhook_delivery::dsl::*;
+
+        let mut filter: Option<
+            Box<dyn BoxableExpression<webhook_delivery, DB, SqlType = Bool> + Send>,
+        > = None;
+        let WebhookDeliveryFilter {
+            id: obj_id,
+            webhook_id: obj_webhook_id,
+            status: obj_status,
+            created: obj_created,
+        } = self;
+        gen_number_filter!(obj_id: I32Filtering, id, filter);
+        gen_number_filter!(obj_webhook_id: I32Filtering, webhook_id, filter);
+        gen_enum_filter!(obj_status: WebhookDeliveryStatusFiltering, status, filter);
+        gen_number_filter!(obj_created: TimestamptzFiltering, created, filter);
+
+        filter
+    }
+}
+
+/// Object for webhook_delivery table
+#[derive(Queryable, Identifiable, Clone, Debug)]
+#[table_name = "webhook_delivery"]
+pub struct WebhookDelivery {
+    pub id: ID,
+    pub webhook_id: ID,
+    pub event: WebhookEvent,
+    /// The JSON body posted
+    pub payload: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt: Timestamptz,
+    pub last_error: Option<String>,
+    pub created: Timestamptz,
+    pub delivered: Option<Timestamptz>,
+}
+
+#[Object]
+impl WebhookDelivery {
+    async fn id(&self) -> ID {
+        self.id
+    }
+    async fn webhook_id(&self) -> ID {
+        self.webhook_id
+    }
+    async fn event(&self) -> WebhookEvent {
+        self.event
+    }
+    async fn payload(&self) -> &str {
+        &self.payload
+    }
+    async fn status(&self) -> WebhookDeliveryStatus {
+        self.status
+    }
+    async fn attempts(&self) -> i32 {
+        self.attempts
+    }
+    async fn next_attempt(&self) -> Timestamptz {
+        self.next_attempt
+    }
+    async fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    async fn created(&self) -> Timestamptz {
+        self.created
+    }
+    async fn delivered(&self) -> Option<Timestamptz> {
+        self.delivered
+    }
+
+    async fn webhook(&self, ctx: &Context<'_>) -> async_graphql::Result<Webhook> {
+        let conn = ctx.data::<GlobalCtx>()?.get_conn()?;
+
+        let webhook = webhook::table
+            .filter(webhook::id.eq(self.webhook_id))
+            .limit(1)
+            .first(&conn)?;
+
+        Ok(webhook)
+    }
+}
@@ -0,0 +1,266 @@
+//! Automatic persisted queries (APQ) and operation allowlist.
+//!
+//! Clients may send `extensions.persistedQuery.sha256Hash` instead of the full query
+//! text. Hashes are looked up from the manifest loaded at startup first, then from
+//! the runtime cache, which is filled when a client registers a query by sending
+//! both the hash and the query text.
+//!
+//! With `PERSISTED_QUERY_ALLOWLIST` enabled, only operations listed in the manifest
+//! are executed for non-admin roles.
+//!
+//! Both are applied by the [`PersistedQuery`] schema extension, so that they cover the
+//! operations sent over websockets as well as http.
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+};
+use async_graphql::{Request, ServerError, ServerResult, Value};
+use ring::digest;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::auth::Role;
+use crate::context::RequestCtx;
+
+const DEFAULT_CACHE_SIZE: usize = 1024;
+
+lazy_static! {
+    /// Operations registered from the manifest file, keyed by sha256 hash.
+    static ref MANIFEST: HashMap<String, String> = load_manifest();
+    /// Operations registered by clients at runtime.
+    static ref CACHE: Mutex<QueryCache> = Mutex::new(QueryCache::new(
+        dotenv::var("PERSISTED_QUERY_CACHE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_CACHE_SIZE)
+    ));
+    /// Whether only operations in the manifest are allowed for non-admin roles.
+    static ref ALLOWLIST_MODE: bool = dotenv::var("PERSISTED_QUERY_ALLOWLIST")
+        .map(|s| s == "true" || s == "1")
+        .unwrap_or(false);
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PersistedQueryError {
+    /// The hash is neither in the manifest nor in the cache.
+    NotFound,
+    /// The hash sent by the client does not match the query text.
+    HashMismatch,
+    /// The operation is not in the allowlist.
+    NotAllowed,
+    /// The `persistedQuery` extension is malformed.
+    Invalid,
+}
+
+impl fmt::Display for PersistedQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The first message is what Apollo clients expect to trigger a registration.
+        match self {
+            PersistedQueryError::NotFound => write!(f, "PersistedQueryNotFound"),
+            PersistedQueryError::HashMismatch => {
+                write!(f, "provided sha256Hash does not match the query")
+            }
+            PersistedQueryError::NotAllowed => write!(f, "Forbidden: Operation not allowed"),
+            PersistedQueryError::Invalid => write!(f, "Invalid persistedQuery extension"),
+        }
+    }
+}
+
+/// A bounded cache of queries that evicts the least recently used entry.
+struct QueryCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (String, u64)>,
+}
+
+impl QueryCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, hash: &str) -> Option<String> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(hash).map(|(query, used)| {
+            *used = tick;
+            query.clone()
+        })
+    }
+
+    fn insert(&mut self, hash: String, query: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if !self.entries.contains_key(&hash) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.to_owned());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(hash, (query, self.tick));
+    }
+}
+
+/// Hex-encoded sha256 hash of the query.
+pub fn hash_query(query: &str) -> String {
+    digest::digest(&digest::SHA256, query.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Loads the manifest file given by `PERSISTED_QUERY_MANIFEST`.
+///
+/// The manifest is either a JSON object mapping hashes to queries, or a JSON array
+/// of queries. Hashes are always recomputed from the query text.
+fn load_manifest() -> HashMap<String, String> {
+    let path = match dotenv::var("PERSISTED_QUERY_MANIFEST") {
+        Ok(path) => path,
+        Err(_) => return HashMap::new(),
+    };
+    let content = std::fs::read_to_string(&path).expect("Error reading PERSISTED_QUERY_MANIFEST");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&content).expect("Error parsing PERSISTED_QUERY_MANIFEST");
+
+    let queries: Vec<(Option<String>, String)> = match manifest {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter_map(|(hash, query)| query.as_str().map(|q| (Some(hash), q.to_owned())))
+            .collect(),
+        serde_json::Value::Array(list) => list
+            .into_iter()
+            .filter_map(|query| query.as_str().map(|q| (None, q.to_owned())))
+            .collect(),
+        _ => panic!("PERSISTED_QUERY_MANIFEST should be a JSON object or array"),
+    };
+
+    queries
+        .into_iter()
+        .map(|(given_hash, query)| {
+            let hash = hash_query(&query);
+            if let Some(given_hash) = given_hash {
+                if given_hash != hash {
+                    warn!(
+                        "Persisted query manifest: hash `{}` does not match its query, using `{}`",
+                        given_hash, hash
+                    );
+                }
+            }
+            (hash, query)
+        })
+        .collect()
+}
+
+/// Loads the manifest eagerly so that errors show up on startup.
+pub fn init() {
+    lazy_static::initialize(&MANIFEST);
+    lazy_static::initialize(&ALLOWLIST_MODE);
+    if *ALLOWLIST_MODE && MANIFEST.is_empty() {
+        warn!("PERSISTED_QUERY_ALLOWLIST is set but no operations are registered");
+    }
+    info!("Loaded {} persisted queries", MANIFEST.len());
+}
+
+/// Extracts `extensions.persistedQuery.sha256Hash` from the request.
+fn requested_hash(request: &Request) -> Result<Option<String>, PersistedQueryError> {
+    let persisted_query = match request.extensions.get("persistedQuery") {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    if let Value::Object(obj) = persisted_query {
+        if let Some(Value::String(hash)) = obj.get("sha256Hash") {
+            return Ok(Some(hash.to_lowercase()));
+        }
+    }
+    Err(PersistedQueryError::Invalid)
+}
+
+/// Resolves the query text of a persisted query and checks the allowlist.
+///
+/// On success, `request.query` is filled with the query to execute.
+pub fn resolve(request: &mut Request, role: Role) -> Result<(), PersistedQueryError> {
+    let strict = *ALLOWLIST_MODE && role != Role::Admin;
+    let hash = requested_hash(request)?;
+
+    match hash {
+        Some(hash) if request.query.is_empty() => {
+            if let Some(query) = MANIFEST.get(&hash) {
+                request.query = query.clone();
+            } else if strict {
+                return Err(PersistedQueryError::NotAllowed);
+            } else if let Some(query) = CACHE.lock().unwrap().get(&hash) {
+                request.query = query;
+            } else {
+                return Err(PersistedQueryError::NotFound);
+            }
+        }
+        Some(hash) => {
+            if hash_query(&request.query) != hash {
+                return Err(PersistedQueryError::HashMismatch);
+            }
+            if !MANIFEST.contains_key(&hash) {
+                if strict {
+                    return Err(PersistedQueryError::NotAllowed);
+                }
+                CACHE.lock().unwrap().insert(hash, request.query.clone());
+            }
+        }
+        None => {
+            if strict && !MANIFEST.contains_key(&hash_query(&request.query)) {
+                return Err(PersistedQueryError::NotAllowed);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Schema extension resolving persisted queries and checking the allowlist for every
+/// operation before it is parsed.
+pub struct PersistedQuery;
+
+impl ExtensionFactory for PersistedQuery {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueryExtension)
+    }
+}
+
+struct PersistedQueryExtension;
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueryExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        // The request context is in the request data for http, and in the session data
+        // for websockets
+        let role = ctx
+            .data_opt::<RequestCtx>()
+            .map(|reqctx| reqctx.get_role())
+            .unwrap_or(Role::Guest);
+
+        if let Err(error) = resolve(&mut request, role) {
+            debug!(
+                "persisted query: {}: {}",
+                request.operation_name.as_deref().unwrap_or("_"),
+                &error
+            );
+            return Err(ServerError::new(error.to_string()));
+        }
+
+        next.run(ctx, request).await
+    }
+}
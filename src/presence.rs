@@ -0,0 +1,239 @@
+//! Presence of the users, tracked with the heartbeats sent by the clients.
+//!
+//! Heartbeats are relayed to all server instances with the broker, so that every
+//! instance keeps the same table of presence. A user is considered to have left a
+//! location when no heartbeat is received within `PRESENCE_TIMEOUT` seconds.
+//!
+//! Typing indicators are ephemeral events, rate-limited per user and location and never
+//! persisted in the event log. The rate limit is kept by each server instance, so that a
+//! user sending events through several instances is limited on each of them.
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::broker::CindyBroker;
+use crate::models::presence::*;
+use crate::models::ID;
+
+const DEFAULT_TIMEOUT: i64 = 60;
+const DEFAULT_TYPING_TIMEOUT: i64 = 5;
+const DEFAULT_TYPING_INTERVAL: i64 = 2;
+/// Interval to look for the stale clients
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+lazy_static! {
+    /// Time of the last heartbeat of each user at each location
+    static ref PRESENCE: Mutex<HashMap<(ID, PresenceLocation), DateTime<Utc>>> =
+        Default::default();
+    static ref TIMEOUT: Duration = Duration::seconds(
+        dotenv::var("PRESENCE_TIMEOUT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|timeout| *timeout > 0)
+            .unwrap_or(DEFAULT_TIMEOUT)
+    );
+    /// Time of the last typing event of each user at each location, and whether they have
+    /// not stopped since
+    static ref TYPING: Mutex<HashMap<(ID, PresenceLocation), (DateTime<Utc>, bool)>> =
+        Default::default();
+    /// Duration the typing indicator is shown without further events
+    static ref TYPING_TIMEOUT: Duration = Duration::seconds(
+        dotenv::var("TYPING_TIMEOUT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|timeout| *timeout > 0)
+            .unwrap_or(DEFAULT_TYPING_TIMEOUT)
+    );
+    /// Minimum interval between the typing events of a user at a location
+    static ref TYPING_INTERVAL: Duration = Duration::seconds(
+        dotenv::var("TYPING_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_TYPING_INTERVAL)
+    );
+}
+
+/// Spawns the tasks receiving heartbeats and sweeping stale clients.
+pub fn init() {
+    let mut heartbeats = CindyBroker::<PresenceHeartbeat>::subscribe();
+    tokio::spawn(async move {
+        while let Some(event) = heartbeats.next().await {
+            receive(event.msg);
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            sweep();
+        }
+    });
+}
+
+/// Records a heartbeat of the user at the location.
+pub fn heartbeat(user_id: ID, location: PresenceLocation) {
+    CindyBroker::publish(PresenceHeartbeat {
+        user_id,
+        location,
+        leave: false,
+    });
+}
+
+/// Removes the user from the location without waiting for the timeout.
+pub fn leave(user_id: ID, location: PresenceLocation) {
+    CindyBroker::publish(PresenceHeartbeat {
+        user_id,
+        location,
+        leave: true,
+    });
+}
+
+fn receive(heartbeat: PresenceHeartbeat) {
+    let key = (heartbeat.user_id, heartbeat.location);
+    let op = {
+        let mut presence = PRESENCE.lock().unwrap();
+        if heartbeat.leave {
+            presence.remove(&key).map(|_| PresenceOp::Left)
+        } else {
+            presence
+                .insert(key, Utc::now())
+                .map_or(Some(PresenceOp::Joined), |_| None)
+        }
+    };
+
+    if let Some(op) = op {
+        publish(op, heartbeat.user_id, heartbeat.location);
+    }
+}
+
+fn sweep() {
+    let typing_deadline = Utc::now() - *TYPING_TIMEOUT;
+    TYPING
+        .lock()
+        .unwrap()
+        .retain(|_, (last_typed, _)| *last_typed >= typing_deadline);
+
+    let deadline = Utc::now() - *TIMEOUT;
+    let stale: Vec<(ID, PresenceLocation)> = {
+        let mut presence = PRESENCE.lock().unwrap();
+        let stale: Vec<_> = presence
+            .iter()
+            .filter(|(_, last_seen)| **last_seen < deadline)
+            .map(|(key, _)| *key)
+            .collect();
+        stale.iter().for_each(|key| {
+            presence.remove(key);
+        });
+        stale
+    };
+
+    for (user_id, location) in stale {
+        publish(PresenceOp::Left, user_id, location);
+    }
+}
+
+fn publish(op: PresenceOp, user_id: ID, location: PresenceLocation) {
+    CindyBroker::publish_to(
+        location.key(),
+        PresenceSub {
+            op,
+            user_id,
+            location,
+        },
+    );
+}
+
+/// Users present at the location
+pub fn users_at(location: PresenceLocation) -> Vec<ID> {
+    let deadline = Utc::now() - *TIMEOUT;
+    let presence = PRESENCE.lock().unwrap();
+    let mut user_ids: Vec<ID> = presence
+        .iter()
+        .filter(|((_, loc), last_seen)| *loc == location && **last_seen >= deadline)
+        .map(|((user_id, _), _)| *user_id)
+        .collect();
+    user_ids.sort();
+    user_ids
+}
+
+/// Users present at any location
+pub fn online_users() -> Vec<ID> {
+    let deadline = Utc::now() - *TIMEOUT;
+    let presence = PRESENCE.lock().unwrap();
+    let user_ids: HashSet<ID> = presence
+        .iter()
+        .filter(|(_, last_seen)| **last_seen >= deadline)
+        .map(|((user_id, _), _)| *user_id)
+        .collect();
+    let mut user_ids: Vec<ID> = user_ids.into_iter().collect();
+    user_ids.sort();
+    user_ids
+}
+
+/// Publishes a typing event of the user at the location.
+///
+/// Returns `false` if the event is dropped by the rate limit. Stop events are only
+/// published once after a typing event, which keeps its time so that typing again is
+/// still limited.
+pub fn typing(user_id: ID, location: PresenceLocation, stopped: bool) -> bool {
+    let now = Utc::now();
+    {
+        let mut typing = TYPING.lock().unwrap();
+        let entry = typing.get_mut(&(user_id, location));
+        if stopped {
+            match entry {
+                Some((_, is_typing)) if *is_typing => *is_typing = false,
+                _ => return false,
+            }
+        } else {
+            if let Some((last_typed, _)) = entry {
+                if now - *last_typed < *TYPING_INTERVAL {
+                    return false;
+                }
+            }
+            typing.insert((user_id, location), (now, true));
+        }
+    }
+
+    CindyBroker::publish_to(
+        TypingSub::key(location),
+        TypingSub {
+            user_id,
+            location,
+            stopped,
+            expires: now + *TYPING_TIMEOUT,
+        },
+    );
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing_events_are_rate_limited() {
+        let location = PresenceLocation::Chatroom(1);
+        let user_id = 1;
+
+        assert!(typing(user_id, location, false));
+        assert!(!typing(user_id, location, false));
+        assert!(typing(user_id, location, true));
+        // Stopping again or typing right after stopping is still limited
+        assert!(!typing(user_id, location, true));
+        assert!(!typing(user_id, location, false));
+        assert!(!typing(user_id, location, true));
+
+        // Other users and locations are limited separately
+        assert!(typing(user_id + 1, location, false));
+        assert!(typing(user_id, PresenceLocation::Chatroom(2), false));
+    }
+
+    #[test]
+    fn stop_events_need_a_typing_event() {
+        assert!(!typing(3, PresenceLocation::Chatroom(3), true));
+    }
+}
@@ -0,0 +1,169 @@
+//! Jobs run periodically in the background.
+//!
+//! Every job only acts on the rows it has not handled yet, so that it is safe to run it
+//! again after a restart, or on multiple server instances at once.
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+use crate::broker::CindyBroker;
+use crate::context::GlobalCtx;
+use crate::gql_schema::{notify, publish_draft, CreateNotificationData};
+use crate::models::notification::NotificationKind;
+use crate::models::puzzle::PuzzleSub;
+use crate::models::*;
+use crate::schema::{notification, puzzle, schedule};
+
+const DEFAULT_INTERVAL: u64 = 600;
+const DEFAULT_DAZE_WARNING_DAYS: i64 = 1;
+/// Interval to look for the scheduled puzzles due, shorter to publish them on time
+const PUBLISH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+lazy_static! {
+    /// Interval between the runs of the jobs
+    static ref INTERVAL: std::time::Duration = std::time::Duration::from_secs(
+        dotenv::var("SCHEDULER_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|interval| *interval > 0)
+            .unwrap_or(DEFAULT_INTERVAL)
+    );
+    /// Days before dazing to warn the author, or 0 to disable the warnings
+    static ref DAZE_WARNING_DAYS: i64 = dotenv::var("DAZE_WARNING_DAYS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_DAZE_WARNING_DAYS);
+}
+
+/// Spawns the task running the jobs.
+pub fn init(ctx: GlobalCtx) {
+    let publish_ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*INTERVAL);
+        loop {
+            interval.tick().await;
+            run(&ctx, "warn dazing puzzles", warn_dazing_puzzles).await;
+            run(&ctx, "daze puzzles", daze_puzzles).await;
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+        loop {
+            interval.tick().await;
+            run(
+                &publish_ctx,
+                "publish scheduled puzzles",
+                publish_scheduled_puzzles,
+            )
+            .await;
+        }
+    });
+}
+
+/// Runs the job in the blocking threads, as the queries are blocking.
+async fn run(ctx: &GlobalCtx, name: &str, job: fn(&GlobalCtx) -> anyhow::Result<()>) {
+    let ctx = ctx.clone();
+    match tokio::task::spawn_blocking(move || job(&ctx)).await {
+        Ok(Err(err)) => error!("Scheduler: unable to {}: {}", name, err),
+        Err(err) => error!("Scheduler: unable to {}: {}", name, err),
+        Ok(Ok(())) => {}
+    }
+}
+
+/// Publishes the draft puzzles scheduled until now.
+pub fn publish_scheduled_puzzles(ctx: &GlobalCtx) -> anyhow::Result<()> {
+    let conn = ctx.get_conn()?;
+
+    let puzzle_ids: Vec<Option<ID>> = schedule::table
+        .filter(schedule::scheduled.le(Utc::now()))
+        .filter(schedule::puzzle_id.is_not_null())
+        .select(schedule::puzzle_id)
+        .load(&conn)?;
+
+    // Puzzles published in the meantime, e.g. by another instance, are skipped
+    for puzzle_id in puzzle_ids.into_iter().flatten() {
+        match publish_draft(&conn, puzzle_id) {
+            Ok(Some(_)) => info!("Scheduler: published puzzle {}", puzzle_id),
+            Ok(None) => {}
+            Err(err) => error!("Scheduler: unable to publish puzzle {}: {}", puzzle_id, err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Marks the undergoing puzzles past their `dazed_on` as dazed.
+pub fn daze_puzzles(ctx: &GlobalCtx) -> anyhow::Result<()> {
+    let conn = ctx.get_conn()?;
+    let today = Utc::now().date().naive_utc();
+
+    let puzzle_insts: Vec<Puzzle> = puzzle::table
+        .filter(puzzle::status.eq(Status::Undergoing))
+        .filter(puzzle::dazed_on.le(today))
+        .load(&conn)?;
+    if puzzle_insts.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<ID> = puzzle_insts.iter().map(|puzzle| puzzle.id).collect();
+
+    // Puzzles updated in the meantime, e.g. by another instance, are skipped
+    let puzzles: Vec<Puzzle> = diesel::update(puzzle::table)
+        .filter(puzzle::id.eq_any(ids))
+        .filter(puzzle::status.eq(Status::Undergoing))
+        .set(puzzle::status.eq(Status::Dazed))
+        .get_results(&conn)?;
+    info!("Scheduler: dazed {} puzzles", puzzles.len());
+
+    let mut puzzle_insts: HashMap<ID, Puzzle> = puzzle_insts
+        .into_iter()
+        .map(|puzzle| (puzzle.id, puzzle))
+        .collect();
+    for puzzle in puzzles {
+        if let Some(puzzle_inst) = puzzle_insts.remove(&puzzle.id) {
+            CindyBroker::publish(PuzzleSub::Updated(puzzle_inst, puzzle));
+        }
+    }
+
+    Ok(())
+}
+
+/// Notifies the authors of the undergoing puzzles to be dazed within
+/// `DAZE_WARNING_DAYS` days, once for each puzzle.
+pub fn warn_dazing_puzzles(ctx: &GlobalCtx) -> anyhow::Result<()> {
+    if *DAZE_WARNING_DAYS <= 0 {
+        return Ok(());
+    }
+
+    let conn = ctx.get_conn()?;
+    let today = Utc::now().date().naive_utc();
+
+    let puzzles: Vec<Puzzle> = puzzle::table
+        .filter(puzzle::status.eq(Status::Undergoing))
+        .filter(puzzle::dazed_on.gt(today))
+        .filter(puzzle::dazed_on.le(today + Duration::days(*DAZE_WARNING_DAYS)))
+        .load(&conn)?;
+    if puzzles.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<ID> = puzzles.iter().map(|puzzle| puzzle.id).collect();
+
+    let warned: Vec<Option<ID>> = notification::table
+        .filter(notification::kind.eq(NotificationKind::DazeWarning))
+        .filter(notification::puzzle_id.eq_any(ids))
+        .select(notification::puzzle_id)
+        .load(&conn)?;
+    let puzzles = puzzles
+        .into_iter()
+        .filter(|puzzle| !warned.contains(&Some(puzzle.id)));
+
+    for puzzle in puzzles {
+        notify(
+            &conn,
+            CreateNotificationData::new(puzzle.user_id, NotificationKind::DazeWarning)
+                .puzzle_id(puzzle.id),
+        );
+    }
+
+    Ok(())
+}
@@ -1,3 +1,19 @@
+table! {
+    attachment (id) {
+        id -> Int4,
+        image_id -> Int4,
+        target -> Int4,
+        puzzle_id -> Nullable<Int4>,
+        hint_id -> Nullable<Int4>,
+        dialogue_id -> Nullable<Int4>,
+        comment_id -> Nullable<Int4>,
+        position -> Int4,
+        alt_text -> Text,
+        user_id -> Nullable<Int4>,
+        created -> Timestamptz,
+    }
+}
+
 table! {
     auth_group (id) {
         id -> Int4,
@@ -55,6 +71,25 @@ table! {
     }
 }
 
+table! {
+    broker_event (id) {
+        id -> Int8,
+        topic -> Text,
+        target -> Text,
+        prefix -> Bool,
+        payload -> Text,
+        created -> Timestamptz,
+    }
+}
+
+table! {
+    broker_message (id) {
+        id -> Int4,
+        payload -> Text,
+        created -> Timestamptz,
+    }
+}
+
 table! {
     chatmessage (id) {
         id -> Int4,
@@ -78,6 +113,36 @@ table! {
     }
 }
 
+table! {
+    collection (id) {
+        id -> Int4,
+        user_id -> Int4,
+        title -> Text,
+        description -> Text,
+        public -> Bool,
+        created -> Timestamptz,
+        modified -> Timestamptz,
+    }
+}
+
+table! {
+    collection_editor (id) {
+        id -> Int4,
+        collection_id -> Int4,
+        user_id -> Int4,
+    }
+}
+
+table! {
+    collection_puzzle (id) {
+        id -> Int4,
+        collection_id -> Int4,
+        puzzle_id -> Int4,
+        position -> Int4,
+        created -> Timestamptz,
+    }
+}
+
 table! {
     comment (id) {
         id -> Int4,
@@ -229,6 +294,44 @@ table! {
     }
 }
 
+table! {
+    image (id) {
+        id -> Int4,
+        hash -> Text,
+        content_type -> Text,
+        size -> Int4,
+        user_id -> Nullable<Int4>,
+        created -> Timestamptz,
+    }
+}
+
+table! {
+    image_pending (image_id) {
+        image_id -> Int4,
+        data -> Bytea,
+    }
+}
+
+table! {
+    image_upload (image_id, user_id) {
+        image_id -> Int4,
+        user_id -> Int4,
+        created -> Timestamptz,
+    }
+}
+
+table! {
+    image_variant (id) {
+        id -> Int4,
+        image_id -> Int4,
+        size -> Int4,
+        format -> Int4,
+        width -> Int4,
+        height -> Int4,
+        byte_size -> Int4,
+    }
+}
+
 table! {
     license (id) {
         id -> Int4,
@@ -237,6 +340,41 @@ table! {
         description -> Text,
         url -> Nullable<Varchar>,
         contract -> Nullable<Text>,
+        no_derivatives -> Bool,
+        share_alike -> Bool,
+    }
+}
+
+table! {
+    notification (id) {
+        id -> Int4,
+        user_id -> Int4,
+        kind -> Int4,
+        actor_id -> Nullable<Int4>,
+        puzzle_id -> Nullable<Int4>,
+        target_id -> Nullable<Int4>,
+        read -> Bool,
+        created -> Timestamptz,
+        hidden -> Bool,
+    }
+}
+
+table! {
+    notification_profile (user_id) {
+        user_id -> Int4,
+        digest_frequency -> Int4,
+        digest_sent -> Nullable<Timestamptz>,
+        webhook_url -> Nullable<Text>,
+    }
+}
+
+table! {
+    notification_setting (id) {
+        id -> Int4,
+        user_id -> Int4,
+        kind -> Int4,
+        channel -> Int4,
+        enabled -> Bool,
     }
 }
 
@@ -257,7 +395,39 @@ table! {
         dazed_on -> Date,
         grotesque -> Bool,
         license_id -> Nullable<Int4>,
-        content_image -> Nullable<Bytea>,
+        solved_at -> Nullable<Timestamptz>,
+        content_image_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    puzzle_coauthor (id) {
+        id -> Int4,
+        puzzle_id -> Int4,
+        user_id -> Int4,
+        role -> Int4,
+        created -> Timestamptz,
+    }
+}
+
+table! {
+    puzzle_derivation (puzzle_id) {
+        puzzle_id -> Int4,
+        source_id -> Nullable<Int4>,
+        attribution -> Text,
+        created -> Timestamptz,
+    }
+}
+
+table! {
+    puzzle_revision (id) {
+        id -> Int4,
+        puzzle_id -> Int4,
+        user_id -> Nullable<Int4>,
+        content -> Text,
+        solution -> Text,
+        memo -> Text,
+        created -> Timestamptz,
     }
 }
 
@@ -303,6 +473,7 @@ table! {
         created -> Timestamptz,
         scheduled -> Timestamptz,
         user_id -> Int4,
+        puzzle_id -> Nullable<Int4>,
     }
 }
 
@@ -378,6 +549,38 @@ table! {
     }
 }
 
+table! {
+    webhook (id) {
+        id -> Int4,
+        url -> Text,
+        secret -> Text,
+        events -> Array<Int4>,
+        enabled -> Bool,
+        created -> Timestamptz,
+    }
+}
+
+table! {
+    webhook_delivery (id) {
+        id -> Int4,
+        webhook_id -> Int4,
+        event -> Int4,
+        payload -> Text,
+        status -> Int4,
+        attempts -> Int4,
+        next_attempt -> Timestamptz,
+        last_error -> Nullable<Text>,
+        created -> Timestamptz,
+        delivered -> Nullable<Timestamptz>,
+    }
+}
+
+joinable!(attachment -> comment (comment_id));
+joinable!(attachment -> dialogue (dialogue_id));
+joinable!(attachment -> hint (hint_id));
+joinable!(attachment -> image (image_id));
+joinable!(attachment -> puzzle (puzzle_id));
+joinable!(attachment -> user (user_id));
 joinable!(auth_group_permissions -> auth_group (group_id));
 joinable!(auth_group_permissions -> auth_permission (permission_id));
 joinable!(auth_permission -> django_content_type (content_type_id));
@@ -387,6 +590,11 @@ joinable!(bookmark -> user (user_id));
 joinable!(chatmessage -> chatroom (chatroom_id));
 joinable!(chatmessage -> user (user_id));
 joinable!(chatroom -> user (user_id));
+joinable!(collection -> user (user_id));
+joinable!(collection_editor -> collection (collection_id));
+joinable!(collection_editor -> user (user_id));
+joinable!(collection_puzzle -> collection (collection_id));
+joinable!(collection_puzzle -> puzzle (puzzle_id));
 joinable!(comment -> puzzle (puzzle_id));
 joinable!(comment -> user (user_id));
 joinable!(dialogue -> puzzle (puzzle_id));
@@ -403,14 +611,29 @@ joinable!(hasura_direct_message_group_trigger -> user (user_id));
 joinable!(hasura_user_ranking_trigger -> user (user_id));
 joinable!(hint -> puzzle (puzzle_id));
 joinable!(hint -> user (receiver_id));
+joinable!(image -> user (user_id));
+joinable!(image_pending -> image (image_id));
+joinable!(image_upload -> image (image_id));
+joinable!(image_upload -> user (user_id));
+joinable!(image_variant -> image (image_id));
+joinable!(notification -> puzzle (puzzle_id));
+joinable!(notification_profile -> user (user_id));
+joinable!(notification_setting -> user (user_id));
+joinable!(puzzle -> image (content_image_id));
 joinable!(puzzle -> license (license_id));
 joinable!(puzzle -> user (user_id));
+joinable!(puzzle_coauthor -> puzzle (puzzle_id));
+joinable!(puzzle_coauthor -> user (user_id));
+joinable!(puzzle_derivation -> puzzle (puzzle_id));
+joinable!(puzzle_revision -> puzzle (puzzle_id));
+joinable!(puzzle_revision -> user (user_id));
 joinable!(puzzle_tag -> puzzle (puzzle_id));
 joinable!(puzzle_tag -> tag (tag_id));
 joinable!(puzzle_tag -> user (user_id));
 joinable!(replay -> puzzle (puzzle_id));
 joinable!(replay -> user (user_id));
 joinable!(replay_dialogue -> replay (replay_id));
+joinable!(schedule -> puzzle (puzzle_id));
 joinable!(schedule -> user (user_id));
 joinable!(star -> puzzle (puzzle_id));
 joinable!(star -> user (user_id));
@@ -420,16 +643,23 @@ joinable!(sui_hei_user_groups -> user (user_id));
 joinable!(sui_hei_user_user_permissions -> auth_permission (permission_id));
 joinable!(sui_hei_user_user_permissions -> user (user_id));
 joinable!(user_award -> award (award_id));
+joinable!(webhook_delivery -> webhook (webhook_id));
 
 allow_tables_to_appear_in_same_query!(
+    attachment,
     auth_group,
     auth_group_permissions,
     auth_permission,
     award,
     award_application,
     bookmark,
+    broker_event,
+    broker_message,
     chatmessage,
     chatroom,
+    collection,
+    collection_editor,
+    collection_puzzle,
     comment,
     dialogue,
     direct_message,
@@ -445,8 +675,18 @@ allow_tables_to_appear_in_same_query!(
     hasura_int_groupby_trigger,
     hasura_user_ranking_trigger,
     hint,
+    image,
+    image_pending,
+    image_upload,
+    image_variant,
     license,
+    notification,
+    notification_profile,
+    notification_setting,
     puzzle,
+    puzzle_coauthor,
+    puzzle_derivation,
+    puzzle_revision,
     puzzle_tag,
     replay,
     replay_dialogue,
@@ -458,4 +698,6 @@ allow_tables_to_appear_in_same_query!(
     tag,
     user,
     user_award,
+    webhook,
+    webhook_delivery,
 );
@@ -1,3 +1,8 @@
 SELECT genre, count(id) as puzzle_count FROM puzzle
 WHERE puzzle.user_id = $1
+  -- Drafts are not published yet
+  AND puzzle.status <> 5
+  -- Anonymous puzzles with the author masked, unless the requester is the
+  -- author or an admin
+  AND (NOT puzzle.anonymous OR (puzzle.status <> 0 AND $2) OR $3)
 GROUP BY genre
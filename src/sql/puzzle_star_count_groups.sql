@@ -3,6 +3,11 @@ FROM (
     SELECT puzzle.id, count(star.id) as star_count FROM puzzle
     INNER JOIN star ON star.puzzle_id = puzzle.id
     WHERE puzzle.user_id = $1
+      -- Drafts are not published yet
+      AND puzzle.status <> 5
+      -- Anonymous puzzles with the author masked, unless the requester is the
+      -- author or an admin
+      AND (NOT puzzle.anonymous OR (puzzle.status <> 0 AND $2) OR $3)
     GROUP BY puzzle.id
 ) as grp
 GROUP BY star_count
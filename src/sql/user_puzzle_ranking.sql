@@ -1,6 +1,15 @@
 SELECT "user".*, count(*) as value_count from "user"
-INNER JOIN puzzle ON "user".id = puzzle.user_id
-WHERE puzzle.created >= $1 AND puzzle.created < $2
+INNER JOIN (
+  SELECT puzzle.id, puzzle.user_id FROM puzzle
+  UNION
+  -- Co-authors are credited along with the author, but not the answerers
+  SELECT puzzle_coauthor.puzzle_id, puzzle_coauthor.user_id FROM puzzle_coauthor
+  WHERE puzzle_coauthor.role = 0
+) AS author ON "user".id = author.user_id
+INNER JOIN puzzle ON author.id = puzzle.id
+WHERE puzzle.solved_at >= $1 AND puzzle.solved_at < $2
+  -- Anonymous puzzles with the author masked
+  AND (NOT puzzle.anonymous OR (puzzle.status <> 0 AND $5))
 GROUP BY "user".id
 ORDER BY value_count DESC, "user".nickname ASC
 LIMIT $3
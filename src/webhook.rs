@@ -0,0 +1,580 @@
+//! Outgoing webhooks.
+//!
+//! Besides the notification webhooks of the users, admins can register webhooks receiving
+//! site events. Site event payloads are signed with the secret of the webhook, persisted
+//! as deliveries and retried with exponential backoff. Deliveries failing
+//! `WEBHOOK_MAX_ATTEMPTS` times are marked as dead until an admin redelivers them.
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel::prelude::*;
+use ring::hmac;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::broker::{self, EventId, Target};
+use crate::context::{GlobalCtx, RequestCtx};
+use crate::models::chatmessage::ChatmessageSub;
+use crate::models::puzzle::PuzzleSub;
+use crate::models::puzzle_log::PuzzleLogSub;
+use crate::models::webhook::*;
+use crate::models::*;
+use crate::schema::{chatroom, puzzle, webhook, webhook_delivery};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_ATTEMPTS: i32 = 5;
+const DEFAULT_RETRY_DELAY: i64 = 30;
+/// Interval to look for the deliveries to retry
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .expect("Unable to build the webhook client");
+    /// Number of attempts before a delivery is marked as dead
+    static ref MAX_ATTEMPTS: i32 = dotenv::var("WEBHOOK_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|attempts| *attempts > 0)
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    /// Delay before the first retry, doubled for each further one
+    static ref RETRY_DELAY: chrono::Duration = chrono::Duration::seconds(
+        dotenv::var("WEBHOOK_RETRY_DELAY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|delay| *delay > 0)
+            .unwrap_or(DEFAULT_RETRY_DELAY)
+    );
+    /// Duration a delivery being attempted is held from the other attempts
+    static ref LEASE: chrono::Duration =
+        chrono::Duration::from_std(TIMEOUT * 3).unwrap();
+    static ref CONTEXT: std::sync::RwLock<Option<GlobalCtx>> = std::sync::RwLock::new(None);
+}
+
+/// Whether the address is publicly routable, i.e. not a loopback, private, link-local,
+/// unique-local or otherwise reserved one.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // 0.0.0.0/8
+                || octets[0] == 0
+                // Shared address space, 100.64.0.0/10
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || segments[0] & 0xfe00 == 0xfc00
+                // Link local, fe80::/10
+                || segments[0] & 0xffc0 == 0xfe80
+                // Documentation, 2001:db8::/32
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+                // IPv4-mapped and IPv4-compatible addresses
+                || ip.to_ipv4().map_or(false, |ip| !is_public(IpAddr::V4(ip))))
+        }
+    }
+}
+
+/// Resolves the host of the webhook url of a user, asserting it is a http(s) url to a
+/// public address so that users cannot make the server post to its internal network.
+///
+/// Returns the host along with the address it resolves to.
+pub async fn resolve_public(url: &str) -> anyhow::Result<(String, SocketAddr)> {
+    let parsed = reqwest::Url::parse(url)?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(anyhow!("unsupported scheme `{}`", parsed.scheme()));
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| anyhow!("no host"))?
+        .to_string();
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("no port"))?;
+
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((literal, port)).await?.collect(),
+    };
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        return Err(anyhow!(
+            "{} resolves to a non-public address {}",
+            host,
+            addr.ip()
+        ));
+    }
+    let addr = addrs
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("{} does not resolve", host))?;
+
+    Ok((host, addr))
+}
+
+/// Posts the payload as json to the webhook url of a user in the background.
+///
+/// The url is resolved again before posting, and the request is pinned to the checked
+/// address without following redirects.
+pub fn post<T: Serialize>(url: String, payload: &T) {
+    let body = match serde_json::to_vec(payload) {
+        Ok(body) => body,
+        Err(err) => {
+            warn!("Webhook: unable to serialize the payload: {}", err);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let result = async {
+            let (host, addr) = resolve_public(&url).await?;
+            reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .resolve(&host, addr)
+                .build()?
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok::<_, anyhow::Error>(())
+        };
+        if let Err(err) = result.await {
+            warn!("Webhook: unable to post to {}: {}", url, err);
+        }
+    });
+}
+
+/// Runs the blocking database work of the background tasks on the blocking pool.
+async fn blocking<T, F>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+/// Listens to the site events and spawns the task retrying failed deliveries.
+pub fn init(ctx: GlobalCtx) {
+    *CONTEXT.write().unwrap() = Some(ctx.clone());
+
+    // Solutions of the undergoing puzzles are not exported
+    broker::listen::<PuzzleSub>(|target, event_id, payload| {
+        let payload = payload.clone();
+        enqueue(WebhookEvent::Puzzle, target, event_id, move |_| {
+            let msg = serde_json::from_value::<PuzzleSub>(payload)?.redacted();
+            Ok(Some(serde_json::to_value(&msg)?))
+        });
+    });
+    // Neither are the answers of the undergoing puzzles and the authors of the anonymous
+    // puzzles
+    broker::listen::<PuzzleLogSub>(|target, event_id, payload| {
+        let payload = payload.clone();
+        enqueue(WebhookEvent::PuzzleLog, target, event_id, move |conn| {
+            let msg = serde_json::from_value::<PuzzleLogSub>(payload)?;
+            let puzzle_id = match msg.puzzle_id() {
+                Some(puzzle_id) => puzzle_id,
+                None => return Ok(Some(serde_json::to_value(&msg)?)),
+            };
+            let puzzle: Option<Puzzle> = puzzle::table
+                .filter(puzzle::id.eq(puzzle_id))
+                .first(conn)
+                .optional()?;
+            let puzzle = match puzzle {
+                Some(puzzle) => puzzle,
+                None => return Ok(None),
+            };
+            let author_ids = if puzzle.author_visible_to(&RequestCtx::default()) {
+                Vec::new()
+            } else {
+                puzzle.author_ids(conn)?
+            };
+            let msg = msg.redacted(&puzzle, &author_ids);
+            Ok(Some(serde_json::to_value(&msg)?))
+        });
+    });
+    // Nor the messages of the private chatrooms
+    broker::listen::<ChatmessageSub>(|target, event_id, payload| {
+        let chatroom_id = match chatroom_id(target) {
+            Some(chatroom_id) => chatroom_id,
+            None => return,
+        };
+        let payload = payload.clone();
+        enqueue(WebhookEvent::Chatmessage, target, event_id, move |conn| {
+            let private: bool = chatroom::table
+                .filter(chatroom::id.eq(chatroom_id))
+                .select(chatroom::private)
+                .first(conn)?;
+            Ok(if private { None } else { Some(payload) })
+        });
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let claimed = blocking({
+                let ctx = ctx.clone();
+                move || claim_due(&ctx)
+            })
+            .await;
+            match claimed {
+                Ok(deliveries) => {
+                    for (delivery, webhook) in deliveries {
+                        attempt(ctx.clone(), delivery, webhook).await;
+                    }
+                }
+                Err(err) => error!("Webhook: {}", err),
+            }
+        }
+    });
+}
+
+/// Body posted for a site event
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SiteEventBody<'a> {
+    event: WebhookEvent,
+    /// Subscription key the event is published to, e.g. `chatroom<1>`
+    key: Option<&'a str>,
+    event_id: Option<EventId>,
+    timestamp: Timestamptz,
+    data: &'a serde_json::Value,
+}
+
+/// Id of the chatroom the chatmessage event is published to.
+fn chatroom_id(target: &Target) -> Option<ID> {
+    match target {
+        Target::Key(key) => key
+            .strip_prefix("chatroom<")?
+            .strip_suffix('>')?
+            .parse()
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Creates the deliveries of the event for the webhooks subscribing to it, and attempts
+/// them in the background.
+///
+/// `prepare` returns the data of the event to deliver, or `None` if the event is not
+/// exported. It runs on the blocking pool along with the other database work.
+fn enqueue<F>(event: WebhookEvent, target: &Target, event_id: Option<EventId>, prepare: F)
+where
+    F: FnOnce(&PgConnection) -> anyhow::Result<Option<serde_json::Value>> + Send + 'static,
+{
+    let ctx = match CONTEXT.read().unwrap().as_ref() {
+        Some(ctx) => ctx.clone(),
+        None => return,
+    };
+    let key = match target {
+        Target::Default => None,
+        Target::Key(key) | Target::Prefix(key) => Some(key.clone()),
+    };
+    let timestamp = Utc::now();
+
+    tokio::spawn(async move {
+        let deliveries = blocking({
+            let ctx = ctx.clone();
+            move || {
+                let conn = ctx.get_conn()?;
+                let webhooks: Vec<Webhook> = webhook::table
+                    .filter(webhook::enabled.eq(true))
+                    .filter(webhook::events.contains(vec![event as i32]))
+                    .load(&conn)?;
+                if webhooks.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let data = match prepare(&conn)? {
+                    Some(data) => data,
+                    None => return Ok(Vec::new()),
+                };
+                let payload = serde_json::to_string(&SiteEventBody {
+                    event,
+                    key: key.as_deref(),
+                    event_id,
+                    timestamp,
+                    data: &data,
+                })?;
+                create_deliveries(&conn, webhooks, event, payload)
+            }
+        })
+        .await;
+        let deliveries = match deliveries {
+            Ok(deliveries) => deliveries,
+            Err(err) => {
+                error!("Webhook: unable to create deliveries: {}", err);
+                return;
+            }
+        };
+        for (delivery, webhook) in deliveries {
+            attempt(ctx.clone(), delivery, webhook).await;
+        }
+    });
+}
+
+fn create_deliveries(
+    conn: &PgConnection,
+    webhooks: Vec<Webhook>,
+    event: WebhookEvent,
+    payload: String,
+) -> anyhow::Result<Vec<(WebhookDelivery, Webhook)>> {
+    let next_attempt = Utc::now() + *LEASE;
+    let values: Vec<_> = webhooks
+        .iter()
+        .map(|webhook| {
+            (
+                webhook_delivery::webhook_id.eq(webhook.id),
+                webhook_delivery::event.eq(event),
+                webhook_delivery::payload.eq(payload.clone()),
+                webhook_delivery::next_attempt.eq(next_attempt),
+            )
+        })
+        .collect();
+    let deliveries: Vec<WebhookDelivery> = diesel::insert_into(webhook_delivery::table)
+        .values(&values)
+        .get_results(conn)?;
+
+    Ok(deliveries.into_iter().zip(webhooks).collect())
+}
+
+/// Claims the pending deliveries due for retry, so that other instances skip them.
+fn claim_due(ctx: &GlobalCtx) -> anyhow::Result<Vec<(WebhookDelivery, Webhook)>> {
+    let conn = ctx.get_conn()?;
+    let now = Utc::now();
+
+    let deliveries: Vec<WebhookDelivery> = diesel::update(webhook_delivery::table)
+        .filter(webhook_delivery::status.eq(WebhookDeliveryStatus::Pending))
+        .filter(webhook_delivery::next_attempt.le(now))
+        .set(webhook_delivery::next_attempt.eq(now + *LEASE))
+        .get_results(&conn)?;
+    if deliveries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let webhook_ids: Vec<ID> = deliveries.iter().map(|d| d.webhook_id).collect();
+    let webhooks: Vec<Webhook> = webhook::table
+        .filter(webhook::id.eq_any(webhook_ids))
+        .load(&conn)?;
+
+    Ok(deliveries
+        .into_iter()
+        .filter_map(|delivery| {
+            webhooks
+                .iter()
+                .find(|webhook| webhook.id == delivery.webhook_id)
+                .map(|webhook| (delivery, webhook.clone()))
+        })
+        .collect())
+}
+
+/// Marks the delivery as pending again and attempts it in the background.
+pub fn redeliver(ctx: &GlobalCtx, id: ID) -> anyhow::Result<WebhookDelivery> {
+    let conn = ctx.get_conn()?;
+
+    let delivery: WebhookDelivery = diesel::update(webhook_delivery::table)
+        .filter(webhook_delivery::id.eq(id))
+        .set((
+            webhook_delivery::status.eq(WebhookDeliveryStatus::Pending),
+            webhook_delivery::attempts.eq(0),
+            webhook_delivery::next_attempt.eq(Utc::now() + *LEASE),
+        ))
+        .get_result(&conn)?;
+    let webhook: Webhook = webhook::table
+        .filter(webhook::id.eq(delivery.webhook_id))
+        .first(&conn)?;
+
+    tokio::spawn(attempt(ctx.clone(), delivery.clone(), webhook));
+
+    Ok(delivery)
+}
+
+/// Hex-encoded HMAC-SHA256 signature of the body.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::sign(&key, body)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Delay before retrying a delivery failed the given times, doubled for each further
+/// attempt.
+fn backoff(delay: chrono::Duration, attempts: i32) -> chrono::Duration {
+    delay * 2i32.pow((attempts - 1).clamp(0, 16) as u32)
+}
+
+/// Posts the delivery and records the outcome.
+async fn attempt(ctx: GlobalCtx, delivery: WebhookDelivery, webhook: Webhook) {
+    let result = CLIENT
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-CINDY-EVENT", delivery.event.topic())
+        .header("X-CINDY-DELIVERY", delivery.id.to_string())
+        .header(
+            "X-CINDY-SIGNATURE",
+            format!(
+                "sha256={}",
+                sign(&webhook.secret, delivery.payload.as_bytes())
+            ),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+
+    let id = delivery.id;
+    let error = result.err().map(|err| err.to_string());
+    if let Err(err) = blocking(move || record(&ctx, &delivery, error)).await {
+        error!("Webhook: unable to update delivery {}: {}", id, err);
+    }
+}
+
+fn record(
+    ctx: &GlobalCtx,
+    delivery: &WebhookDelivery,
+    error: Option<String>,
+) -> anyhow::Result<()> {
+    let conn = ctx.get_conn()?;
+    let now = Utc::now();
+    let attempts = delivery.attempts + 1;
+    let target = webhook_delivery::table.filter(webhook_delivery::id.eq(delivery.id));
+
+    match error {
+        None => {
+            diesel::update(target)
+                .set((
+                    webhook_delivery::status.eq(WebhookDeliveryStatus::Delivered),
+                    webhook_delivery::attempts.eq(attempts),
+                    webhook_delivery::last_error.eq(None::<String>),
+                    webhook_delivery::delivered.eq(now),
+                ))
+                .execute(&conn)?;
+        }
+        Some(err) => {
+            let status = if attempts >= *MAX_ATTEMPTS {
+                warn!(
+                    "Webhook: delivery {} to {} is dead: {}",
+                    delivery.id, delivery.webhook_id, err
+                );
+                WebhookDeliveryStatus::Dead
+            } else {
+                WebhookDeliveryStatus::Pending
+            };
+            diesel::update(target)
+                .set((
+                    webhook_delivery::status.eq(status),
+                    webhook_delivery::attempts.eq(attempts),
+                    webhook_delivery::last_error.eq(err),
+                    webhook_delivery::next_attempt.eq(now + backoff(*RETRY_DELAY, attempts)),
+                ))
+                .execute(&conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(sign("other", b"{}"), sign("secret", b"{}"));
+    }
+
+    #[test]
+    fn backoff_doubles_the_delay() {
+        let delay = chrono::Duration::seconds(30);
+
+        assert_eq!(backoff(delay, 1), chrono::Duration::seconds(30));
+        assert_eq!(backoff(delay, 2), chrono::Duration::seconds(60));
+        assert_eq!(backoff(delay, 5), chrono::Duration::seconds(480));
+        assert_eq!(backoff(delay, 0), delay);
+        // Capped so that it does not overflow
+        assert_eq!(backoff(delay, 100), delay * 65536);
+    }
+
+    #[test]
+    fn chatmessage_events_are_keyed_by_chatroom() {
+        assert_eq!(
+            chatroom_id(&Target::Key("chatroom<12>".to_string())),
+            Some(12)
+        );
+        assert_eq!(chatroom_id(&Target::Key("chatroom<x>".to_string())), None);
+        assert_eq!(chatroom_id(&Target::Key("puzzle<12>".to_string())), None);
+        assert_eq!(chatroom_id(&Target::Default), None);
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in &[
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(
+                !is_public(ip.parse().unwrap()),
+                "{} should not be public",
+                ip
+            );
+        }
+        for ip in &["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn webhook_urls_to_internal_hosts_are_rejected() {
+        for url in &[
+            "http://127.0.0.1:8000/graphql",
+            "http://[::1]/",
+            "http://169.254.169.254/latest/meta-data/",
+            "https://10.0.0.1/hook",
+            "ftp://93.184.216.34/",
+            "not a url",
+        ] {
+            assert!(
+                resolve_public(url).await.is_err(),
+                "{} should be rejected",
+                url
+            );
+        }
+
+        let (host, addr) = resolve_public("https://93.184.216.34/hook").await.unwrap();
+        assert_eq!(host, "93.184.216.34");
+        assert_eq!(addr, "93.184.216.34:443".parse().unwrap());
+    }
+}